@@ -4,6 +4,7 @@
 use crate::utils::Utils;
 use figment::Figment;
 use figment::providers::{Format, Serialized, Toml};
+use rocket::data::ByteUnit;
 use rocket::serde::{Deserialize, Serialize};
 use std::fs;
 use std::fs::File;
@@ -21,11 +22,75 @@ pub struct Config {
     pub github_client_id: String,
     pub github_client_secret: String,
     pub github_redirect_url: String,
+    pub gitlab_client_id: String,
+    pub gitlab_client_secret: String,
+    pub gitlab_redirect_url: String,
+    /// Base URL of the GitLab instance; `https://gitlab.com` if empty.
+    pub gitlab_url: String,
+    pub microsoft_client_id: String,
+    pub microsoft_client_secret: String,
+    pub microsoft_redirect_url: String,
+    /// The Microsoft Entra tenant users sign in to; `common` if empty.
+    pub microsoft_tenant: String,
+    pub google_client_id: String,
+    pub google_client_secret: String,
+    pub google_redirect_url: String,
     pub tls_cert_path: String,
     pub tls_key_path: String,
+    pub max_upload_size: String,
+    pub upload_dir: String,
+    pub upload_expiry_hours: u64,
+    pub blob_backend: String,
+    pub blob_dir: String,
+    pub s3_endpoint: String,
+    pub s3_bucket: String,
+    pub s3_region: String,
+    pub s3_access_key: String,
+    pub s3_secret_key: String,
+    pub admins: Vec<String>,
+    pub lock_timeout_minutes: u64,
+    pub trash_retention_days: u64,
+    pub session_lifetime_hours: u64,
+    /// Whether anyone may register a local account; otherwise only admins create them.
+    pub allow_registration: bool,
+    /// Shortest password accepted for local accounts; 12 if 0.
+    pub min_password_length: u64,
+    /// OpenID Connect providers users may sign in with besides GitHub.
+    pub oidc_providers: Vec<OidcProvider>,
+}
+
+/// An OpenID Connect identity provider, configured as an `[[oidc_providers]]` table.
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct OidcProvider {
+    /// Short name used in the login URL, `/auth/oidc/<name>/login`; must differ from the
+    /// built-in providers `github`, `gitlab`, `microsoft` and `google`.
+    pub name: String,
+    /// The issuer; its metadata is discovered at `<issuer>/.well-known/openid-configuration`.
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// The callback URL registered with the provider, ending in `/auth/oidc/<name>/callback`.
+    pub redirect_url: String,
+    /// Scopes requested besides `openid`, e.g. `profile` and `email`.
+    pub scopes: Vec<String>,
+    /// Claim mapped to the login; `preferred_username` if empty.
+    pub login_claim: String,
+    /// Claim mapped to the display name; `name` if empty.
+    pub name_claim: String,
+    /// Claim mapped to the email address; `email` if empty.
+    pub email_claim: String,
+    /// Claim mapped to the avatar URL; `picture` if empty.
+    pub avatar_claim: String,
 }
 
 impl Config {
+    /// Upload size limit used when `max_upload_size` is empty or invalid.
+    pub const DEFAULT_MAX_UPLOAD_SIZE: ByteUnit = ByteUnit::Gibibyte(2);
+
+    /// Names of the login providers built into the application.
+    pub const BUILT_IN_PROVIDERS: [&str; 4] = ["github", "gitlab", "microsoft", "google"];
+
     /// Creates a new instance of `AppConfig` with default values.
     ///
     /// # Returns
@@ -58,6 +123,143 @@ impl Config {
             })
     }
 
+    /// Returns the maximum size of an uploaded model.
+    ///
+    /// # Returns
+    /// The parsed `max_upload_size` (e.g. "512 MiB"), or the default of 2 GiB.
+    pub fn max_upload_size(&self) -> ByteUnit {
+        self.max_upload_size
+            .parse()
+            .unwrap_or(Self::DEFAULT_MAX_UPLOAD_SIZE)
+    }
+
+    /// Returns the directory where partial resumable uploads are stored.
+    ///
+    /// # Returns
+    /// The configured `upload_dir`, or `xbim-uploads` in the system temporary directory.
+    pub fn upload_dir(&self) -> PathBuf {
+        if self.upload_dir.is_empty() {
+            std::env::temp_dir().join("xbim-uploads")
+        } else {
+            PathBuf::from(&self.upload_dir)
+        }
+    }
+
+    /// Returns the directory of the filesystem blob store.
+    ///
+    /// # Returns
+    /// The configured `blob_dir`, or `blobs` next to the executable.
+    pub fn blob_dir(&self) -> PathBuf {
+        if self.blob_dir.is_empty() {
+            Utils::get_exec_path("blobs")
+        } else {
+            PathBuf::from(&self.blob_dir)
+        }
+    }
+
+    /// Returns how long an idle resumable upload is kept before it expires.
+    ///
+    /// # Returns
+    /// The configured `upload_expiry_hours`, or 24 hours if unset.
+    pub fn upload_expiry(&self) -> chrono::Duration {
+        chrono::Duration::hours(match self.upload_expiry_hours {
+            0 => 24,
+            hours => hours as i64,
+        })
+    }
+
+    /// Returns how long a model lock is held when the client does not ask for a duration.
+    ///
+    /// # Returns
+    /// The configured `lock_timeout_minutes`, or 60 minutes if unset.
+    pub fn lock_timeout(&self) -> chrono::Duration {
+        chrono::Duration::minutes(match self.lock_timeout_minutes {
+            0 => 60,
+            minutes => minutes as i64,
+        })
+    }
+
+    /// Returns how long a deleted model stays in the trash before it is purged.
+    ///
+    /// # Returns
+    /// The configured `trash_retention_days`, or 30 days if unset.
+    pub fn trash_retention(&self) -> chrono::Duration {
+        chrono::Duration::days(match self.trash_retention_days {
+            0 => 30,
+            days => days as i64,
+        })
+    }
+
+    /// Returns how long a sign-in session lasts.
+    ///
+    /// # Returns
+    /// The configured `session_lifetime_hours`, or 24 hours if unset.
+    pub fn session_lifetime(&self) -> chrono::Duration {
+        chrono::Duration::hours(match self.session_lifetime_hours {
+            0 => 24,
+            hours => hours as i64,
+        })
+    }
+
+    /// Returns the shortest password accepted for local accounts.
+    ///
+    /// # Returns
+    /// The configured `min_password_length`, or 12 characters if unset.
+    pub fn min_password_length(&self) -> usize {
+        match self.min_password_length {
+            0 => 12,
+            length => length as usize,
+        }
+    }
+
+    /// Returns the OpenID Connect provider with the given name.
+    ///
+    /// Providers named like a built-in provider are ignored, as their accounts would be
+    /// mistaken for accounts of the built-in one.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the provider.
+    pub fn oidc_provider(&self, name: &str) -> Option<&OidcProvider> {
+        if Self::BUILT_IN_PROVIDERS.contains(&name) {
+            return None;
+        }
+        self.oidc_providers
+            .iter()
+            .find(|provider| provider.name == name)
+    }
+
+    /// Returns the base URL of the GitLab instance users sign in with.
+    ///
+    /// # Returns
+    /// The configured `gitlab_url` without trailing slash, or `https://gitlab.com`.
+    pub fn gitlab_url(&self) -> &str {
+        match self.gitlab_url.trim_end_matches('/') {
+            "" => "https://gitlab.com",
+            url => url,
+        }
+    }
+
+    /// Returns the Microsoft Entra tenant users sign in to.
+    ///
+    /// # Returns
+    /// The configured `microsoft_tenant`, or `common` for work, school and personal accounts.
+    pub fn microsoft_tenant(&self) -> &str {
+        match self.microsoft_tenant.as_str() {
+            "" => "common",
+            tenant => tenant,
+        }
+    }
+
+    /// Returns `true` if the login is listed in `admins`.
+    ///
+    /// # Arguments
+    /// * `login` - The login to check.
+    pub fn is_admin(&self, login: &str) -> bool {
+        self.admins
+            .iter()
+            .any(|admin| admin.eq_ignore_ascii_case(login))
+    }
+
     /// Saves the current configuration to a file.
     ///
     /// # Arguments
@@ -3,7 +3,9 @@
 
 use crate::config::Config;
 use crate::utils::Utils;
+use chrono::{DateTime, SecondsFormat, Utc};
 use rocket::serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use surrealdb::{
     Error, Surreal,
     engine::remote::ws::{Client, Ws},
@@ -12,6 +14,137 @@ use surrealdb::{
     sql::Uuid,
 };
 
+/// A condition on the records returned by [`Database::list`].
+pub enum Filter {
+    /// The field equals the value.
+    Equals(&'static str, Value),
+    /// The field is an object holding every entry of the map.
+    Includes(&'static str, Map<String, Value>),
+    /// The field links to the record with the given key in the table.
+    Links(&'static str, &'static str, String),
+    /// The field is the `column` of a record in `table` whose `login` is the login.
+    Listed {
+        field: &'static str,
+        table: &'static str,
+        column: &'static str,
+        login: String,
+    },
+    /// The field holds no value.
+    Unset(&'static str),
+    /// The field holds a value.
+    Set(&'static str),
+    /// At least one of the filters holds.
+    Any(Vec<Filter>),
+}
+
+impl Filter {
+    /// Renders the filter as a SurrealQL condition, adding its parameters to `bindings`.
+    ///
+    /// # Arguments
+    /// * `bindings` - The query parameters; parameters are named `filter0`, `filter1`, ...
+    /// * `count` - The number of filter parameters bound so far.
+    ///
+    /// # Returns
+    /// The condition.
+    fn to_condition(&self, bindings: &mut Map<String, Value>, count: &mut usize) -> String {
+        let mut bind = |value: Value| {
+            let param = format!("filter{count}");
+            *count += 1;
+            bindings.insert(param.clone(), value);
+            param
+        };
+        match self {
+            Filter::Equals(field, value) => {
+                format!("{field} = ${}", bind(value.clone()))
+            }
+            Filter::Includes(field, entries) => {
+                let param = bind(Value::Object(entries.clone()));
+                format!("object::entries(${param}) ALLINSIDE object::entries({field})")
+            }
+            Filter::Links(field, table, key) => {
+                let param = bind(Value::from(key.as_str()));
+                format!("{field} = type::thing('{table}', ${param})")
+            }
+            Filter::Listed {
+                field,
+                table,
+                column,
+                login,
+            } => {
+                let param = bind(Value::from(login.as_str()));
+                format!("{field} IN (SELECT VALUE {column} FROM {table} WHERE login = ${param})")
+            }
+            Filter::Unset(field) => format!("{field} IS NONE"),
+            Filter::Set(field) => format!("{field} IS NOT NONE"),
+            Filter::Any(filters) => {
+                let conditions: Vec<String> = filters
+                    .iter()
+                    .map(|filter| filter.to_condition(bindings, count))
+                    .collect();
+                format!("({})", conditions.join(" OR "))
+            }
+        }
+    }
+}
+
+/// A page of records to list, ordered by a datetime field and then by record ID.
+pub struct Listing {
+    pub filters: Vec<Filter>,
+    /// The datetime field to order by.
+    pub sort: &'static str,
+    pub descending: bool,
+    /// Sort value and record ID of the last record of the previous page.
+    pub after: Option<(DateTime<Utc>, String)>,
+    pub limit: usize,
+}
+
+impl Listing {
+    /// Builds the SurrealQL statement and bindings selecting this page from a table.
+    ///
+    /// # Arguments
+    /// * `table` - The table to list.
+    ///
+    /// # Returns
+    /// The query and its parameters.
+    fn to_query(&self, table: &str) -> (String, Map<String, Value>) {
+        let mut bindings = Map::new();
+        bindings.insert(String::from("table"), Value::from(table));
+        bindings.insert(String::from("limit"), Value::from(self.limit));
+        let mut count = 0;
+        let mut conditions: Vec<String> = self
+            .filters
+            .iter()
+            .map(|filter| filter.to_condition(&mut bindings, &mut count))
+            .collect();
+        let sort = format!("<datetime> {}", self.sort);
+        let (direction, beyond) = if self.descending {
+            ("DESC", "<")
+        } else {
+            ("ASC", ">")
+        };
+        if let Some((at, id)) = &self.after {
+            conditions.push(format!(
+                "({sort} {beyond} <datetime> $after OR ({sort} = <datetime> $after AND id {beyond} type::thing($table, $after_id)))"
+            ));
+            bindings.insert(
+                String::from("after"),
+                Value::from(at.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+            );
+            bindings.insert(String::from("after_id"), Value::from(id.as_str()));
+        }
+        let mut sql = format!("SELECT *, {sort} AS sort_key FROM type::table($table)");
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(&format!(
+            " ORDER BY sort_key {direction}, id {direction} LIMIT $limit"
+        ));
+        (sql, bindings)
+    }
+}
+
+#[derive(Clone)]
 pub struct Database {
     pub client: Surreal<Client>,
     pub session_token: Uuid,
@@ -118,6 +251,107 @@ impl Database {
             .ok_or_else(|| Error::Api(Api::ParseError(String::from("Failed to update record"))))
     }
 
+    /// Updates a record only if it still matches a condition, so that concurrent writers
+    /// cannot overwrite each other's changes.
+    ///
+    /// # Arguments
+    /// * `table` - The table name where the record is stored.
+    /// * `id` - The ID of the record to update.
+    /// * `data` - The updated data.
+    /// * `condition` - The SurrealQL condition the stored record must match.
+    /// * `followup` - A statement run in the same transaction after the update, such as
+    ///   numbering the record; its result is returned instead. It must only change the
+    ///   record if the update matched.
+    /// * `bindings` - The parameters of the condition, e.g. `("revision", 3)`.
+    ///
+    /// # Returns
+    /// A `Result` containing the updated record, or `None` if the record did not match.
+    pub async fn update_where<T>(
+        &self,
+        table: &str,
+        id: &str,
+        data: T,
+        condition: &str,
+        followup: Option<&str>,
+        bindings: impl Serialize + 'static,
+    ) -> Result<Option<T>, Error>
+    where
+        T: Serialize + for<'a> Deserialize<'a> + 'static,
+    {
+        let update = format!("UPDATE type::thing($table, $id) CONTENT $data WHERE {condition}");
+        let (sql, statement) = match followup {
+            Some(followup) => (
+                format!("BEGIN TRANSACTION; {update}; {followup}; COMMIT TRANSACTION;"),
+                1,
+            ),
+            None => (update, 0),
+        };
+        self.client
+            .query(sql)
+            .bind(("table", table.to_string()))
+            .bind(("id", id.to_string()))
+            .bind(("data", data))
+            .bind(bindings)
+            .await?
+            .take(statement)
+    }
+
+    /// Creates or replaces a record in the specified table.
+    ///
+    /// # Arguments
+    /// * `table` - The table name where the record is stored.
+    /// * `id` - The ID of the record to create or replace.
+    /// * `data` - The record data.
+    ///
+    /// # Returns
+    /// A `Result` containing the stored record.
+    pub async fn upsert<T>(&self, table: &str, id: &str, data: T) -> Result<T, Error>
+    where
+        T: Serialize + for<'a> Deserialize<'a> + 'static,
+    {
+        self.client
+            .upsert((table, id))
+            .content(data)
+            .await?
+            .take()
+            .ok_or_else(|| Error::Api(Api::ParseError(String::from("Failed to upsert record"))))
+    }
+
+    /// Runs a SurrealQL query and returns the records of its first statement.
+    ///
+    /// # Arguments
+    /// * `sql` - The query to run.
+    /// * `bindings` - The query parameters, e.g. `("table", "users")` or a map.
+    ///
+    /// # Returns
+    /// A `Result` containing the selected records.
+    pub async fn query<T>(
+        &self,
+        sql: &str,
+        bindings: impl Serialize + 'static,
+    ) -> Result<Vec<T>, Error>
+    where
+        T: for<'a> Deserialize<'a> + 'static,
+    {
+        self.client.query(sql).bind(bindings).await?.take(0)
+    }
+
+    /// Lists one page of the records in a table.
+    ///
+    /// # Arguments
+    /// * `table` - The table name to list.
+    /// * `listing` - The filters, ordering and position of the page.
+    ///
+    /// # Returns
+    /// A `Result` containing at most `listing.limit` records.
+    pub async fn list<T>(&self, table: &str, listing: &Listing) -> Result<Vec<T>, Error>
+    where
+        T: for<'a> Deserialize<'a> + 'static,
+    {
+        let (sql, bindings) = listing.to_query(table);
+        self.query(&sql, bindings).await
+    }
+
     /// Deletes a record from the specified table.
     ///
     /// # Arguments
@@ -134,3 +368,89 @@ impl Database {
         Ok(result.is_some())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_listing_query_first_page() {
+        let listing = Listing {
+            filters: vec![
+                Filter::Unset("deleted_at"),
+                Filter::Equals("name", Value::from("Office")),
+            ],
+            sort: "created_at",
+            descending: false,
+            after: None,
+            limit: 10,
+        };
+        let (sql, bindings) = listing.to_query("ifc_models");
+        assert_eq!(
+            sql,
+            "SELECT *, <datetime> created_at AS sort_key FROM type::table($table) \
+             WHERE deleted_at IS NONE AND name = $filter0 ORDER BY sort_key ASC, id ASC LIMIT $limit"
+        );
+        assert_eq!(bindings["table"], "ifc_models");
+        assert_eq!(bindings["filter0"], "Office");
+        assert_eq!(bindings["limit"], 10);
+    }
+
+    #[test]
+    fn test_listing_query_continues_after_cursor() {
+        let mut metadata = Map::new();
+        metadata.insert(String::from("schema"), Value::from("IFC4"));
+        let listing = Listing {
+            filters: vec![Filter::Includes("metadata", metadata)],
+            sort: "updated_at",
+            descending: true,
+            after: Some((
+                Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap(),
+                String::from("abc"),
+            )),
+            limit: 5,
+        };
+        let (sql, bindings) = listing.to_query("ifc_models");
+        assert_eq!(
+            sql,
+            "SELECT *, <datetime> updated_at AS sort_key FROM type::table($table) \
+             WHERE object::entries($filter0) ALLINSIDE object::entries(metadata) \
+             AND (<datetime> updated_at < <datetime> $after OR (<datetime> updated_at = <datetime> $after \
+             AND id < type::thing($table, $after_id))) ORDER BY sort_key DESC, id DESC LIMIT $limit"
+        );
+        assert_eq!(bindings["filter0"]["schema"], "IFC4");
+        assert_eq!(bindings["after"], "2025-03-01T12:00:00Z");
+        assert_eq!(bindings["after_id"], "abc");
+    }
+
+    #[test]
+    fn test_listing_query_combines_alternatives() {
+        let listing = Listing {
+            filters: vec![
+                Filter::Any(vec![
+                    Filter::Equals("owner", Value::from("octocat")),
+                    Filter::Listed {
+                        field: "id",
+                        table: "ifc_shares",
+                        column: "model",
+                        login: String::from("octocat"),
+                    },
+                ]),
+                Filter::Links("project", "projects", String::from("tower")),
+            ],
+            sort: "created_at",
+            descending: false,
+            after: None,
+            limit: 10,
+        };
+        let (sql, bindings) = listing.to_query("ifc_models");
+        assert!(sql.contains(
+            "WHERE (owner = $filter0 OR id IN (SELECT VALUE model FROM ifc_shares WHERE login = $filter1)) \
+             AND project = type::thing('projects', $filter2) ORDER BY"
+        ));
+        assert_eq!(bindings["filter0"], "octocat");
+        assert_eq!(bindings["filter1"], "octocat");
+        assert_eq!(bindings["filter2"], "tower");
+    }
+}
@@ -1,7 +1,10 @@
 #![warn(clippy::all)]
 #![forbid(unsafe_code)]
 
-use rocket::{Catcher, catch, catchers, http::Status, serde::Serialize, serde::json::Json};
+use crate::guards::scope::Denial;
+use rocket::{
+    Catcher, Request, catch, catchers, http::Status, serde::Serialize, serde::json::Json,
+};
 use rocket_governor::rocket_governor_catcher;
 
 #[derive(Serialize)]
@@ -22,6 +25,13 @@ pub fn catchers() -> Vec<Catcher> {
         err_403,
         err_404,
         err_405,
+        err_409,
+        err_410,
+        err_412,
+        err_413,
+        err_415,
+        err_422,
+        err_423,
         rocket_governor_catcher,
         err_500,
         err_503
@@ -44,11 +54,21 @@ fn err_401() -> Json<Response> {
     })
 }
 
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct Forbidden {
+    status: Status,
+    message: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
 #[catch(403)]
-fn err_403() -> Json<Response> {
-    Json(Response {
+fn err_403(request: &Request) -> Json<Forbidden> {
+    Json(Forbidden {
         status: Status::Forbidden,
         message: "Access forbidden - You don't have permission to access this resource",
+        reason: request.local_cache(Denial::default).0.clone(),
     })
 }
 
@@ -68,6 +88,62 @@ fn err_405() -> Json<Response> {
     })
 }
 
+#[catch(409)]
+fn err_409() -> Json<Response> {
+    Json(Response {
+        status: Status::Conflict,
+        message: "Request conflicts with the current state of the resource",
+    })
+}
+
+#[catch(410)]
+fn err_410() -> Json<Response> {
+    Json(Response {
+        status: Status::Gone,
+        message: "Resource has expired or is no longer available",
+    })
+}
+
+#[catch(412)]
+fn err_412() -> Json<Response> {
+    Json(Response {
+        status: Status::PreconditionFailed,
+        message: "Resource has changed since it was last retrieved",
+    })
+}
+
+#[catch(413)]
+fn err_413() -> Json<Response> {
+    Json(Response {
+        status: Status::PayloadTooLarge,
+        message: "Payload exceeds the maximum upload size",
+    })
+}
+
+#[catch(415)]
+fn err_415() -> Json<Response> {
+    Json(Response {
+        status: Status::UnsupportedMediaType,
+        message: "Unsupported media type or file format",
+    })
+}
+
+#[catch(422)]
+fn err_422() -> Json<Response> {
+    Json(Response {
+        status: Status::UnprocessableEntity,
+        message: "The request was well-formed but could not be processed",
+    })
+}
+
+#[catch(423)]
+fn err_423() -> Json<Response> {
+    Json(Response {
+        status: Status::Locked,
+        message: "Resource is locked by another user",
+    })
+}
+
 #[catch(500)]
 fn err_500() -> Json<Response> {
     Json(Response {
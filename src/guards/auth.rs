@@ -1,42 +1,265 @@
 #![warn(clippy::all)]
 #![forbid(unsafe_code)]
 
-use crate::routes::github::GitHubUser;
+use crate::config::Config;
+use crate::database::Database;
+use crate::guards::client::ClientInfo;
+use crate::models::{
+    session::Session,
+    token::{AccessToken, Scope},
+    user::User,
+};
+use crate::utils::Utils;
+use chrono::Utc;
 use rocket::{
     Request, async_trait,
-    http::Status,
+    http::{Cookie, CookieJar, SameSite, Status},
     request::{FromRequest, Outcome},
-    serde::json::from_str,
 };
+use serde_json::json;
+use surrealdb::sql::Thing;
+
+/// Name of the private cookie holding the session id.
+pub const SESSION_COOKIE: &str = "user_session";
+
+/// How a request was authenticated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Credential {
+    /// A browser session, by its id.
+    Session(String),
+    /// A personal access token, by its record key, with the scopes it grants.
+    Token { id: String, scopes: Vec<Scope> },
+}
 
 /// Authentication Guard
-pub struct AuthGuard;
+///
+/// Accepts the session cookie set on sign-in, or a personal access token in an
+/// `Authorization: Bearer` header.
+pub struct AuthGuard {
+    /// The signed-in user.
+    pub user: User,
+    /// The session or token the request was made with.
+    pub credential: Credential,
+    /// Whether the user is listed in `admins` of the configuration; tokens also need
+    /// the `admin` scope.
+    pub admin: bool,
+}
+
+impl AuthGuard {
+    /// Returns the id of the session the request was made with, if any.
+    pub fn session(&self) -> Option<&str> {
+        match &self.credential {
+            Credential::Session(key) => Some(key),
+            Credential::Token { .. } => None,
+        }
+    }
+
+    /// Returns `true` if the caller holds a scope: sessions hold every scope but `admin`,
+    /// which only admins hold, and access tokens the scopes they were created with.
+    ///
+    /// # Arguments
+    /// * `scope` - The scope to check.
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        match (&self.credential, scope) {
+            (_, Scope::Admin) => self.admin,
+            (Credential::Session(_), _) => true,
+            (Credential::Token { scopes, .. }, scope) => scopes.contains(&scope),
+        }
+    }
+
+    /// Resolves the session a cookie refers to, updating when it was last seen.
+    ///
+    /// # Arguments
+    /// * `database` - The database instance.
+    /// * `key` - The session id from the cookie.
+    ///
+    /// # Returns
+    /// The signed-in user, or `None` for unknown or expired sessions.
+    pub async fn authenticate(database: &Database, key: &str) -> Option<User> {
+        let session = database
+            .read::<Session>("sessions", key)
+            .await
+            .ok()
+            .filter(Session::is_active)?;
+        let user = database
+            .read::<User>("users", &session.user.id.to_raw())
+            .await
+            .ok()?;
+        let now = Utc::now();
+        if session.is_stale(now)
+            && let Err(e) = database
+                .query::<Session>(
+                    "UPDATE type::thing('sessions', $session) SET last_seen_at = $now",
+                    json!({ "session": key, "now": now }),
+                )
+                .await
+        {
+            println!("Error updating session of {}: {e:?}", user.login);
+        }
+        Some(user)
+    }
+
+    /// Resolves a personal access token, updating when it was last used.
+    ///
+    /// # Arguments
+    /// * `database` - The database instance.
+    /// * `config` - The application configuration.
+    /// * `bearer` - The token from the `Authorization` header.
+    ///
+    /// # Returns
+    /// The user and the token, or `None` for unknown, forged or expired tokens.
+    async fn authenticate_token(
+        database: &Database,
+        config: &Config,
+        bearer: &str,
+    ) -> Option<(User, AccessToken)> {
+        let (key, secret) = AccessToken::parse(bearer)?;
+        let token = database
+            .read::<AccessToken>("access_tokens", key)
+            .await
+            .ok()
+            .filter(|token| {
+                token.is_active()
+                    && Utils::verify_signature(&config.secret_key, secret, &token.secret_hash)
+            })?;
+        let user = database
+            .read::<User>("users", &token.user.id.to_raw())
+            .await
+            .ok()?;
+        let now = Utc::now();
+        if token.is_stale(now)
+            && let Err(e) = database
+                .query::<AccessToken>(
+                    "UPDATE type::thing('access_tokens', $token) SET last_used_at = $now",
+                    json!({ "token": key, "now": now }),
+                )
+                .await
+        {
+            println!("Error updating access token of {}: {e:?}", user.login);
+        }
+        Some((user, token))
+    }
+}
 
 #[async_trait]
 impl<'r> FromRequest<'r> for AuthGuard {
     type Error = ();
 
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        request
-            .cookies()
-            .get_private("user_session")
-            .and_then(|cookie| {
-                from_str::<GitHubUser>(cookie.value())
-                    .map(|_| AuthGuard)
-                    .ok()
-            })
-            .map(Outcome::Success)
-            .unwrap_or(Outcome::Error((Status::Unauthorized, ())))
+        let config = request.rocket().state::<Config>();
+        let is_admin = |user: &User| config.is_some_and(|config| config.is_admin(&user.login));
+        if let Some(authorization) = request.headers().get_one("Authorization") {
+            let (Some(bearer), Some(database), Some(config)) = (
+                authorization.strip_prefix("Bearer "),
+                request.rocket().state::<Database>(),
+                config,
+            ) else {
+                return Outcome::Error((Status::Unauthorized, ()));
+            };
+            return match Self::authenticate_token(database, config, bearer.trim()).await {
+                Some((user, token)) => Outcome::Success(AuthGuard {
+                    admin: is_admin(&user) && token.allows(Scope::Admin),
+                    user,
+                    credential: Credential::Token {
+                        id: token.id.map(|id| id.id.to_raw()).unwrap_or_default(),
+                        scopes: token.scopes,
+                    },
+                }),
+                None => Outcome::Error((Status::Unauthorized, ())),
+            };
+        }
+        let Some(cookie) = request.cookies().get_private(SESSION_COOKIE) else {
+            return Outcome::Error((Status::Unauthorized, ()));
+        };
+        let Some(database) = request.rocket().state::<Database>() else {
+            return Outcome::Error((Status::Unauthorized, ()));
+        };
+        match Self::authenticate(database, cookie.value()).await {
+            Some(user) => Outcome::Success(AuthGuard {
+                admin: is_admin(&user),
+                user,
+                credential: Credential::Session(cookie.value().to_string()),
+            }),
+            None => {
+                request.cookies().remove_private(SESSION_COOKIE);
+                Outcome::Error((Status::Unauthorized, ()))
+            }
+        }
     }
 }
 
+/// Signs a user in: stores a new session and sets the session cookie.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `config` - The application configuration.
+/// * `cookies` - The cookie jar of the response.
+/// * `user` - The user record.
+/// * `client` - The client signing in.
+///
+/// # Returns
+/// The stored session, or the database error.
+pub async fn start_session(
+    database: &Database,
+    config: &Config,
+    cookies: &CookieJar<'_>,
+    user: Thing,
+    client: ClientInfo,
+) -> Result<Session, surrealdb::Error> {
+    let key = Utils::random_token(32);
+    let now = Utc::now();
+    let lifetime = config.session_lifetime();
+    let session = database
+        .create(
+            "sessions",
+            Session {
+                id: Some(Thing::from(("sessions", key.as_str()))),
+                user,
+                ip: client.ip,
+                user_agent: client.user_agent,
+                created_at: now,
+                last_seen_at: now,
+                expires_at: now + lifetime,
+            },
+        )
+        .await?;
+    cookies.add_private(
+        Cookie::build((SESSION_COOKIE, key))
+            .same_site(SameSite::Lax)
+            .http_only(true)
+            .max_age(rocket::time::Duration::seconds(lifetime.num_seconds()))
+            .build(),
+    );
+    Ok(session)
+}
+
+/// Removes every session whose expiry time has passed.
+///
+/// # Arguments
+/// * `database` - The database instance.
+///
+/// # Returns
+/// The number of removed sessions.
+pub async fn purge_expired_sessions(database: &Database) -> usize {
+    database
+        .query::<Session>(
+            "DELETE sessions WHERE <datetime> expires_at <= $now RETURN BEFORE",
+            ("now", surrealdb::sql::Datetime::from(Utc::now())),
+        )
+        .await
+        .map(|expired| expired.len())
+        .unwrap_or_else(|e| {
+            println!("Error removing expired sessions: {e:?}");
+            0
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rocket::http::Cookie;
+    use rocket::http::Header;
     use rocket::local::asynchronous::Client;
     use rocket::{Build, Rocket, get, routes, tokio};
-    use serde_json::json;
 
     #[get("/protected")]
     fn test_endpoint(_auth: AuthGuard) -> &'static str {
@@ -48,7 +271,7 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_auth_guard_success() {
+    async fn test_auth_guard_rejects_user_json_cookie() {
         let client = Client::tracked(rocket_test())
             .await
             .expect("valid rocket instance");
@@ -60,7 +283,7 @@ mod tests {
         })
         .to_string();
 
-        let cookie = Cookie::new("user_session", user_json);
+        let cookie = Cookie::new(SESSION_COOKIE, user_json);
 
         let response = client
             .get("/protected")
@@ -68,7 +291,7 @@ mod tests {
             .dispatch()
             .await;
 
-        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.status(), Status::Unauthorized);
     }
 
     #[tokio::test]
@@ -82,13 +305,28 @@ mod tests {
         assert_eq!(response.status(), Status::Unauthorized);
     }
 
+    #[tokio::test]
+    async fn test_auth_guard_unauthorized_invalid_bearer() {
+        let client = Client::tracked(rocket_test())
+            .await
+            .expect("valid rocket instance");
+
+        let response = client
+            .get("/protected")
+            .header(Header::new("Authorization", "Basic dXNlcjpwYXNz"))
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::Unauthorized);
+    }
+
     #[tokio::test]
     async fn test_auth_guard_invalid_cookie_value() {
         let client = Client::tracked(rocket_test())
             .await
             .expect("valid rocket instance");
 
-        let invalid_cookie = Cookie::new("user_session", "not_valid_json");
+        let invalid_cookie = Cookie::new(SESSION_COOKIE, "not_valid_json");
 
         let response = client
             .get("/protected")
@@ -0,0 +1,170 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use rocket::{
+    Request, Response, async_trait,
+    http::Status,
+    request::{FromRequest, Outcome},
+    response::{self, Responder},
+};
+
+/// Precondition Guard carrying the `If-Match` and `If-None-Match` request headers.
+pub struct Preconditions {
+    if_match: Option<Vec<String>>,
+    if_none_match: Option<Vec<String>>,
+}
+
+/// Splits a comma separated list of entity tags.
+fn tags(header: Option<&str>) -> Option<Vec<String>> {
+    header.map(|value| {
+        value
+            .split(',')
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect()
+    })
+}
+
+/// Returns an entity tag without its weakness indicator.
+fn opaque(tag: &str) -> &str {
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
+
+impl Preconditions {
+    /// Checks `If-Match` against the current entity tag of a resource.
+    ///
+    /// # Arguments
+    /// * `etag` - The current entity tag, including quotes.
+    ///
+    /// # Returns
+    /// `Ok(())` if the header is absent or matches, otherwise 412 Precondition Failed.
+    pub fn check_match(&self, etag: &str) -> Result<(), Status> {
+        match &self.if_match {
+            // Strong comparison: weak tags never match.
+            Some(tags) if !tags.iter().any(|tag| tag == "*" || tag == etag) => {
+                println!("If-Match {tags:?} does not match {etag}");
+                Err(Status::PreconditionFailed)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns `true` if `If-None-Match` matches the current entity tag of a resource.
+    ///
+    /// # Arguments
+    /// * `etag` - The current entity tag, including quotes.
+    pub fn is_not_modified(&self, etag: &str) -> bool {
+        self.if_none_match.as_ref().is_some_and(|tags| {
+            tags.iter()
+                .any(|tag| tag == "*" || opaque(tag) == opaque(etag))
+        })
+    }
+}
+
+#[async_trait]
+impl<'r> FromRequest<'r> for Preconditions {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let headers = request.headers();
+        Outcome::Success(Preconditions {
+            if_match: tags(headers.get_one("If-Match")),
+            if_none_match: tags(headers.get_one("If-None-Match")),
+        })
+    }
+}
+
+/// A response carrying an `ETag` header, or an empty 304 Not Modified.
+pub struct Tagged<R> {
+    pub etag: String,
+    /// The response body, or `None` to answer 304 Not Modified.
+    pub body: Option<R>,
+}
+
+impl<'r, R: Responder<'r, 'static>> Responder<'r, 'static> for Tagged<R> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = match self.body {
+            Some(body) => body.respond_to(request)?,
+            None => Response::build().status(Status::NotModified).finalize(),
+        };
+        response.set_raw_header("ETag", self.etag);
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::http::Header;
+    use rocket::local::asynchronous::Client;
+    use rocket::{Build, Rocket, get, put, routes, tokio};
+
+    const ETAG: &str = "\"r7\"";
+
+    #[get("/resource")]
+    fn test_get(preconditions: Preconditions) -> Tagged<&'static str> {
+        Tagged {
+            etag: String::from(ETAG),
+            body: (!preconditions.is_not_modified(ETAG)).then_some("body"),
+        }
+    }
+
+    #[put("/resource")]
+    fn test_put(preconditions: Preconditions) -> Result<&'static str, Status> {
+        preconditions.check_match(ETAG).map(|_| "updated")
+    }
+
+    fn rocket_test() -> Rocket<Build> {
+        rocket::build().mount("/", routes![test_get, test_put])
+    }
+
+    #[tokio::test]
+    async fn test_get_sets_etag_and_honours_if_none_match() {
+        let client = Client::tracked(rocket_test())
+            .await
+            .expect("valid rocket instance");
+
+        let response = client.get("/resource").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("ETag"), Some(ETAG));
+
+        let response = client
+            .get("/resource")
+            .header(Header::new("If-None-Match", "\"r6\", W/\"r7\""))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::NotModified);
+        assert_eq!(response.headers().get_one("ETag"), Some(ETAG));
+    }
+
+    #[tokio::test]
+    async fn test_put_honours_if_match() {
+        let client = Client::tracked(rocket_test())
+            .await
+            .expect("valid rocket instance");
+
+        let unconditional = client.put("/resource").dispatch().await;
+        assert_eq!(unconditional.status(), Status::Ok);
+
+        let matching = client
+            .put("/resource")
+            .header(Header::new("If-Match", ETAG))
+            .dispatch()
+            .await;
+        assert_eq!(matching.status(), Status::Ok);
+
+        let stale = client
+            .put("/resource")
+            .header(Header::new("If-Match", "\"r6\""))
+            .dispatch()
+            .await;
+        assert_eq!(stale.status(), Status::PreconditionFailed);
+
+        let weak = client
+            .put("/resource")
+            .header(Header::new("If-Match", "W/\"r7\""))
+            .dispatch()
+            .await;
+        assert_eq!(weak.status(), Status::PreconditionFailed);
+    }
+}
@@ -0,0 +1,146 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::guards::auth::AuthGuard;
+use crate::models::token::Scope;
+use rocket::{
+    Request, async_trait,
+    http::Status,
+    request::{FromRequest, Outcome},
+};
+use std::{marker::PhantomData, ops::Deref};
+
+/// A scope required by a `ScopeGuard`.
+pub trait RequiredScope {
+    /// The scope the caller must hold.
+    const SCOPE: Scope;
+}
+
+/// Requires the `ifc:read` scope.
+pub struct IfcRead;
+
+/// Requires the `ifc:write` scope.
+pub struct IfcWrite;
+
+/// Requires the `admin` scope, held by configured admins only.
+pub struct Admin;
+
+impl RequiredScope for IfcRead {
+    const SCOPE: Scope = Scope::IfcRead;
+}
+
+impl RequiredScope for IfcWrite {
+    const SCOPE: Scope = Scope::IfcWrite;
+}
+
+impl RequiredScope for Admin {
+    const SCOPE: Scope = Scope::Admin;
+}
+
+/// Why a request was refused with 403 Forbidden, reported by the 403 catcher.
+#[derive(Clone, Debug, Default)]
+pub struct Denial(pub Option<String>);
+
+/// Authentication Guard that also requires a scope.
+///
+/// Sessions hold every scope but `admin`, which is reserved for configured admins;
+/// access tokens hold the scopes they were created with.
+pub struct ScopeGuard<S: RequiredScope> {
+    auth: AuthGuard,
+    scope: PhantomData<S>,
+}
+
+impl<S: RequiredScope> Deref for ScopeGuard<S> {
+    type Target = AuthGuard;
+
+    fn deref(&self) -> &AuthGuard {
+        &self.auth
+    }
+}
+
+#[async_trait]
+impl<'r, S: RequiredScope> FromRequest<'r> for ScopeGuard<S> {
+    type Error = String;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let auth = match request.guard::<AuthGuard>().await {
+            Outcome::Success(auth) => auth,
+            Outcome::Error((status, ())) => {
+                return Outcome::Error((status, String::from("Authentication required")));
+            }
+            Outcome::Forward(status) => return Outcome::Forward(status),
+        };
+        if auth.has_scope(S::SCOPE) {
+            return Outcome::Success(ScopeGuard {
+                auth,
+                scope: PhantomData,
+            });
+        }
+        let reason = format!("Missing scope {}", S::SCOPE);
+        println!("Refused {} {}: {reason}", request.method(), request.uri());
+        request.local_cache(|| Denial(Some(reason.clone())));
+        Outcome::Error((Status::Forbidden, reason))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::guards::auth::Credential;
+    use crate::models::user::User;
+    use rocket::local::asynchronous::Client;
+    use rocket::{get, routes, tokio};
+
+    fn guard(credential: Credential, admin: bool) -> AuthGuard {
+        AuthGuard {
+            user: User {
+                id: None,
+                identities: Vec::new(),
+                login: String::from("octocat"),
+                name: None,
+                email: None,
+                avatar_url: None,
+                password_hash: None,
+            },
+            credential,
+            admin,
+        }
+    }
+
+    #[get("/write")]
+    fn write_endpoint(_auth: ScopeGuard<IfcWrite>) -> &'static str {
+        "Written!"
+    }
+
+    #[test]
+    fn test_sessions_and_tokens_hold_scopes_uniformly() {
+        let session = guard(Credential::Session(String::from("s")), false);
+        assert!(session.has_scope(Scope::IfcRead));
+        assert!(session.has_scope(Scope::IfcWrite));
+        assert!(!session.has_scope(Scope::Admin));
+
+        let token = guard(
+            Credential::Token {
+                id: String::from("t"),
+                scopes: vec![Scope::IfcRead],
+            },
+            false,
+        );
+        assert!(token.has_scope(Scope::IfcRead));
+        assert!(!token.has_scope(Scope::IfcWrite));
+
+        let admin = guard(Credential::Session(String::from("s")), true);
+        assert!(admin.has_scope(Scope::Admin));
+    }
+
+    #[tokio::test]
+    async fn test_scope_guard_requires_authentication() {
+        let client = Client::tracked(rocket::build().mount("/", routes![write_endpoint]))
+            .await
+            .expect("valid rocket instance");
+
+        let response = client.get("/write").dispatch().await;
+
+        assert_eq!(response.status(), Status::Unauthorized);
+    }
+}
@@ -0,0 +1,305 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::ifc::index::summarize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// Number of leading bytes inspected when detecting a file format.
+const SNIFF_LEN: usize = 4096;
+
+/// The serialization formats accepted for IFC models.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// ISO 10303-21 STEP physical file (`.ifc`).
+    Step,
+    /// ISO 10303-28 XML (`.ifcxml`).
+    Xml,
+    /// ZIP archive containing one of the above (`.ifczip`).
+    Zip,
+}
+
+impl Format {
+    /// Detects the format from the first bytes of a file.
+    ///
+    /// # Arguments
+    /// * `head` - The leading bytes of the file.
+    ///
+    /// # Returns
+    /// The detected format, or `None` if the bytes match no supported format.
+    pub fn detect(head: &[u8]) -> Option<Self> {
+        if head.starts_with(b"PK\x03\x04") {
+            return Some(Format::Zip);
+        }
+        let head = head.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(head);
+        let start = head.iter().position(|b| !b.is_ascii_whitespace())?;
+        let head = &head[start..];
+        if head.starts_with(b"ISO-10303-21") {
+            Some(Format::Step)
+        } else if head.starts_with(b"<") {
+            let text = String::from_utf8_lossy(head).to_ascii_lowercase();
+            (text.contains("ifcxml") || text.contains("iso_10303_28")).then_some(Format::Xml)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the name stored in the `ifc.format` metadata entry.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Format::Step => "step",
+            Format::Xml => "ifcxml",
+            Format::Zip => "ifczip",
+        }
+    }
+}
+
+/// An error encountered while ingesting an uploaded model.
+#[derive(Debug)]
+pub enum IngestError {
+    /// The file is not a STEP, ifcXML or ZIP file, or the archive holds no model.
+    Unsupported,
+    /// The decompressed model is larger than the allowed size.
+    TooLarge,
+    /// The file could not be read or decompressed.
+    Io(io::Error),
+}
+
+impl fmt::Display for IngestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IngestError::Unsupported => f.write_str("unsupported or empty model file"),
+            IngestError::TooLarge => f.write_str("decompressed model exceeds the size limit"),
+            IngestError::Io(e) => write!(f, "failed to read model file: {e}"),
+        }
+    }
+}
+
+impl From<io::Error> for IngestError {
+    fn from(error: io::Error) -> Self {
+        IngestError::Io(error)
+    }
+}
+
+impl From<zip::result::ZipError> for IngestError {
+    fn from(error: zip::result::ZipError) -> Self {
+        match error {
+            zip::result::ZipError::Io(e) => IngestError::Io(e),
+            e => IngestError::Io(io::Error::new(io::ErrorKind::InvalidData, e)),
+        }
+    }
+}
+
+/// Returns the path a model extracted from an uploaded archive is written to.
+///
+/// # Arguments
+/// * `upload` - The path of the uploaded file.
+pub fn extracted_path(upload: &Path) -> PathBuf {
+    upload.with_extension("model")
+}
+
+/// A decoded model ready to be stored.
+pub struct Ingested {
+    pub format: Format,
+    /// The model file: the upload itself, or the entry extracted from an archive.
+    pub path: PathBuf,
+    pub metadata: HashMap<String, String>,
+}
+
+/// Detects, decompresses and indexes an uploaded model file, reading it in a single
+/// pass rather than into memory.
+///
+/// # Arguments
+/// * `path` - The path of the uploaded file.
+/// * `limit` - The maximum size of the (decompressed) model in bytes.
+///
+/// # Returns
+/// A `Result` containing the path of the model file with its `ifc.*` metadata. An
+/// archive entry is extracted next to the upload, which the caller removes as well.
+pub fn ingest(path: &Path, limit: u64) -> Result<Ingested, IngestError> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let format = Format::detect(&read_head(&mut file)?).ok_or(IngestError::Unsupported)?;
+
+    let mut metadata = HashMap::from([
+        (String::from("ifc.format"), format.as_str().to_string()),
+        (String::from("ifc.size"), size.to_string()),
+    ]);
+    let (format, model_path) = match format {
+        Format::Zip => {
+            let model_path = extracted_path(path);
+            let (format, extracted) = unzip(file, &model_path, limit)?;
+            metadata.insert(String::from("ifc.format"), format.as_str().to_string());
+            metadata.insert(String::from("ifc.compression"), String::from("zip"));
+            metadata.insert(String::from("ifc.uncompressed_size"), extracted.to_string());
+            (format, model_path)
+        }
+        _ if size > limit => return Err(IngestError::TooLarge),
+        format => (format, path.to_path_buf()),
+    };
+
+    if let Err(e) = index(format, &model_path, &mut metadata) {
+        if model_path != path {
+            let _ = std::fs::remove_file(&model_path);
+        }
+        return Err(e);
+    }
+    Ok(Ingested {
+        format,
+        path: model_path,
+        metadata,
+    })
+}
+
+/// Adds the `ifc.*` metadata read from the contents of a model file.
+fn index(
+    format: Format,
+    path: &Path,
+    metadata: &mut HashMap<String, String>,
+) -> Result<(), IngestError> {
+    let mut model = File::open(path)?;
+    match format {
+        Format::Step => match summarize(BufReader::new(model)) {
+            Ok(summary) => metadata.extend(summary.to_metadata()),
+            Err(e) => println!("Error indexing uploaded IFC model: {e}"),
+        },
+        _ => {
+            if let Some(schema) = xml_schema(&read_head(&mut model)?) {
+                metadata.insert(String::from("ifc.schema"), schema.to_string());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads the leading bytes of a file used for format and schema detection.
+fn read_head(reader: impl Read) -> io::Result<Vec<u8>> {
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    reader.take(SNIFF_LEN as u64).read_to_end(&mut head)?;
+    Ok(head)
+}
+
+/// Extracts the first STEP or ifcXML model from a ZIP archive to `target`.
+///
+/// # Returns
+/// The format of the model and its decompressed size, or `TooLarge` if the model
+/// exceeds `limit` bytes.
+fn unzip(file: File, target: &Path, limit: u64) -> Result<(Format, u64), IngestError> {
+    let mut archive = zip::ZipArchive::new(BufReader::new(file))?;
+    let mut candidates: Vec<usize> = (0..archive.len()).collect();
+    // Prefer entries with a model extension, but fall back to sniffing every file.
+    candidates.sort_by_key(|&i| {
+        let name = archive
+            .name_for_index(i)
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        !(name.ends_with(".ifc") || name.ends_with(".ifcxml"))
+    });
+    for index in candidates {
+        let head = read_head(archive.by_index(index)?)?;
+        match Format::detect(&head) {
+            Some(format @ (Format::Step | Format::Xml)) => {
+                let entry = archive.by_index(index)?;
+                let written = io::copy(&mut entry.take(limit + 1), &mut File::create(target)?)?;
+                if written > limit {
+                    std::fs::remove_file(target)?;
+                    return Err(IngestError::TooLarge);
+                }
+                return Ok((format, written));
+            }
+            _ => continue,
+        }
+    }
+    Err(IngestError::Unsupported)
+}
+
+/// Reads the schema identifier from the namespace of an ifcXML document.
+fn xml_schema(head: &[u8]) -> Option<&'static str> {
+    let head = String::from_utf8_lossy(head).to_ascii_uppercase();
+    ["IFC4X3", "IFC4", "IFC2X3"]
+        .into_iter()
+        .find(|schema| head.contains(schema))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const STEP: &str = "ISO-10303-21;HEADER;FILE_SCHEMA(('IFC4'));ENDSEC;DATA;\n\
+        #1=IFCPROJECT('0proj000000000000000001',$,'Tower',$,$,$,$,$,$);\n\
+        ENDSEC;END-ISO-10303-21;";
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("xbim-format-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn test_detect_formats() {
+        assert_eq!(
+            Format::detect(b"\xEF\xBB\xBF\nISO-10303-21;"),
+            Some(Format::Step)
+        );
+        assert_eq!(
+            Format::detect(b"<?xml version=\"1.0\"?><ifcXML xmlns=\"...\">"),
+            Some(Format::Xml)
+        );
+        assert_eq!(Format::detect(b"PK\x03\x04rest"), Some(Format::Zip));
+        assert_eq!(Format::detect(b"<html></html>"), None);
+        assert_eq!(Format::detect(b""), None);
+    }
+
+    #[test]
+    fn test_ingest_zipped_step() {
+        let path = temp_path("model.ifczip");
+        let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+        zip.start_file("readme.txt", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(b"not a model").unwrap();
+        zip.start_file("model.ifc", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(STEP.as_bytes()).unwrap();
+        zip.finish().unwrap();
+
+        let ingested = ingest(&path, 1024).expect("valid archive");
+        let content = std::fs::read_to_string(&ingested.path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&ingested.path).unwrap();
+
+        assert_eq!(ingested.format, Format::Step);
+        assert_eq!(content, STEP);
+        assert_eq!(ingested.metadata["ifc.compression"], "zip");
+        assert_eq!(ingested.metadata["ifc.project"], "Tower");
+        assert_eq!(ingested.metadata["ifc.schema"], "IFC4");
+    }
+
+    #[test]
+    fn test_ingest_enforces_limit() {
+        let path = temp_path("large.ifc");
+        std::fs::write(&path, STEP).unwrap();
+
+        let result = ingest(&path, 16);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(IngestError::TooLarge)));
+    }
+
+    #[test]
+    fn test_ingest_enforces_limit_on_archive_entry() {
+        let path = temp_path("large.ifczip");
+        let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+        zip.start_file("model.ifc", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(STEP.as_bytes()).unwrap();
+        zip.finish().unwrap();
+
+        let result = ingest(&path, 64);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(IngestError::TooLarge)));
+        assert!(!extracted_path(&path).exists());
+    }
+}
@@ -0,0 +1,856 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::ifc::step::{Entity, StepFile, Value};
+use std::collections::HashMap;
+use std::f64::consts::TAU;
+
+pub type Vec3 = [f64; 3];
+pub type Vec2 = [f64; 2];
+
+/// Maximum depth when following placement or mapping chains, guarding against cycles.
+const MAX_DEPTH: usize = 64;
+
+/// Number of segments used to approximate circular profiles.
+const CIRCLE_SEGMENTS: usize = 24;
+
+/// A triangle mesh in world coordinates (metres).
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub positions: Vec<Vec3>,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+impl Mesh {
+    /// Returns `true` when the mesh contains no triangles.
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty()
+    }
+
+    /// Appends another mesh, transforming its vertices first.
+    fn append(&mut self, other: &Mesh, transform: &Transform) {
+        let offset = self.positions.len() as u32;
+        self.positions
+            .extend(other.positions.iter().map(|p| transform.apply(*p)));
+        self.triangles.extend(
+            other
+                .triangles
+                .iter()
+                .map(|t| [t[0] + offset, t[1] + offset, t[2] + offset]),
+        );
+    }
+
+    /// Iterates over the triangles as vertex position triples.
+    pub fn faces(&self) -> impl Iterator<Item = [Vec3; 3]> + '_ {
+        self.triangles.iter().map(|t| {
+            [
+                self.positions[t[0] as usize],
+                self.positions[t[1] as usize],
+                self.positions[t[2] as usize],
+            ]
+        })
+    }
+
+    /// Returns the axis-aligned bounding box as `(min, max)`.
+    pub fn bounds(&self) -> Option<(Vec3, Vec3)> {
+        let mut iter = self.positions.iter();
+        let first = *iter.next()?;
+        Some(iter.fold((first, first), |(min, max), p| {
+            (
+                [min[0].min(p[0]), min[1].min(p[1]), min[2].min(p[2])],
+                [max[0].max(p[0]), max[1].max(p[1]), max[2].max(p[2])],
+            )
+        }))
+    }
+}
+
+/// A product (wall, slab, window, ...) with its tessellated body geometry.
+#[derive(Clone, Debug)]
+pub struct Element {
+    pub id: u64,
+    pub global_id: String,
+    pub class: String,
+    pub name: Option<String>,
+    pub mesh: Mesh,
+}
+
+/// Tessellates the body representation of every product in a STEP file.
+///
+/// # Arguments
+/// * `file` - The parsed IFC file.
+///
+/// # Returns
+/// All products that have a non-empty body geometry.
+pub fn extract(file: &StepFile) -> Vec<Element> {
+    let mut builder = Builder::new(file);
+    let mut elements: Vec<Element> = file
+        .entities
+        .values()
+        .filter(|e| is_product(file, e))
+        .filter_map(|e| builder.element(e))
+        .collect();
+    elements.sort_by_key(|e| e.id);
+    elements
+}
+
+/// Returns the scale from the project length unit to metres.
+pub fn length_unit_scale(file: &StepFile) -> f64 {
+    file.by_class("IFCSIUNIT")
+        .find(|u| u.param(1).as_enum() == Some("LENGTHUNIT"))
+        .map(|u| match u.param(2).as_enum() {
+            Some("MILLI") => 0.001,
+            Some("CENTI") => 0.01,
+            Some("DECI") => 0.1,
+            Some("KILO") => 1000.0,
+            _ => 1.0,
+        })
+        .unwrap_or(1.0)
+}
+
+/// Returns `true` for product instances: a GlobalId plus placement and representation.
+fn is_product(file: &StepFile, entity: &Entity) -> bool {
+    entity.param(0).as_str().is_some()
+        && file
+            .resolve(entity.param(6))
+            .is_some_and(|r| r.class == "IFCPRODUCTDEFINITIONSHAPE")
+}
+
+/// A 3D affine transformation stored as a row-major 3x4 matrix.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    m: [[f64; 4]; 3],
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        m: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+        ],
+    };
+
+    fn from_axes(origin: Vec3, x: Vec3, y: Vec3, z: Vec3) -> Self {
+        Self {
+            m: [
+                [x[0], y[0], z[0], origin[0]],
+                [x[1], y[1], z[1], origin[1]],
+                [x[2], y[2], z[2], origin[2]],
+            ],
+        }
+    }
+
+    fn scale(factor: f64) -> Self {
+        Self::from_axes(
+            [0.0; 3],
+            [factor, 0.0, 0.0],
+            [0.0, factor, 0.0],
+            [0.0, 0.0, factor],
+        )
+    }
+
+    pub fn apply(&self, p: Vec3) -> Vec3 {
+        let m = &self.m;
+        [
+            m[0][0] * p[0] + m[0][1] * p[1] + m[0][2] * p[2] + m[0][3],
+            m[1][0] * p[0] + m[1][1] * p[1] + m[1][2] * p[2] + m[1][3],
+            m[2][0] * p[0] + m[2][1] * p[1] + m[2][2] * p[2] + m[2][3],
+        ]
+    }
+
+    fn apply_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        [
+            m[0][0] * v[0] + m[0][1] * v[1] + m[0][2] * v[2],
+            m[1][0] * v[0] + m[1][1] * v[1] + m[1][2] * v[2],
+            m[2][0] * v[0] + m[2][1] * v[1] + m[2][2] * v[2],
+        ]
+    }
+
+    /// Returns `self * other`, i.e. `other` is applied first.
+    pub fn then(&self, other: &Transform) -> Transform {
+        let x = self.apply_vector([other.m[0][0], other.m[1][0], other.m[2][0]]);
+        let y = self.apply_vector([other.m[0][1], other.m[1][1], other.m[2][1]]);
+        let z = self.apply_vector([other.m[0][2], other.m[1][2], other.m[2][2]]);
+        let o = self.apply([other.m[0][3], other.m[1][3], other.m[2][3]]);
+        Transform::from_axes(o, x, y, z)
+    }
+}
+
+pub fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub fn dot(a: Vec3, b: Vec3) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub fn normalize(v: Vec3) -> Option<Vec3> {
+    let len = dot(v, v).sqrt();
+    (len > 1e-12).then(|| [v[0] / len, v[1] / len, v[2] / len])
+}
+
+/// Resolves IFC geometry entities into meshes, caching shared representation items.
+struct Builder<'a> {
+    file: &'a StepFile,
+    placements: HashMap<u64, Transform>,
+    items: HashMap<u64, Mesh>,
+    unit: Transform,
+}
+
+impl<'a> Builder<'a> {
+    fn new(file: &'a StepFile) -> Self {
+        Self {
+            file,
+            placements: HashMap::new(),
+            items: HashMap::new(),
+            unit: Transform::scale(length_unit_scale(file)),
+        }
+    }
+
+    fn element(&mut self, entity: &Entity) -> Option<Element> {
+        let placement = self
+            .file
+            .resolve(entity.param(5))
+            .map(|p| self.placement(p, 0))
+            .unwrap_or(Transform::IDENTITY);
+        let transform = self.unit.then(&placement);
+        let shape = self.file.resolve(entity.param(6))?;
+        let representations: Vec<&Entity> = shape
+            .param(2)
+            .as_list()
+            .iter()
+            .filter_map(|r| self.file.resolve(r))
+            .collect();
+        let body = representations
+            .iter()
+            .find(|r| r.param(1).as_str() == Some("Body"))
+            .or_else(|| {
+                representations
+                    .iter()
+                    .find(|r| !matches!(r.param(1).as_str(), Some("Axis" | "FootPrint" | "Box")))
+            })?;
+
+        let mut mesh = Mesh::default();
+        for item in body.param(3).as_list() {
+            if let Some(item) = self.file.resolve(item) {
+                let item_mesh = self.item(item, 0);
+                mesh.append(&item_mesh, &transform);
+            }
+        }
+        (!mesh.is_empty()).then(|| Element {
+            id: entity.id,
+            global_id: entity.param(0).as_str().unwrap_or_default().to_string(),
+            class: entity.class.clone(),
+            name: entity.param(2).as_str().map(str::to_string),
+            mesh,
+        })
+    }
+
+    /// Resolves an `IfcLocalPlacement` chain into an absolute transform.
+    fn placement(&mut self, entity: &Entity, depth: usize) -> Transform {
+        if let Some(t) = self.placements.get(&entity.id) {
+            return *t;
+        }
+        let transform = if entity.class == "IFCLOCALPLACEMENT" && depth < MAX_DEPTH {
+            let parent = self
+                .file
+                .resolve(entity.param(0))
+                .map(|p| self.placement(p, depth + 1))
+                .unwrap_or(Transform::IDENTITY);
+            let relative = self
+                .file
+                .resolve(entity.param(1))
+                .map(|p| self.axis_placement(p))
+                .unwrap_or(Transform::IDENTITY);
+            parent.then(&relative)
+        } else {
+            Transform::IDENTITY
+        };
+        self.placements.insert(entity.id, transform);
+        transform
+    }
+
+    fn point(&self, value: &Value) -> Option<Vec3> {
+        let point = self.file.resolve(value)?;
+        let coords: Vec<f64> = point
+            .param(0)
+            .as_list()
+            .iter()
+            .filter_map(Value::as_f64)
+            .collect();
+        Some([
+            *coords.first()?,
+            coords.get(1).copied().unwrap_or(0.0),
+            coords.get(2).copied().unwrap_or(0.0),
+        ])
+    }
+
+    fn direction(&self, value: &Value) -> Option<Vec3> {
+        self.point(value).and_then(normalize)
+    }
+
+    /// Builds a transform from `IfcAxis2Placement3D`/`2D` or a transformation operator.
+    fn axis_placement(&self, entity: &Entity) -> Transform {
+        match entity.class.as_str() {
+            "IFCAXIS2PLACEMENT3D" => {
+                let origin = self.point(entity.param(0)).unwrap_or([0.0; 3]);
+                let z = self.direction(entity.param(1)).unwrap_or([0.0, 0.0, 1.0]);
+                let x = self.direction(entity.param(2)).unwrap_or([1.0, 0.0, 0.0]);
+                let (x, y, z) = orthonormal(x, z);
+                Transform::from_axes(origin, x, y, z)
+            }
+            "IFCAXIS2PLACEMENT2D" => {
+                let origin = self.point(entity.param(0)).unwrap_or([0.0; 3]);
+                let x = self.direction(entity.param(1)).unwrap_or([1.0, 0.0, 0.0]);
+                let (x, y, z) = orthonormal(x, [0.0, 0.0, 1.0]);
+                Transform::from_axes(origin, x, y, z)
+            }
+            "IFCCARTESIANTRANSFORMATIONOPERATOR3D"
+            | "IFCCARTESIANTRANSFORMATIONOPERATOR3DNONUNIFORM" => {
+                let origin = self.point(entity.param(2)).unwrap_or([0.0; 3]);
+                let x = self.direction(entity.param(0)).unwrap_or([1.0, 0.0, 0.0]);
+                let z = self.direction(entity.param(4)).unwrap_or([0.0, 0.0, 1.0]);
+                let (x, y, z) = orthonormal(x, z);
+                let scale = entity.param(3).as_f64().unwrap_or(1.0);
+                let scale_y = entity.param(5).as_f64().unwrap_or(scale);
+                let scale_z = entity.param(6).as_f64().unwrap_or(scale);
+                Transform::from_axes(
+                    origin,
+                    x.map(|c| c * scale),
+                    y.map(|c| c * scale_y),
+                    z.map(|c| c * scale_z),
+                )
+            }
+            _ => Transform::IDENTITY,
+        }
+    }
+
+    /// Tessellates a single representation item in its local coordinates.
+    fn item(&mut self, entity: &Entity, depth: usize) -> Mesh {
+        if let Some(mesh) = self.items.get(&entity.id) {
+            return mesh.clone();
+        }
+        if depth > MAX_DEPTH {
+            return Mesh::default();
+        }
+        let mesh = match entity.class.as_str() {
+            "IFCEXTRUDEDAREASOLID" => self.extrusion(entity).unwrap_or_default(),
+            "IFCFACETEDBREP" | "IFCFACETEDBREPWITHVOIDS" => self
+                .file
+                .resolve(entity.param(0))
+                .map(|shell| self.shell(shell))
+                .unwrap_or_default(),
+            "IFCSHELLBASEDSURFACEMODEL" | "IFCFACEBASEDSURFACEMODEL" => {
+                let mut mesh = Mesh::default();
+                for shell in entity.param(0).as_list() {
+                    if let Some(shell) = self.file.resolve(shell) {
+                        mesh.append(&self.shell(shell), &Transform::IDENTITY);
+                    }
+                }
+                mesh
+            }
+            "IFCTRIANGULATEDFACESET" => self.triangulated_face_set(entity).unwrap_or_default(),
+            "IFCPOLYGONALFACESET" => self.polygonal_face_set(entity).unwrap_or_default(),
+            "IFCBOOLEANRESULT" | "IFCBOOLEANCLIPPINGRESULT" => {
+                let mut mesh = self
+                    .file
+                    .resolve(entity.param(1))
+                    .map(|e| self.item(e, depth + 1))
+                    .unwrap_or_default();
+                if entity.param(0).as_enum() == Some("UNION")
+                    && let Some(second) = self.file.resolve(entity.param(2))
+                {
+                    let second = self.item(second, depth + 1);
+                    mesh.append(&second, &Transform::IDENTITY);
+                }
+                mesh
+            }
+            "IFCMAPPEDITEM" => self.mapped_item(entity, depth).unwrap_or_default(),
+            _ => Mesh::default(),
+        };
+        self.items.insert(entity.id, mesh.clone());
+        mesh
+    }
+
+    fn mapped_item(&mut self, entity: &Entity, depth: usize) -> Option<Mesh> {
+        let map = self.file.resolve(entity.param(0))?;
+        let origin = self
+            .file
+            .resolve(map.param(0))
+            .map(|o| self.axis_placement(o))
+            .unwrap_or(Transform::IDENTITY);
+        let target = self
+            .file
+            .resolve(entity.param(1))
+            .map(|t| self.axis_placement(t))
+            .unwrap_or(Transform::IDENTITY);
+        let transform = target.then(&origin);
+        let representation = self.file.resolve(map.param(1))?;
+        let mut mesh = Mesh::default();
+        for item in representation.param(3).as_list() {
+            if let Some(item) = self.file.resolve(item) {
+                let item_mesh = self.item(item, depth + 1);
+                mesh.append(&item_mesh, &transform);
+            }
+        }
+        Some(mesh)
+    }
+
+    fn extrusion(&mut self, entity: &Entity) -> Option<Mesh> {
+        let profile = self.profile(self.file.resolve(entity.param(0))?)?;
+        let position = self
+            .file
+            .resolve(entity.param(1))
+            .map(|p| self.axis_placement(p))
+            .unwrap_or(Transform::IDENTITY);
+        let direction = self.direction(entity.param(2)).unwrap_or([0.0, 0.0, 1.0]);
+        let depth = entity.param(3).as_f64()?;
+        let offset = direction.map(|c| c * depth);
+        Some(extrude(&profile, offset, &position))
+    }
+
+    /// Returns the outer boundary of a profile definition as a 2D polygon.
+    fn profile(&self, entity: &Entity) -> Option<Vec<Vec2>> {
+        let position = || {
+            self.file
+                .resolve(entity.param(2))
+                .map(|p| self.axis_placement(p))
+                .unwrap_or(Transform::IDENTITY)
+        };
+        let placed = |points: Vec<Vec2>, t: Transform| {
+            points
+                .into_iter()
+                .map(|p| {
+                    let q = t.apply([p[0], p[1], 0.0]);
+                    [q[0], q[1]]
+                })
+                .collect::<Vec<_>>()
+        };
+        match entity.class.as_str() {
+            "IFCRECTANGLEPROFILEDEF"
+            | "IFCRECTANGLEHOLLOWPROFILEDEF"
+            | "IFCROUNDEDRECTANGLEPROFILEDEF" => {
+                let (x, y) = (
+                    entity.param(3).as_f64()? / 2.0,
+                    entity.param(4).as_f64()? / 2.0,
+                );
+                Some(placed(vec![[-x, -y], [x, -y], [x, y], [-x, y]], position()))
+            }
+            "IFCISHAPEPROFILEDEF" | "IFCTSHAPEPROFILEDEF" | "IFCUSHAPEPROFILEDEF" => {
+                let (x, y) = (
+                    entity.param(4).as_f64()? / 2.0,
+                    entity.param(3).as_f64()? / 2.0,
+                );
+                Some(placed(vec![[-x, -y], [x, -y], [x, y], [-x, y]], position()))
+            }
+            "IFCCIRCLEPROFILEDEF" | "IFCCIRCLEHOLLOWPROFILEDEF" => {
+                let r = entity.param(3).as_f64()?;
+                let points = (0..CIRCLE_SEGMENTS)
+                    .map(|i| {
+                        let a = TAU * i as f64 / CIRCLE_SEGMENTS as f64;
+                        [r * a.cos(), r * a.sin()]
+                    })
+                    .collect();
+                Some(placed(points, position()))
+            }
+            "IFCARBITRARYCLOSEDPROFILEDEF" | "IFCARBITRARYPROFILEDEFWITHVOIDS" => {
+                self.curve(self.file.resolve(entity.param(2))?, 0)
+            }
+            _ => None,
+        }
+    }
+
+    /// Flattens a bounded curve into its 2D vertices.
+    fn curve(&self, entity: &Entity, depth: usize) -> Option<Vec<Vec2>> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        match entity.class.as_str() {
+            "IFCPOLYLINE" => Some(
+                entity
+                    .param(0)
+                    .as_list()
+                    .iter()
+                    .filter_map(|p| self.point(p))
+                    .map(|p| [p[0], p[1]])
+                    .collect(),
+            ),
+            "IFCINDEXEDPOLYCURVE" => {
+                let list = self.file.resolve(entity.param(0))?;
+                Some(
+                    list.param(0)
+                        .as_list()
+                        .iter()
+                        .filter_map(|c| {
+                            let c = c.as_list();
+                            Some([c.first()?.as_f64()?, c.get(1)?.as_f64()?])
+                        })
+                        .collect(),
+                )
+            }
+            "IFCCOMPOSITECURVE" => {
+                let mut points = Vec::new();
+                for segment in entity.param(0).as_list() {
+                    let segment = self.file.resolve(segment)?;
+                    let parent = self.file.resolve(segment.param(2))?;
+                    let mut part = self.curve(parent, depth + 1)?;
+                    if segment.param(1).as_enum() == Some("F") {
+                        part.reverse();
+                    }
+                    points.extend(part);
+                }
+                Some(points)
+            }
+            _ => None,
+        }
+    }
+
+    fn shell(&self, shell: &Entity) -> Mesh {
+        let mut mesh = Mesh::default();
+        for face in shell.param(0).as_list() {
+            let Some(face) = self.file.resolve(face) else {
+                continue;
+            };
+            let bounds: Vec<&Entity> = face
+                .param(0)
+                .as_list()
+                .iter()
+                .filter_map(|b| self.file.resolve(b))
+                .collect();
+            let Some(bound) = bounds
+                .iter()
+                .find(|b| b.class == "IFCFACEOUTERBOUND")
+                .or_else(|| bounds.first())
+            else {
+                continue;
+            };
+            let Some(polygon) = self.file.resolve(bound.param(0)) else {
+                continue;
+            };
+            let mut points: Vec<Vec3> = polygon
+                .param(0)
+                .as_list()
+                .iter()
+                .filter_map(|p| self.point(p))
+                .collect();
+            if bound.param(1).as_enum() == Some("F") {
+                points.reverse();
+            }
+            add_polygon(&mut mesh, &points);
+        }
+        mesh
+    }
+
+    fn coordinate_list(&self, value: &Value) -> Option<Vec<Vec3>> {
+        let list = self.file.resolve(value)?;
+        Some(
+            list.param(0)
+                .as_list()
+                .iter()
+                .filter_map(|c| {
+                    let c = c.as_list();
+                    Some([
+                        c.first()?.as_f64()?,
+                        c.get(1)?.as_f64()?,
+                        c.get(2).and_then(Value::as_f64).unwrap_or(0.0),
+                    ])
+                })
+                .collect(),
+        )
+    }
+
+    fn triangulated_face_set(&self, entity: &Entity) -> Option<Mesh> {
+        let positions = self.coordinate_list(entity.param(0))?;
+        let pn_index: Vec<usize> = indices(entity.param(4));
+        let lookup = |i: usize| -> Option<u32> {
+            let i = if pn_index.is_empty() {
+                i
+            } else {
+                *pn_index.get(i.checked_sub(1)?)?
+            };
+            let i = i.checked_sub(1)?;
+            (i < positions.len()).then_some(i as u32)
+        };
+        let triangles = entity
+            .param(3)
+            .as_list()
+            .iter()
+            .filter_map(|t| {
+                let t = indices(t);
+                Some([
+                    lookup(*t.first()?)?,
+                    lookup(*t.get(1)?)?,
+                    lookup(*t.get(2)?)?,
+                ])
+            })
+            .collect();
+        Some(Mesh {
+            positions,
+            triangles,
+        })
+    }
+
+    fn polygonal_face_set(&self, entity: &Entity) -> Option<Mesh> {
+        let positions = self.coordinate_list(entity.param(0))?;
+        let mut mesh = Mesh::default();
+        for face in entity.param(2).as_list() {
+            let Some(face) = self.file.resolve(face) else {
+                continue;
+            };
+            let points: Vec<Vec3> = indices(face.param(0))
+                .into_iter()
+                .filter_map(|i| positions.get(i.checked_sub(1)?).copied())
+                .collect();
+            add_polygon(&mut mesh, &points);
+        }
+        Some(mesh)
+    }
+}
+
+fn indices(value: &Value) -> Vec<usize> {
+    value
+        .as_list()
+        .iter()
+        .filter_map(Value::as_f64)
+        .map(|i| i as usize)
+        .collect()
+}
+
+/// Builds a right-handed orthonormal basis from a reference x direction and a z axis.
+fn orthonormal(x: Vec3, z: Vec3) -> (Vec3, Vec3, Vec3) {
+    let z = normalize(z).unwrap_or([0.0, 0.0, 1.0]);
+    let projected = sub(x, z.map(|c| c * dot(x, z)));
+    let x = normalize(projected).unwrap_or_else(|| {
+        let fallback = if z[0].abs() < 0.9 {
+            [1.0, 0.0, 0.0]
+        } else {
+            [0.0, 1.0, 0.0]
+        };
+        normalize(sub(fallback, z.map(|c| c * dot(fallback, z)))).unwrap_or([1.0, 0.0, 0.0])
+    });
+    (x, cross(z, x), z)
+}
+
+/// Sweeps a closed 2D profile along `offset`, producing caps and side walls.
+fn extrude(profile: &[Vec2], offset: Vec3, position: &Transform) -> Mesh {
+    let mut profile = dedup_closed(profile);
+    if profile.len() < 3 {
+        return Mesh::default();
+    }
+    if signed_area(&profile) < 0.0 {
+        profile.reverse();
+    }
+    let n = profile.len() as u32;
+    let mut mesh = Mesh::default();
+    mesh.positions
+        .extend(profile.iter().map(|p| position.apply([p[0], p[1], 0.0])));
+    mesh.positions.extend(
+        profile
+            .iter()
+            .map(|p| position.apply([p[0] + offset[0], p[1] + offset[1], offset[2]])),
+    );
+    for [a, b, c] in triangulate(&profile) {
+        mesh.triangles.push([c as u32, b as u32, a as u32]);
+        mesh.triangles
+            .push([a as u32 + n, b as u32 + n, c as u32 + n]);
+    }
+    for i in 0..n {
+        let j = (i + 1) % n;
+        mesh.triangles.push([i, j, j + n]);
+        mesh.triangles.push([i, j + n, i + n]);
+    }
+    mesh
+}
+
+/// Triangulates a planar 3D polygon and appends it to `mesh`.
+fn add_polygon(mesh: &mut Mesh, points: &[Vec3]) {
+    if points.len() < 3 {
+        return;
+    }
+    // Newell's method gives a robust normal for non-convex polygons.
+    let mut normal = [0.0; 3];
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        normal[0] += (a[1] - b[1]) * (a[2] + b[2]);
+        normal[1] += (a[2] - b[2]) * (a[0] + b[0]);
+        normal[2] += (a[0] - b[0]) * (a[1] + b[1]);
+    }
+    let Some(normal) = normalize(normal) else {
+        return;
+    };
+    let (u, v, _) = orthonormal(
+        if normal[0].abs() < 0.9 {
+            [1.0, 0.0, 0.0]
+        } else {
+            [0.0, 1.0, 0.0]
+        },
+        normal,
+    );
+    let flat: Vec<Vec2> = points.iter().map(|p| [dot(*p, u), dot(*p, v)]).collect();
+    let offset = mesh.positions.len() as u32;
+    mesh.positions.extend_from_slice(points);
+    for [a, b, c] in triangulate(&flat) {
+        mesh.triangles
+            .push([offset + a as u32, offset + b as u32, offset + c as u32]);
+    }
+}
+
+fn dedup_closed(points: &[Vec2]) -> Vec<Vec2> {
+    let mut out: Vec<Vec2> = Vec::with_capacity(points.len());
+    for p in points {
+        if out
+            .last()
+            .is_none_or(|q| (q[0] - p[0]).abs() > 1e-9 || (q[1] - p[1]).abs() > 1e-9)
+        {
+            out.push(*p);
+        }
+    }
+    while out.len() > 1 && {
+        let (first, last) = (out[0], out[out.len() - 1]);
+        (first[0] - last[0]).abs() <= 1e-9 && (first[1] - last[1]).abs() <= 1e-9
+    } {
+        out.pop();
+    }
+    out
+}
+
+/// Returns twice the signed area of a polygon (positive when counter-clockwise).
+pub fn signed_area(points: &[Vec2]) -> f64 {
+    (0..points.len())
+        .map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            a[0] * b[1] - b[0] * a[1]
+        })
+        .sum()
+}
+
+/// Ear-clipping triangulation of a simple polygon, preserving its winding.
+pub fn triangulate(points: &[Vec2]) -> Vec<[usize; 3]> {
+    let n = points.len();
+    if n < 3 {
+        return Vec::new();
+    }
+    let ccw = signed_area(points) >= 0.0;
+    let mut remaining: Vec<usize> = if ccw {
+        (0..n).collect()
+    } else {
+        (0..n).rev().collect()
+    };
+    let mut triangles = Vec::with_capacity(n - 2);
+    let cross2 =
+        |o: Vec2, a: Vec2, b: Vec2| (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0]);
+    let mut guard = 0;
+    while remaining.len() > 3 && guard < n * n {
+        guard += 1;
+        let m = remaining.len();
+        let ear = (0..m).find(|&i| {
+            let (a, b, c) = (
+                points[remaining[(i + m - 1) % m]],
+                points[remaining[i]],
+                points[remaining[(i + 1) % m]],
+            );
+            if cross2(a, b, c) <= 1e-12 {
+                return false;
+            }
+            remaining.iter().all(|&k| {
+                let p = points[k];
+                p == a
+                    || p == b
+                    || p == c
+                    || !(cross2(a, b, p) >= 0.0 && cross2(b, c, p) >= 0.0 && cross2(c, a, p) >= 0.0)
+            })
+        });
+        // Degenerate input: fall back to a fan over what is left.
+        let i = ear.unwrap_or(1);
+        let tri = [
+            remaining[(i + m - 1) % m],
+            remaining[i],
+            remaining[(i + 1) % m],
+        ];
+        triangles.push(if ccw { tri } else { [tri[2], tri[1], tri[0]] });
+        remaining.remove(i);
+    }
+    if remaining.len() == 3 {
+        let tri = [remaining[0], remaining[1], remaining[2]];
+        triangles.push(if ccw { tri } else { [tri[2], tri[1], tri[0]] });
+    }
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ifc::step::parse;
+
+    const BOX_WALL: &str = r#"ISO-10303-21;
+HEADER;
+FILE_SCHEMA(('IFC4'));
+ENDSEC;
+DATA;
+#1=IFCSIUNIT(*,.LENGTHUNIT.,.MILLI.,.METRE.);
+#10=IFCCARTESIANPOINT((0.,0.,0.));
+#11=IFCDIRECTION((0.,0.,1.));
+#12=IFCDIRECTION((1.,0.,0.));
+#13=IFCAXIS2PLACEMENT3D(#10,#11,#12);
+#14=IFCLOCALPLACEMENT($,#13);
+#15=IFCCARTESIANPOINT((1000.,0.,0.));
+#16=IFCAXIS2PLACEMENT3D(#15,$,$);
+#17=IFCLOCALPLACEMENT(#14,#16);
+#20=IFCRECTANGLEPROFILEDEF(.AREA.,$,$,4000.,200.);
+#21=IFCEXTRUDEDAREASOLID(#20,#13,#11,3000.);
+#22=IFCSHAPEREPRESENTATION($,'Body','SweptSolid',(#21));
+#23=IFCPRODUCTDEFINITIONSHAPE($,$,(#22));
+#30=IFCWALL('0wall000000000000000001',$,'Wall',$,$,#17,#23,$,$);
+ENDSEC;
+END-ISO-10303-21;
+"#;
+
+    #[test]
+    fn test_extract_extruded_wall() {
+        let file = parse(BOX_WALL).expect("valid STEP");
+        let elements = extract(&file);
+
+        assert_eq!(elements.len(), 1);
+        let wall = &elements[0];
+        assert_eq!(wall.class, "IFCWALL");
+        assert_eq!(wall.name.as_deref(), Some("Wall"));
+        assert_eq!(wall.mesh.triangles.len(), 12);
+
+        let (min, max) = wall.mesh.bounds().unwrap();
+        let expected_min = [-1.0, -0.1, 0.0];
+        let expected_max = [3.0, 0.1, 3.0];
+        for i in 0..3 {
+            assert!((min[i] - expected_min[i]).abs() < 1e-9, "min {min:?}");
+            assert!((max[i] - expected_max[i]).abs() < 1e-9, "max {max:?}");
+        }
+    }
+
+    #[test]
+    fn test_triangulate_concave_polygon() {
+        let l_shape = [
+            [0.0, 0.0],
+            [2.0, 0.0],
+            [2.0, 1.0],
+            [1.0, 1.0],
+            [1.0, 2.0],
+            [0.0, 2.0],
+        ];
+        let triangles = triangulate(&l_shape);
+
+        assert_eq!(triangles.len(), 4);
+        let area: f64 = triangles
+            .iter()
+            .map(|t| signed_area(&[l_shape[t[0]], l_shape[t[1]], l_shape[t[2]]]))
+            .sum();
+        assert!((area / 2.0 - 3.0).abs() < 1e-9);
+    }
+}
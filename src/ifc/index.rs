@@ -0,0 +1,110 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::ifc::step::{Record, StepError, StepReader};
+use std::collections::HashMap;
+use std::io::BufRead;
+
+/// A building storey found while indexing a model.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Storey {
+    pub global_id: String,
+    pub name: Option<String>,
+    pub elevation: Option<f64>,
+}
+
+/// Key facts about an IFC model, gathered in a single streaming pass.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Summary {
+    pub schema: Option<String>,
+    pub entities: u64,
+    pub products: u64,
+    pub project: Option<String>,
+    pub storeys: Vec<Storey>,
+}
+
+impl Summary {
+    /// Returns the summary as `ifc.*` metadata entries.
+    pub fn to_metadata(&self) -> HashMap<String, String> {
+        let mut metadata = HashMap::from([
+            (String::from("ifc.entities"), self.entities.to_string()),
+            (String::from("ifc.products"), self.products.to_string()),
+            (String::from("ifc.storeys"), self.storeys.len().to_string()),
+        ]);
+        if let Some(schema) = &self.schema {
+            metadata.insert(String::from("ifc.schema"), schema.clone());
+        }
+        if let Some(project) = &self.project {
+            metadata.insert(String::from("ifc.project"), project.clone());
+        }
+        metadata
+    }
+}
+
+/// Indexes a STEP file without holding more than one record in memory.
+///
+/// # Arguments
+/// * `reader` - The source of the STEP text.
+///
+/// # Returns
+/// A `Result` containing the summary of the model.
+pub fn summarize<R: BufRead>(reader: R) -> Result<Summary, StepError> {
+    let mut summary = Summary::default();
+    for record in StepReader::new(reader) {
+        match record? {
+            Record::Header(header) if header.name == "FILE_SCHEMA" => {
+                summary.schema = header
+                    .params
+                    .first()
+                    .and_then(|v| v.as_list().first())
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string);
+            }
+            Record::Header(_) => {}
+            Record::Entity(entity) => {
+                summary.entities += 1;
+                // Products carry a GlobalId, a placement and a representation.
+                if entity.param(0).as_str().is_some() && entity.param(6).as_ref_id().is_some() {
+                    summary.products += 1;
+                }
+                match entity.class.as_str() {
+                    "IFCPROJECT" => {
+                        summary.project = entity.param(2).as_str().map(str::to_string);
+                    }
+                    "IFCBUILDINGSTOREY" => summary.storeys.push(Storey {
+                        global_id: entity.param(0).as_str().unwrap_or_default().to_string(),
+                        name: entity.param(2).as_str().map(str::to_string),
+                        elevation: entity.param(9).as_f64(),
+                    }),
+                    _ => {}
+                }
+            }
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize_collects_project_and_storeys() {
+        let content = "ISO-10303-21;HEADER;FILE_SCHEMA(('IFC2X3'));ENDSEC;DATA;\n\
+            #1=IFCPROJECT('0proj000000000000000001',$,'Tower',$,$,$,$,$,$);\n\
+            #2=IFCBUILDINGSTOREY('0storey0000000000000001',$,'Level 1',$,$,$,$,$,.ELEMENT.,3.5);\n\
+            #3=IFCWALL('0wall000000000000000001',$,$,$,$,#4,#5,$);\n\
+            ENDSEC;END-ISO-10303-21;";
+        let summary = summarize(content.as_bytes()).expect("valid STEP");
+
+        assert_eq!(summary.schema.as_deref(), Some("IFC2X3"));
+        assert_eq!(summary.entities, 3);
+        assert_eq!(summary.products, 1);
+        assert_eq!(summary.project.as_deref(), Some("Tower"));
+        assert_eq!(summary.storeys[0].elevation, Some(3.5));
+
+        let metadata = summary.to_metadata();
+        assert_eq!(metadata["ifc.schema"], "IFC2X3");
+        assert_eq!(metadata["ifc.storeys"], "1");
+    }
+}
@@ -0,0 +1,568 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::ifc::step::{Entity, StepFile, StepWriter, Value};
+use rocket::serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+/// How an element changed on one side of a merge relative to the common base.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum Change {
+    Unchanged,
+    Added,
+    Modified,
+    Deleted,
+}
+
+/// An element that was changed differently on both sides of a merge.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Conflict {
+    pub global_id: String,
+    pub class: String,
+    pub ours: Change,
+    pub theirs: Change,
+}
+
+/// The outcome of a successful merge.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Merged {
+    /// The merged STEP text.
+    #[serde(skip)]
+    pub content: String,
+    /// GlobalIds of elements added from the other side.
+    pub added: Vec<String>,
+    /// GlobalIds of elements whose changes were taken from the other side.
+    pub modified: Vec<String>,
+    /// GlobalIds of elements deleted on the other side.
+    pub removed: Vec<String>,
+}
+
+/// Returns the GlobalId of an `IfcRoot` instance.
+///
+/// # Arguments
+/// * `entity` - The entity instance.
+///
+/// # Returns
+/// The GlobalId, or `None` for entities that are not rooted.
+pub fn global_id(entity: &Entity) -> Option<&str> {
+    let id = entity.param(0).as_str()?;
+    let rooted = id.len() == 22
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'$')
+        && matches!(entity.param(1), Value::Ref(_) | Value::Null);
+    rooted.then_some(id)
+}
+
+/// Structural fingerprints of the entities of a file.
+///
+/// A rooted entity is identified by its GlobalId; everything it references that is not
+/// itself rooted (placements, geometry, property values, …) is folded into its hash, so
+/// two files that differ only in `#id` numbering produce identical fingerprints.
+struct Fingerprints<'a> {
+    file: &'a StepFile,
+    memo: HashMap<u64, u64>,
+    visiting: HashSet<u64>,
+}
+
+impl<'a> Fingerprints<'a> {
+    fn new(file: &'a StepFile) -> Self {
+        Self {
+            file,
+            memo: HashMap::new(),
+            visiting: HashSet::new(),
+        }
+    }
+
+    /// Returns the structural hash of an entity, including its non-rooted closure.
+    fn entity(&mut self, id: u64) -> u64 {
+        if let Some(&hash) = self.memo.get(&id) {
+            return hash;
+        }
+        let Some(entity) = self.file.get(id) else {
+            return id;
+        };
+        if !self.visiting.insert(id) {
+            // A reference cycle through non-rooted entities; fall back to the id.
+            return id;
+        }
+        let mut hasher = DefaultHasher::new();
+        entity.class.hash(&mut hasher);
+        for param in &entity.params {
+            self.value(param, &mut hasher);
+        }
+        let hash = hasher.finish();
+        self.visiting.remove(&id);
+        self.memo.insert(id, hash);
+        hash
+    }
+
+    fn value(&mut self, value: &Value, hasher: &mut DefaultHasher) {
+        std::mem::discriminant(value).hash(hasher);
+        match value {
+            Value::Null | Value::Derived => {}
+            Value::Integer(i) => i.hash(hasher),
+            Value::Real(r) => r.to_bits().hash(hasher),
+            Value::String(s) | Value::Enum(s) | Value::Binary(s) => s.hash(hasher),
+            Value::Ref(id) => match self.file.get(*id).and_then(global_id) {
+                Some(gid) => gid.hash(hasher),
+                None => self.entity(*id).hash(hasher),
+            },
+            Value::List(items) => {
+                items.len().hash(hasher);
+                for item in items {
+                    self.value(item, hasher);
+                }
+            }
+            Value::Typed(name, inner) => {
+                name.hash(hasher);
+                self.value(inner, hasher);
+            }
+        }
+    }
+}
+
+/// The rooted elements of a file with their entity id, class and fingerprint.
+struct Elements {
+    by_gid: HashMap<String, (u64, String, u64)>,
+}
+
+impl Elements {
+    fn new(file: &StepFile) -> Self {
+        let mut fingerprints = Fingerprints::new(file);
+        let by_gid = file
+            .entities
+            .values()
+            .filter_map(|entity| {
+                let gid = global_id(entity)?;
+                Some((
+                    gid.to_string(),
+                    (
+                        entity.id,
+                        entity.class.clone(),
+                        fingerprints.entity(entity.id),
+                    ),
+                ))
+            })
+            .collect();
+        Self { by_gid }
+    }
+
+    fn fingerprint(&self, gid: &str) -> Option<u64> {
+        self.by_gid.get(gid).map(|(_, _, hash)| *hash)
+    }
+
+    fn change(&self, base: &Elements, gid: &str) -> Change {
+        match (base.fingerprint(gid), self.fingerprint(gid)) {
+            (None, None) => Change::Unchanged,
+            (None, Some(_)) => Change::Added,
+            (Some(_), None) => Change::Deleted,
+            (Some(a), Some(b)) if a == b => Change::Unchanged,
+            _ => Change::Modified,
+        }
+    }
+}
+
+/// Returns the ids of the non-rooted entities reachable from the given entities.
+fn closure(file: &StepFile, roots: impl IntoIterator<Item = u64>) -> HashSet<u64> {
+    let mut seen = HashSet::new();
+    let mut stack: Vec<u64> = roots.into_iter().collect();
+    while let Some(id) = stack.pop() {
+        let Some(entity) = file.get(id) else {
+            continue;
+        };
+        for param in &entity.params {
+            collect_refs(param, &mut |target| {
+                if file.get(target).is_some_and(|e| global_id(e).is_none()) && seen.insert(target) {
+                    stack.push(target);
+                }
+            });
+        }
+    }
+    seen
+}
+
+fn collect_refs(value: &Value, f: &mut impl FnMut(u64)) {
+    match value {
+        Value::Ref(id) => f(*id),
+        Value::List(items) => items.iter().for_each(|item| collect_refs(item, f)),
+        Value::Typed(_, inner) => collect_refs(inner, f),
+        _ => {}
+    }
+}
+
+/// Copies entities from the other side of a merge into the output file.
+struct Importer<'a> {
+    theirs: &'a StepFile,
+    theirs_hashes: Fingerprints<'a>,
+    output: HashMap<u64, Entity>,
+    /// Output ids of non-rooted entities by fingerprint, used to share identical geometry.
+    shared: HashMap<u64, u64>,
+    /// Output ids of rooted entities by GlobalId.
+    rooted: HashMap<String, u64>,
+    next_id: u64,
+}
+
+impl Importer<'_> {
+    /// Copies a rooted entity from `theirs` into the output under the given id.
+    fn import_root(&mut self, theirs_id: u64, output_id: u64) -> Result<(), String> {
+        let entity = self.theirs.get(theirs_id).expect("element exists").clone();
+        let params = entity
+            .params
+            .iter()
+            .map(|value| self.import_value(value))
+            .collect::<Result<_, _>>()?;
+        self.output.insert(
+            output_id,
+            Entity {
+                id: output_id,
+                class: entity.class,
+                params,
+            },
+        );
+        Ok(())
+    }
+
+    fn import_value(&mut self, value: &Value) -> Result<Value, String> {
+        Ok(match value {
+            Value::Ref(id) => Value::Ref(self.import_ref(*id)?),
+            Value::List(items) => Value::List(
+                items
+                    .iter()
+                    .map(|item| self.import_value(item))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Value::Typed(name, inner) => {
+                Value::Typed(name.clone(), Box::new(self.import_value(inner)?))
+            }
+            other => other.clone(),
+        })
+    }
+
+    /// Maps a reference in `theirs` to an output id, copying non-rooted entities as needed.
+    fn import_ref(&mut self, id: u64) -> Result<u64, String> {
+        let Some(entity) = self.theirs.get(id) else {
+            return Ok(id);
+        };
+        if let Some(gid) = global_id(entity) {
+            return self.rooted.get(gid).copied().ok_or_else(|| gid.to_string());
+        }
+        let hash = self.theirs_hashes.entity(id);
+        if let Some(&existing) = self.shared.get(&hash) {
+            return Ok(existing);
+        }
+        let output_id = self.next_id;
+        self.next_id += 1;
+        self.shared.insert(hash, output_id);
+        let entity = entity.clone();
+        let params = entity
+            .params
+            .iter()
+            .map(|value| self.import_value(value))
+            .collect::<Result<_, _>>()?;
+        self.output.insert(
+            output_id,
+            Entity {
+                id: output_id,
+                class: entity.class,
+                params,
+            },
+        );
+        Ok(output_id)
+    }
+}
+
+/// Merges the changes made in `theirs` into `ours`, element by element.
+///
+/// Elements are matched by GlobalId. An element is taken from `theirs` when only that
+/// side changed it relative to `base`; an element changed differently on both sides is a
+/// conflict. The output keeps the header and entity numbering of `ours`.
+///
+/// # Arguments
+/// * `base` - The common ancestor.
+/// * `ours` - The target side, e.g. the main line of the model.
+/// * `theirs` - The side being merged in, e.g. a design option branch.
+///
+/// # Returns
+/// The merged file, or the list of conflicting elements.
+pub fn merge(base: &StepFile, ours: &StepFile, theirs: &StepFile) -> Result<Merged, Vec<Conflict>> {
+    let base_elements = Elements::new(base);
+    let our_elements = Elements::new(ours);
+    let their_elements = Elements::new(theirs);
+
+    let gids: BTreeSet<&String> = base_elements
+        .by_gid
+        .keys()
+        .chain(our_elements.by_gid.keys())
+        .chain(their_elements.by_gid.keys())
+        .collect();
+
+    let mut merged = Merged::default();
+    let mut conflicts = Vec::new();
+    let mut take = Vec::new();
+    let mut remove = Vec::new();
+    for gid in gids {
+        let ours_change = our_elements.change(&base_elements, gid);
+        let theirs_change = their_elements.change(&base_elements, gid);
+        if theirs_change == Change::Unchanged
+            || our_elements.fingerprint(gid) == their_elements.fingerprint(gid)
+        {
+            continue;
+        }
+        if ours_change != Change::Unchanged {
+            let class = [&our_elements, &their_elements, &base_elements]
+                .iter()
+                .find_map(|elements| elements.by_gid.get(gid))
+                .map(|(_, class, _)| class.clone())
+                .unwrap_or_default();
+            conflicts.push(Conflict {
+                global_id: gid.clone(),
+                class,
+                ours: ours_change,
+                theirs: theirs_change,
+            });
+            continue;
+        }
+        match theirs_change {
+            Change::Added => {
+                merged.added.push(gid.clone());
+                take.push(gid.clone());
+            }
+            Change::Modified => {
+                merged.modified.push(gid.clone());
+                take.push(gid.clone());
+            }
+            Change::Deleted => {
+                merged.removed.push(gid.clone());
+                remove.push(gid.clone());
+            }
+            Change::Unchanged => {}
+        }
+    }
+    if !conflicts.is_empty() {
+        return Err(conflicts);
+    }
+
+    // Entities only used by elements that are replaced or removed become unreachable.
+    let replaced: HashSet<u64> = take
+        .iter()
+        .chain(&remove)
+        .filter_map(|gid| our_elements.by_gid.get(gid).map(|(id, _, _)| *id))
+        .collect();
+    let kept = closure(
+        ours,
+        our_elements
+            .by_gid
+            .values()
+            .map(|(id, _, _)| *id)
+            .filter(|id| !replaced.contains(id)),
+    );
+    let orphans: HashSet<u64> = closure(ours, replaced.iter().copied())
+        .into_iter()
+        .filter(|id| !kept.contains(id))
+        .collect();
+    let mut output = ours.entities.clone();
+    output.retain(|id, _| !orphans.contains(id));
+    for gid in &remove {
+        if let Some((id, _, _)) = our_elements.by_gid.get(gid) {
+            output.remove(id);
+        }
+    }
+
+    let mut ours_hashes = Fingerprints::new(ours);
+    let shared = ours
+        .entities
+        .values()
+        .filter(|entity| global_id(entity).is_none() && !orphans.contains(&entity.id))
+        .map(|entity| (ours_hashes.entity(entity.id), entity.id))
+        .collect();
+    let mut next_id = ours.entities.keys().max().copied().unwrap_or(0) + 1;
+    let mut rooted: HashMap<String, u64> = our_elements
+        .by_gid
+        .iter()
+        .filter(|(gid, _)| !remove.contains(gid))
+        .map(|(gid, (id, _, _))| (gid.clone(), *id))
+        .collect();
+    for gid in &take {
+        // Modified elements keep their id so references from unchanged elements stay valid.
+        rooted.entry(gid.clone()).or_insert_with(|| {
+            next_id += 1;
+            next_id - 1
+        });
+    }
+
+    let mut importer = Importer {
+        theirs,
+        theirs_hashes: Fingerprints::new(theirs),
+        output,
+        shared,
+        rooted,
+        next_id,
+    };
+    let mut dangling = Vec::new();
+    for gid in &take {
+        let (theirs_id, class, _) = &their_elements.by_gid[gid];
+        if let Err(missing) = importer.import_root(*theirs_id, importer.rooted[gid]) {
+            // The element refers to one that was deleted on our side.
+            dangling.push(Conflict {
+                global_id: gid.clone(),
+                class: class.clone(),
+                ours: our_elements.change(&base_elements, &missing),
+                theirs: their_elements.change(&base_elements, gid),
+            });
+        }
+    }
+    if !dangling.is_empty() {
+        return Err(dangling);
+    }
+
+    let output = importer.output;
+    let mut ids: Vec<u64> = output.keys().copied().collect();
+    ids.sort_unstable();
+    let write = || -> std::io::Result<Vec<u8>> {
+        let mut writer = StepWriter::new(Vec::new(), &ours.header)?;
+        for id in &ids {
+            writer.write(&output[id])?;
+        }
+        writer.finish()
+    };
+    merged.content = String::from_utf8(write().expect("writing to memory cannot fail"))
+        .expect("STEP writer emits UTF-8");
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ifc::step::parse;
+
+    const HEAD: &str = "ISO-10303-21;HEADER;FILE_SCHEMA(('IFC4'));ENDSEC;DATA;\n";
+    const TAIL: &str = "ENDSEC;END-ISO-10303-21;";
+
+    fn file(data: &str) -> StepFile {
+        parse(&format!("{HEAD}{data}{TAIL}")).expect("valid STEP")
+    }
+
+    fn base() -> StepFile {
+        file(
+            "#1=IFCCARTESIANPOINT((0.,0.,0.));\n\
+             #2=IFCAXIS2PLACEMENT3D(#1,$,$);\n\
+             #3=IFCLOCALPLACEMENT($,#2);\n\
+             #10=IFCWALL('0wall00000000000000001',$,'Wall A',$,$,#3,$,$,$);\n\
+             #11=IFCWALL('0wall00000000000000002',$,'Wall B',$,$,#3,$,$,$);\n",
+        )
+    }
+
+    fn names(content: &str) -> Vec<String> {
+        let file = parse(content).expect("merged STEP is valid");
+        let mut names: Vec<String> = file
+            .entities
+            .values()
+            .filter(|e| global_id(e).is_some())
+            .filter_map(|e| e.param(2).as_str().map(str::to_string))
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_merge_applies_non_overlapping_changes() {
+        let ours = file(
+            "#1=IFCCARTESIANPOINT((0.,0.,0.));\n\
+             #2=IFCAXIS2PLACEMENT3D(#1,$,$);\n\
+             #3=IFCLOCALPLACEMENT($,#2);\n\
+             #10=IFCWALL('0wall00000000000000001',$,'Wall A (ours)',$,$,#3,$,$,$);\n\
+             #11=IFCWALL('0wall00000000000000002',$,'Wall B',$,$,#3,$,$,$);\n",
+        );
+        // Renumbered throughout; Wall B moved, Wall C added.
+        let theirs = file(
+            "#5=IFCCARTESIANPOINT((0.,0.,0.));\n\
+             #6=IFCAXIS2PLACEMENT3D(#5,$,$);\n\
+             #7=IFCLOCALPLACEMENT($,#6);\n\
+             #8=IFCCARTESIANPOINT((5.,0.,0.));\n\
+             #9=IFCAXIS2PLACEMENT3D(#8,$,$);\n\
+             #20=IFCLOCALPLACEMENT($,#9);\n\
+             #21=IFCWALL('0wall00000000000000001',$,'Wall A',$,$,#7,$,$,$);\n\
+             #22=IFCWALL('0wall00000000000000002',$,'Wall B',$,$,#20,$,$,$);\n\
+             #23=IFCWALL('0wall00000000000000003',$,'Wall C',$,$,#7,$,$,$);\n",
+        );
+
+        let merged = merge(&base(), &ours, &theirs).expect("no conflicts");
+        assert_eq!(merged.added, ["0wall00000000000000003"]);
+        assert_eq!(merged.modified, ["0wall00000000000000002"]);
+        assert!(merged.removed.is_empty());
+        assert_eq!(
+            names(&merged.content),
+            ["Wall A (ours)", "Wall B", "Wall C"]
+        );
+
+        let output = parse(&merged.content).unwrap();
+        let wall_b = output.get(11).expect("modified element keeps its id");
+        let placement = output.resolve(wall_b.param(5)).unwrap();
+        let point = output
+            .resolve(output.resolve(placement.param(1)).unwrap().param(0))
+            .unwrap();
+        assert_eq!(point.param(0).as_list()[0].as_f64(), Some(5.0));
+        // The unchanged placement is shared rather than copied.
+        let wall_c = output
+            .entities
+            .values()
+            .find(|e| global_id(e) == Some("0wall00000000000000003"))
+            .unwrap();
+        assert_eq!(wall_c.param(5), &Value::Ref(3));
+    }
+
+    #[test]
+    fn test_merge_removes_deleted_elements_and_orphans() {
+        let theirs = file(
+            "#1=IFCCARTESIANPOINT((0.,0.,0.));\n\
+             #2=IFCAXIS2PLACEMENT3D(#1,$,$);\n\
+             #3=IFCLOCALPLACEMENT($,#2);\n\
+             #10=IFCWALL('0wall00000000000000001',$,'Wall A',$,$,#3,$,$,$);\n",
+        );
+        let merged = merge(&base(), &base(), &theirs).expect("no conflicts");
+        assert_eq!(merged.removed, ["0wall00000000000000002"]);
+        let output = parse(&merged.content).unwrap();
+        assert_eq!(output.entities.len(), 4);
+    }
+
+    #[test]
+    fn test_merge_reports_conflicting_changes() {
+        let ours = file(
+            "#1=IFCCARTESIANPOINT((0.,0.,0.));\n\
+             #2=IFCAXIS2PLACEMENT3D(#1,$,$);\n\
+             #3=IFCLOCALPLACEMENT($,#2);\n\
+             #10=IFCWALL('0wall00000000000000001',$,'Wall A (ours)',$,$,#3,$,$,$);\n",
+        );
+        let theirs = file(
+            "#1=IFCCARTESIANPOINT((0.,0.,0.));\n\
+             #2=IFCAXIS2PLACEMENT3D(#1,$,$);\n\
+             #3=IFCLOCALPLACEMENT($,#2);\n\
+             #10=IFCWALL('0wall00000000000000001',$,'Wall A (theirs)',$,$,#3,$,$,$);\n\
+             #11=IFCWALL('0wall00000000000000002',$,'Wall B (theirs)',$,$,#3,$,$,$);\n",
+        );
+        let conflicts = merge(&base(), &ours, &theirs).unwrap_err();
+        assert_eq!(
+            conflicts,
+            [
+                Conflict {
+                    global_id: String::from("0wall00000000000000001"),
+                    class: String::from("IFCWALL"),
+                    ours: Change::Modified,
+                    theirs: Change::Modified,
+                },
+                Conflict {
+                    global_id: String::from("0wall00000000000000002"),
+                    class: String::from("IFCWALL"),
+                    ours: Change::Deleted,
+                    theirs: Change::Modified,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,519 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::ifc::geometry::{Element, Vec2, length_unit_scale, signed_area};
+use crate::ifc::step::{Entity, StepFile};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+/// Default height of the horizontal section above the storey elevation, in metres.
+pub const DEFAULT_CUT_HEIGHT: f64 = 1.2;
+
+/// Tolerance used when joining section segments into polylines, in metres.
+const EPSILON: f64 = 1e-6;
+
+/// Margin around the drawing, in metres.
+const MARGIN: f64 = 0.5;
+
+/// The drawing layers of a floor plan, in paint order.
+const LAYERS: [Layer; 5] = [
+    Layer::Spaces,
+    Layer::Other,
+    Layer::Walls,
+    Layer::Windows,
+    Layer::Doors,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Layer {
+    Spaces,
+    Walls,
+    Doors,
+    Windows,
+    Other,
+}
+
+impl Layer {
+    fn of(class: &str) -> Self {
+        match class {
+            "IFCWALL" | "IFCWALLSTANDARDCASE" | "IFCWALLELEMENTEDCASE" | "IFCCURTAINWALL" => {
+                Layer::Walls
+            }
+            "IFCDOOR" | "IFCDOORSTANDARDCASE" => Layer::Doors,
+            "IFCWINDOW" | "IFCWINDOWSTANDARDCASE" => Layer::Windows,
+            "IFCSPACE" => Layer::Spaces,
+            _ => Layer::Other,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Layer::Spaces => "spaces",
+            Layer::Walls => "walls",
+            Layer::Doors => "doors",
+            Layer::Windows => "windows",
+            Layer::Other => "other",
+        }
+    }
+
+    fn style(self) -> &'static str {
+        match self {
+            Layer::Spaces => "fill:#eef3f8;stroke:#9fb3c8;stroke-width:0.01",
+            Layer::Walls => "fill:#3c3c3c;fill-rule:evenodd;stroke:#000;stroke-width:0.02",
+            Layer::Doors => "fill:none;stroke:#8a5a2b;stroke-width:0.02",
+            Layer::Windows => "fill:none;stroke:#2b6fa8;stroke-width:0.02",
+            Layer::Other => "fill:#bbb;stroke:#555;stroke-width:0.015",
+        }
+    }
+}
+
+/// A section outline of one element.
+struct Section {
+    global_id: String,
+    class: String,
+    polylines: Vec<Vec<Vec2>>,
+}
+
+/// Renders a horizontal section through a building storey as SVG.
+///
+/// # Arguments
+/// * `file` - The parsed IFC file.
+/// * `elements` - The tessellated products of the file.
+/// * `storey` - The GlobalId of the `IfcBuildingStorey`.
+/// * `cut_height` - Height of the section plane above the storey elevation, in metres.
+///
+/// # Returns
+/// The SVG document, or `None` if the storey does not exist.
+pub fn floor_plan(
+    file: &StepFile,
+    elements: &[Element],
+    storey: &str,
+    cut_height: f64,
+) -> Option<String> {
+    let storey = file
+        .by_class("IFCBUILDINGSTOREY")
+        .find(|s| s.param(0).as_str() == Some(storey))?;
+    let elevation = storey.param(9).as_f64().unwrap_or(0.0) * length_unit_scale(file);
+    let z = elevation + cut_height;
+
+    let members = storey_members(file, storey);
+    let openings = openings_by_element(file);
+    let by_id: HashMap<u64, &Element> = elements.iter().map(|e| (e.id, e)).collect();
+
+    let mut layers: HashMap<Layer, Vec<Section>> = HashMap::new();
+    let mut labels = Vec::new();
+    for element in elements.iter().filter(|e| members.contains(&e.id)) {
+        let mut segments = cut(element, z);
+        if let Some(voids) = openings.get(&element.id) {
+            let holes: Vec<Vec<[Vec2; 2]>> = voids
+                .iter()
+                .filter_map(|id| by_id.get(id))
+                .map(|opening| cut(opening, z))
+                .filter(|s| !s.is_empty())
+                .collect();
+            segments = subtract(segments, &holes);
+        }
+        if segments.is_empty() {
+            continue;
+        }
+        let polylines = chain(segments);
+        let layer = Layer::of(&element.class);
+        if layer == Layer::Spaces {
+            labels.extend(space_label(file, element, &polylines));
+        }
+        layers.entry(layer).or_default().push(Section {
+            global_id: element.global_id.clone(),
+            class: element.class.clone(),
+            polylines,
+        });
+    }
+    Some(to_svg(&layers, &labels))
+}
+
+/// Collects the ids of all products contained in or aggregated below a storey.
+fn storey_members(file: &StepFile, storey: &Entity) -> HashSet<u64> {
+    let mut members = HashSet::new();
+    for rel in file.by_class("IFCRELCONTAINEDINSPATIALSTRUCTURE") {
+        if rel.param(5).as_ref_id() == Some(storey.id) {
+            members.extend(rel.param(4).as_list().iter().filter_map(|v| v.as_ref_id()));
+        }
+    }
+    let mut parents: Vec<u64> = members.iter().copied().chain([storey.id]).collect();
+    while let Some(parent) = parents.pop() {
+        for rel in file.by_class("IFCRELAGGREGATES") {
+            if rel.param(4).as_ref_id() != Some(parent) {
+                continue;
+            }
+            for child in rel.param(5).as_list().iter().filter_map(|v| v.as_ref_id()) {
+                let is_storey = file
+                    .get(child)
+                    .is_some_and(|c| c.class == "IFCBUILDINGSTOREY");
+                if !is_storey && members.insert(child) {
+                    parents.push(child);
+                }
+            }
+        }
+    }
+    members
+}
+
+/// Maps building elements to the opening elements that void them.
+fn openings_by_element(file: &StepFile) -> HashMap<u64, Vec<u64>> {
+    let mut openings: HashMap<u64, Vec<u64>> = HashMap::new();
+    for rel in file.by_class("IFCRELVOIDSELEMENT") {
+        if let (Some(element), Some(opening)) = (rel.param(4).as_ref_id(), rel.param(5).as_ref_id())
+        {
+            openings.entry(element).or_default().push(opening);
+        }
+    }
+    openings
+}
+
+/// Intersects the triangles of an element with the plane at height `z`.
+fn cut(element: &Element, z: f64) -> Vec<[Vec2; 2]> {
+    let mut segments = Vec::new();
+    for tri in element.mesh.faces() {
+        let d = tri.map(|p| p[2] - z);
+        let mut points = Vec::with_capacity(2);
+        for i in 0..3 {
+            let (a, b) = (i, (i + 1) % 3);
+            let (da, db) = (d[a], d[b]);
+            // Treat vertices on the plane as lying above it, so shared edges are cut once.
+            if (da >= 0.0) != (db >= 0.0) {
+                let t = da / (da - db);
+                let (pa, pb) = (tri[a], tri[b]);
+                points.push([pa[0] + t * (pb[0] - pa[0]), pa[1] + t * (pb[1] - pa[1])]);
+            }
+        }
+        if let [a, b] = points[..]
+            && distance(a, b) > EPSILON
+        {
+            segments.push([a, b]);
+        }
+    }
+    segments
+}
+
+fn distance(a: Vec2, b: Vec2) -> f64 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt()
+}
+
+/// Returns `true` if `p` lies inside the region bounded by `segments` (even-odd rule).
+fn inside(p: Vec2, segments: &[[Vec2; 2]]) -> bool {
+    segments
+        .iter()
+        .filter(|[a, b]| {
+            (a[1] > p[1]) != (b[1] > p[1])
+                && p[0] < a[0] + (p[1] - a[1]) * (b[0] - a[0]) / (b[1] - a[1])
+        })
+        .count()
+        % 2
+        == 1
+}
+
+/// Removes the parts of `segments` that fall inside any of the `holes`.
+fn subtract(segments: Vec<[Vec2; 2]>, holes: &[Vec<[Vec2; 2]>]) -> Vec<[Vec2; 2]> {
+    let mut result = Vec::with_capacity(segments.len());
+    for [a, b] in segments {
+        let mut cuts = vec![0.0, 1.0];
+        for [c, d] in holes.iter().flatten() {
+            let r = [b[0] - a[0], b[1] - a[1]];
+            let s = [d[0] - c[0], d[1] - c[1]];
+            let denom = r[0] * s[1] - r[1] * s[0];
+            if denom.abs() < 1e-12 {
+                continue;
+            }
+            let q = [c[0] - a[0], c[1] - a[1]];
+            let t = (q[0] * s[1] - q[1] * s[0]) / denom;
+            let u = (q[0] * r[1] - q[1] * r[0]) / denom;
+            if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+                cuts.push(t);
+            }
+        }
+        cuts.sort_by(f64::total_cmp);
+        for pair in cuts.windows(2) {
+            let lerp = |t: f64| [a[0] + t * (b[0] - a[0]), a[1] + t * (b[1] - a[1])];
+            let (p, q) = (lerp(pair[0]), lerp(pair[1]));
+            if distance(p, q) <= EPSILON {
+                continue;
+            }
+            let mid = lerp((pair[0] + pair[1]) / 2.0);
+            if !holes.iter().any(|hole| inside(mid, hole)) {
+                result.push([p, q]);
+            }
+        }
+    }
+    result
+}
+
+/// Joins loose segments into polylines; closed loops repeat their first point at the end.
+fn chain(segments: Vec<[Vec2; 2]>) -> Vec<Vec<Vec2>> {
+    let key = |p: Vec2| {
+        (
+            (p[0] / EPSILON / 10.0).round() as i64,
+            (p[1] / EPSILON / 10.0).round() as i64,
+        )
+    };
+    let mut by_point: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    for (i, [a, b]) in segments.iter().enumerate() {
+        by_point.entry(key(*a)).or_default().push(i);
+        by_point.entry(key(*b)).or_default().push(i);
+    }
+    let mut used = vec![false; segments.len()];
+    let mut polylines = Vec::new();
+    for start in 0..segments.len() {
+        if used[start] {
+            continue;
+        }
+        used[start] = true;
+        let mut line = vec![segments[start][0], segments[start][1]];
+        // Extend forwards from the tail, then backwards from the head.
+        for _ in 0..2 {
+            loop {
+                let tail = *line.last().unwrap();
+                let next = by_point
+                    .get(&key(tail))
+                    .and_then(|c| c.iter().copied().find(|&i| !used[i]));
+                let Some(next) = next else { break };
+                used[next] = true;
+                let [a, b] = segments[next];
+                line.push(if key(a) == key(tail) { b } else { a });
+            }
+            line.reverse();
+        }
+        polylines.push(line);
+    }
+    polylines
+}
+
+fn is_closed(line: &[Vec2]) -> bool {
+    line.len() > 3 && distance(line[0], line[line.len() - 1]) <= EPSILON * 10.0
+}
+
+struct Label {
+    position: Vec2,
+    lines: Vec<String>,
+}
+
+/// Builds the name and area label for a space from its largest closed outline.
+fn space_label(file: &StepFile, element: &Element, polylines: &[Vec<Vec2>]) -> Option<Label> {
+    let outline = polylines
+        .iter()
+        .filter(|l| is_closed(l))
+        .max_by(|a, b| signed_area(a).abs().total_cmp(&signed_area(b).abs()))?;
+    let area = signed_area(&outline[..outline.len() - 1]).abs() / 2.0;
+    let n = (outline.len() - 1) as f64;
+    let centroid = outline[..outline.len() - 1]
+        .iter()
+        .fold([0.0, 0.0], |acc, p| [acc[0] + p[0] / n, acc[1] + p[1] / n]);
+    let entity = file.get(element.id);
+    let long_name = entity.and_then(|e| e.param(7).as_str());
+    let mut lines: Vec<String> = [element.name.as_deref(), long_name]
+        .into_iter()
+        .flatten()
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect();
+    lines.push(format!("{area:.2} m²"));
+    Some(Label {
+        position: centroid,
+        lines,
+    })
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn to_svg(layers: &HashMap<Layer, Vec<Section>>, labels: &[Label]) -> String {
+    let points = layers
+        .values()
+        .flatten()
+        .flat_map(|s| s.polylines.iter().flatten());
+    let (mut min, mut max) = ([f64::INFINITY; 2], [f64::NEG_INFINITY; 2]);
+    for p in points {
+        min = [min[0].min(p[0]), min[1].min(p[1])];
+        max = [max[0].max(p[0]), max[1].max(p[1])];
+    }
+    if min[0] > max[0] {
+        (min, max) = ([0.0; 2], [0.0; 2]);
+    }
+    // SVG's y axis points down, so plan coordinates are mirrored on y.
+    let (x, y) = (min[0] - MARGIN, -max[1] - MARGIN);
+    let (w, h) = (
+        max[0] - min[0] + 2.0 * MARGIN,
+        max[1] - min[1] + 2.0 * MARGIN,
+    );
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{x:.3} {y:.3} {w:.3} {h:.3}" width="{:.0}mm" height="{:.0}mm">"#,
+        w * 10.0,
+        h * 10.0
+    );
+    for layer in LAYERS {
+        let Some(sections) = layers.get(&layer) else {
+            continue;
+        };
+        let _ = writeln!(
+            svg,
+            r#"<g id="{0}" class="{0}" style="{1}">"#,
+            layer.name(),
+            layer.style()
+        );
+        for section in sections {
+            let mut d = String::new();
+            for line in &section.polylines {
+                for (i, p) in line.iter().enumerate() {
+                    let _ = write!(
+                        d,
+                        "{}{:.3} {:.3} ",
+                        if i == 0 { "M" } else { "L" },
+                        p[0],
+                        -p[1]
+                    );
+                }
+                if is_closed(line) {
+                    d.push_str("Z ");
+                }
+            }
+            let _ = writeln!(
+                svg,
+                r#"<path data-global-id="{}" data-class="{}" d="{}"/>"#,
+                escape(&section.global_id),
+                escape(&section.class),
+                d.trim_end()
+            );
+        }
+        svg.push_str("</g>\n");
+    }
+    if !labels.is_empty() {
+        svg.push_str(
+            r#"<g id="labels" class="labels" style="font-family:sans-serif;font-size:0.25px;fill:#223;text-anchor:middle">"#,
+        );
+        svg.push('\n');
+        for label in labels {
+            let _ = write!(
+                svg,
+                r#"<text x="{:.3}" y="{:.3}">"#,
+                label.position[0], -label.position[1]
+            );
+            for (i, line) in label.lines.iter().enumerate() {
+                let _ = write!(
+                    svg,
+                    r#"<tspan x="{:.3}" dy="{}">{}</tspan>"#,
+                    label.position[0],
+                    if i == 0 { "0" } else { "1.2em" },
+                    escape(line)
+                );
+            }
+            svg.push_str("</text>\n");
+        }
+        svg.push_str("</g>\n");
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ifc::geometry::extract;
+    use crate::ifc::step::parse;
+
+    const STOREY: &str = r#"ISO-10303-21;
+HEADER;
+FILE_SCHEMA(('IFC4'));
+ENDSEC;
+DATA;
+#10=IFCCARTESIANPOINT((0.,0.,0.));
+#11=IFCDIRECTION((0.,0.,1.));
+#12=IFCAXIS2PLACEMENT3D(#10,$,$);
+#13=IFCLOCALPLACEMENT($,#12);
+#14=IFCCARTESIANPOINT((0.,0.,3.));
+#15=IFCAXIS2PLACEMENT3D(#14,$,$);
+#16=IFCLOCALPLACEMENT(#13,#15);
+#20=IFCBUILDINGSTOREY('0storey0000000000000001',$,'Level 1',$,$,#16,$,$,.ELEMENT.,3.);
+#30=IFCRECTANGLEPROFILEDEF(.AREA.,$,$,4.,0.2);
+#31=IFCEXTRUDEDAREASOLID(#30,#12,#11,2.5);
+#32=IFCSHAPEREPRESENTATION($,'Body','SweptSolid',(#31));
+#33=IFCPRODUCTDEFINITIONSHAPE($,$,(#32));
+#34=IFCWALL('0wall000000000000000001',$,'Wall',$,$,#16,#33,$,$);
+#40=IFCRECTANGLEPROFILEDEF(.AREA.,$,$,1.,0.4);
+#41=IFCEXTRUDEDAREASOLID(#40,#12,#11,2.);
+#42=IFCSHAPEREPRESENTATION($,'Body','SweptSolid',(#41));
+#43=IFCPRODUCTDEFINITIONSHAPE($,$,(#42));
+#44=IFCOPENINGELEMENT('0open000000000000000001',$,$,$,$,#16,#43,$,$);
+#45=IFCRELVOIDSELEMENT('0rvoid00000000000000001',$,$,$,#34,#44);
+#50=IFCRECTANGLEPROFILEDEF(.AREA.,$,$,4.,3.);
+#51=IFCEXTRUDEDAREASOLID(#50,#12,#11,2.5);
+#52=IFCSHAPEREPRESENTATION($,'Body','SweptSolid',(#51));
+#53=IFCPRODUCTDEFINITIONSHAPE($,$,(#52));
+#54=IFCSPACE('0space00000000000000001',$,'101',$,$,#16,#53,'Kitchen & Dining',.ELEMENT.,$,$);
+#60=IFCRELCONTAINEDINSPATIALSTRUCTURE('0rcont00000000000000001',$,$,$,(#34),#20);
+#61=IFCRELAGGREGATES('0ragg000000000000000001',$,$,$,#20,(#54));
+ENDSEC;
+END-ISO-10303-21;
+"#;
+
+    fn plan(cut_height: f64) -> String {
+        let file = parse(STOREY).expect("valid STEP");
+        let elements = extract(&file);
+        floor_plan(&file, &elements, "0storey0000000000000001", cut_height).expect("storey")
+    }
+
+    #[test]
+    fn test_floor_plan_groups_tagged_paths() {
+        let svg = plan(DEFAULT_CUT_HEIGHT);
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains(r#"<g id="walls""#));
+        assert!(svg.contains(r#"<g id="spaces""#));
+        assert!(svg.contains(r#"data-global-id="0wall000000000000000001""#));
+        assert!(svg.contains(r#"data-global-id="0space00000000000000001""#));
+        assert!(!svg.contains("0open000000000000000001"));
+    }
+
+    #[test]
+    fn test_floor_plan_labels_space_name_and_area() {
+        let svg = plan(DEFAULT_CUT_HEIGHT);
+
+        assert!(svg.contains(">101<"));
+        assert!(svg.contains(">Kitchen &amp; Dining<"));
+        assert!(svg.contains(">12.00 m²<"));
+    }
+
+    #[test]
+    fn test_floor_plan_cut_above_geometry_is_empty() {
+        let svg = plan(10.0);
+
+        assert!(!svg.contains("<path"));
+    }
+
+    #[test]
+    fn test_subtract_removes_opening_from_wall() {
+        let wall = vec![[[-2.0, 0.0], [2.0, 0.0]]];
+        let opening = vec![
+            [[-0.5, -1.0], [0.5, -1.0]],
+            [[0.5, -1.0], [0.5, 1.0]],
+            [[0.5, 1.0], [-0.5, 1.0]],
+            [[-0.5, 1.0], [-0.5, -1.0]],
+        ];
+        let remaining = subtract(wall, &[opening]);
+
+        assert_eq!(remaining.len(), 2);
+        let length: f64 = remaining.iter().map(|[a, b]| distance(*a, *b)).sum();
+        assert!((length - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_unknown_storey_returns_none() {
+        let file = parse(STOREY).expect("valid STEP");
+
+        assert!(floor_plan(&file, &[], "missing", DEFAULT_CUT_HEIGHT).is_none());
+    }
+}
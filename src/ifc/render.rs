@@ -0,0 +1,278 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::ifc::geometry::{Element, Vec3, cross, dot, normalize, sub};
+use std::f64::consts::FRAC_1_SQRT_2;
+
+/// Edge length of rendered thumbnails in pixels.
+pub const THUMBNAIL_SIZE: u32 = 256;
+
+/// Supersampling factor per axis used for anti-aliasing.
+const SUPERSAMPLE: u32 = 2;
+
+/// Fraction of the image kept free around the model.
+const MARGIN: f64 = 0.06;
+
+/// Direction towards the viewer for the isometric projection.
+const VIEW: Vec3 = [0.577_350_269, -0.577_350_269, 0.577_350_269];
+/// Screen-space right vector, perpendicular to `VIEW`.
+const RIGHT: Vec3 = [FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.0];
+/// Screen-space up vector, `VIEW x RIGHT`.
+const UP: Vec3 = [-0.408_248_290, 0.408_248_290, 0.816_496_581];
+/// Directional light used for flat shading.
+const LIGHT: Vec3 = [0.267_261_242, -0.534_522_484, 0.801_783_726];
+
+/// Classes that are not drawn because they are invisible or would hide the building.
+const HIDDEN_CLASSES: [&str; 4] = [
+    "IFCOPENINGELEMENT",
+    "IFCSPACE",
+    "IFCVIRTUALELEMENT",
+    "IFCANNOTATION",
+];
+
+/// An RGBA8 image.
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[u8; 4]>,
+}
+
+impl Image {
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![[0; 4]; (width * height) as usize],
+        }
+    }
+
+    /// Encodes the image as PNG.
+    ///
+    /// # Returns
+    /// A `Result` containing the PNG bytes.
+    pub fn to_png(&self) -> Result<Vec<u8>, png::EncodingError> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(self.pixels.as_flattened())?;
+        writer.finish()?;
+        Ok(bytes)
+    }
+
+    /// Averages blocks of `factor x factor` pixels into a smaller image.
+    fn downsample(&self, factor: u32) -> Image {
+        let mut out = Image::new(self.width / factor, self.height / factor);
+        for y in 0..out.height {
+            for x in 0..out.width {
+                let mut sum = [0u32; 4];
+                for dy in 0..factor {
+                    for dx in 0..factor {
+                        let p = self.pixels
+                            [((y * factor + dy) * self.width + x * factor + dx) as usize];
+                        // Premultiply so transparent background does not darken edges.
+                        let a = p[3] as u32;
+                        sum[0] += p[0] as u32 * a;
+                        sum[1] += p[1] as u32 * a;
+                        sum[2] += p[2] as u32 * a;
+                        sum[3] += a;
+                    }
+                }
+                let alpha = sum[3];
+                out.pixels[(y * out.width + x) as usize] = match std::num::NonZeroU32::new(alpha) {
+                    None => [0; 4],
+                    Some(a) => [
+                        (sum[0] / a) as u8,
+                        (sum[1] / a) as u8,
+                        (sum[2] / a) as u8,
+                        (alpha / (factor * factor)) as u8,
+                    ],
+                };
+            }
+        }
+        out
+    }
+}
+
+/// Returns the base colour used for an IFC class.
+fn class_color(class: &str) -> [f64; 3] {
+    match class {
+        "IFCWALL" | "IFCWALLSTANDARDCASE" | "IFCCURTAINWALL" => [0.86, 0.84, 0.80],
+        "IFCSLAB" | "IFCROOF" | "IFCCOVERING" => [0.70, 0.70, 0.72],
+        "IFCWINDOW" | "IFCPLATE" => [0.55, 0.75, 0.92],
+        "IFCDOOR" => [0.65, 0.47, 0.32],
+        "IFCBEAM" | "IFCCOLUMN" | "IFCMEMBER" => [0.60, 0.62, 0.66],
+        "IFCSTAIR" | "IFCSTAIRFLIGHT" | "IFCRAMP" | "IFCRAILING" => [0.76, 0.70, 0.60],
+        "IFCFURNISHINGELEMENT" | "IFCFURNITURE" => [0.80, 0.62, 0.45],
+        "IFCSITE" | "IFCGEOGRAPHICELEMENT" => [0.55, 0.68, 0.45],
+        class if class.starts_with("IFCFLOW") || class.starts_with("IFCDISTRIBUTION") => {
+            [0.85, 0.55, 0.30]
+        }
+        _ => [0.78, 0.78, 0.78],
+    }
+}
+
+/// Renders an isometric view of the given elements with a z-buffered software rasterizer.
+///
+/// # Arguments
+/// * `elements` - The tessellated model elements.
+/// * `size` - The edge length of the square output image in pixels.
+///
+/// # Returns
+/// The rendered image with a transparent background.
+pub fn isometric(elements: &[Element], size: u32) -> Image {
+    let visible: Vec<&Element> = elements
+        .iter()
+        .filter(|e| !HIDDEN_CLASSES.contains(&e.class.as_str()))
+        .collect();
+
+    let projected = |p: Vec3| [dot(p, RIGHT), dot(p, UP), dot(p, VIEW)];
+    let mut min = [f64::INFINITY; 2];
+    let mut max = [f64::NEG_INFINITY; 2];
+    for p in visible.iter().flat_map(|e| e.mesh.positions.iter()) {
+        let q = projected(*p);
+        min = [min[0].min(q[0]), min[1].min(q[1])];
+        max = [max[0].max(q[0]), max[1].max(q[1])];
+    }
+
+    let full = size * SUPERSAMPLE;
+    let mut image = Image::new(full, full);
+    if min[0] > max[0] {
+        return image.downsample(SUPERSAMPLE);
+    }
+    let extent = (max[0] - min[0]).max(max[1] - min[1]).max(1e-9);
+    let scale = full as f64 * (1.0 - 2.0 * MARGIN) / extent;
+    let center = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0];
+    let half = full as f64 / 2.0;
+    let to_screen = |p: Vec3| {
+        let q = projected(p);
+        [
+            half + (q[0] - center[0]) * scale,
+            half - (q[1] - center[1]) * scale,
+            q[2],
+        ]
+    };
+
+    let mut depth = vec![f64::NEG_INFINITY; (full * full) as usize];
+    for element in visible {
+        let base = class_color(&element.class);
+        for [a, b, c] in element.mesh.faces() {
+            let Some(mut normal) = normalize(cross(sub(b, a), sub(c, a))) else {
+                continue;
+            };
+            // Triangle winding is unreliable across exporters, so shade both sides.
+            if dot(normal, VIEW) < 0.0 {
+                normal = normal.map(|n| -n);
+            }
+            let intensity = 0.45 + 0.55 * dot(normal, LIGHT).max(0.0);
+            let color = [
+                (base[0] * intensity * 255.0) as u8,
+                (base[1] * intensity * 255.0) as u8,
+                (base[2] * intensity * 255.0) as u8,
+                255,
+            ];
+            rasterize(
+                &mut image,
+                &mut depth,
+                [to_screen(a), to_screen(b), to_screen(c)],
+                color,
+            );
+        }
+    }
+    image.downsample(SUPERSAMPLE)
+}
+
+/// Fills a screen-space triangle, keeping the fragment nearest to the viewer.
+fn rasterize(image: &mut Image, depth: &mut [f64], tri: [Vec3; 3], color: [u8; 4]) {
+    let [a, b, c] = tri;
+    let area = (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]);
+    if area.abs() < 1e-12 {
+        return;
+    }
+    let x0 = a[0].min(b[0]).min(c[0]).floor().max(0.0) as u32;
+    let y0 = a[1].min(b[1]).min(c[1]).floor().max(0.0) as u32;
+    let x1 = (a[0].max(b[0]).max(c[0]).ceil() as u32).min(image.width - 1);
+    let y1 = (a[1].max(b[1]).max(c[1]).ceil() as u32).min(image.height - 1);
+    for y in y0..=y1 {
+        for x in x0..=x1 {
+            let (px, py) = (x as f64 + 0.5, y as f64 + 0.5);
+            let w0 = ((b[0] - px) * (c[1] - py) - (b[1] - py) * (c[0] - px)) / area;
+            let w1 = ((c[0] - px) * (a[1] - py) - (c[1] - py) * (a[0] - px)) / area;
+            let w2 = 1.0 - w0 - w1;
+            if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                continue;
+            }
+            let z = w0 * a[2] + w1 * b[2] + w2 * c[2];
+            let index = (y * image.width + x) as usize;
+            if z > depth[index] {
+                depth[index] = z;
+                image.pixels[index] = color;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ifc::geometry::Mesh;
+
+    fn cube() -> Element {
+        let positions = vec![
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [1.0, 0.0, 1.0],
+            [1.0, 1.0, 1.0],
+            [0.0, 1.0, 1.0],
+        ];
+        let quads = [
+            [0, 3, 2, 1],
+            [4, 5, 6, 7],
+            [0, 1, 5, 4],
+            [1, 2, 6, 5],
+            [2, 3, 7, 6],
+            [3, 0, 4, 7],
+        ];
+        let triangles = quads
+            .iter()
+            .flat_map(|q| [[q[0], q[1], q[2]], [q[0], q[2], q[3]]])
+            .collect();
+        Element {
+            id: 1,
+            global_id: String::from("0cube00000000000000001"),
+            class: String::from("IFCWALL"),
+            name: None,
+            mesh: Mesh {
+                positions,
+                triangles,
+            },
+        }
+    }
+
+    #[test]
+    fn test_isometric_renders_centered_opaque_model() {
+        let image = isometric(&[cube()], 64);
+
+        assert_eq!(image.pixels.len(), 64 * 64);
+        assert_eq!(image.pixels[32 * 64 + 32][3], 255);
+        assert_eq!(image.pixels[0][3], 0);
+    }
+
+    #[test]
+    fn test_isometric_empty_model_is_transparent() {
+        let image = isometric(&[], 16);
+
+        assert!(image.pixels.iter().all(|p| p[3] == 0));
+    }
+
+    #[test]
+    fn test_to_png_writes_signature() {
+        let png = isometric(&[cube()], 16).to_png().expect("encodable image");
+
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    }
+}
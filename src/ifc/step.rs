@@ -0,0 +1,966 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{BufRead, Write};
+
+/// A single attribute value of a STEP (ISO 10303-21) entity instance.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Derived,
+    Integer(i64),
+    Real(f64),
+    String(String),
+    Enum(String),
+    Binary(String),
+    Ref(u64),
+    List(Vec<Value>),
+    Typed(String, Box<Value>),
+}
+
+static NULL: Value = Value::Null;
+
+impl Value {
+    /// Returns the referenced entity id, if this value is a reference.
+    pub fn as_ref_id(&self) -> Option<u64> {
+        match self {
+            Value::Ref(id) => Some(*id),
+            Value::Typed(_, inner) => inner.as_ref_id(),
+            _ => None,
+        }
+    }
+
+    /// Returns the numeric value, accepting both integers and reals.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(i) => Some(*i as f64),
+            Value::Real(r) => Some(*r),
+            Value::Typed(_, inner) => inner.as_f64(),
+            _ => None,
+        }
+    }
+
+    /// Returns the string value, if this value is a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            Value::Typed(_, inner) => inner.as_str(),
+            _ => None,
+        }
+    }
+
+    /// Returns the enumeration literal without the surrounding dots.
+    pub fn as_enum(&self) -> Option<&str> {
+        match self {
+            Value::Enum(e) => Some(e),
+            Value::Typed(_, inner) => inner.as_enum(),
+            _ => None,
+        }
+    }
+
+    /// Returns the list items, or an empty slice for non-list values.
+    pub fn as_list(&self) -> &[Value] {
+        match self {
+            Value::List(items) => items,
+            Value::Typed(_, inner) => inner.as_list(),
+            _ => &[],
+        }
+    }
+}
+
+/// An entity instance from the DATA section, e.g. `#12=IFCWALL(...)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entity {
+    pub id: u64,
+    pub class: String,
+    pub params: Vec<Value>,
+}
+
+impl Entity {
+    /// Returns the attribute at `index`, or `Value::Null` if it does not exist.
+    pub fn param(&self, index: usize) -> &Value {
+        self.params.get(index).unwrap_or(&NULL)
+    }
+}
+
+/// A record from the HEADER section, e.g. `FILE_SCHEMA(('IFC4'))`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeaderRecord {
+    pub name: String,
+    pub params: Vec<Value>,
+}
+
+/// A fully parsed STEP physical file.
+#[derive(Debug, Default)]
+pub struct StepFile {
+    pub header: Vec<HeaderRecord>,
+    pub entities: HashMap<u64, Entity>,
+}
+
+impl StepFile {
+    /// Returns the entity with the given id.
+    pub fn get(&self, id: u64) -> Option<&Entity> {
+        self.entities.get(&id)
+    }
+
+    /// Resolves a reference value to the entity it points to.
+    pub fn resolve(&self, value: &Value) -> Option<&Entity> {
+        value.as_ref_id().and_then(|id| self.get(id))
+    }
+
+    /// Returns all entities of the given (upper-case) class.
+    pub fn by_class<'a>(&'a self, class: &'a str) -> impl Iterator<Item = &'a Entity> + 'a {
+        self.entities.values().filter(move |e| e.class == class)
+    }
+
+    /// Returns the first schema identifier declared in `FILE_SCHEMA`.
+    pub fn schema(&self) -> Option<&str> {
+        self.header
+            .iter()
+            .find(|r| r.name == "FILE_SCHEMA")
+            .and_then(|r| r.params.first())
+            .and_then(|v| v.as_list().first())
+            .and_then(Value::as_str)
+    }
+}
+
+/// An error encountered while parsing a STEP file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepError {
+    pub message: String,
+}
+
+impl StepError {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for StepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "STEP parse error: {}", self.message)
+    }
+}
+
+impl std::error::Error for StepError {}
+
+/// Parses the complete text of a STEP physical file.
+///
+/// # Arguments
+/// * `content` - The STEP file text.
+///
+/// # Returns
+/// A `Result` containing the parsed header and entity instances.
+pub fn parse(content: &str) -> Result<StepFile, StepError> {
+    StepFile::read(content.as_bytes())
+}
+
+impl StepFile {
+    /// Reads a complete STEP file from a buffered reader.
+    ///
+    /// # Arguments
+    /// * `reader` - The source of the STEP text.
+    ///
+    /// # Returns
+    /// A `Result` containing the parsed header and entity instances.
+    pub fn read<R: BufRead>(reader: R) -> Result<Self, StepError> {
+        let mut file = Self::default();
+        for record in StepReader::new(reader) {
+            match record? {
+                Record::Header(header) => file.header.push(header),
+                Record::Entity(entity) => {
+                    file.entities.insert(entity.id, entity);
+                }
+            }
+        }
+        Ok(file)
+    }
+}
+
+/// Default upper bound for the size of a single record, in bytes.
+pub const DEFAULT_MAX_RECORD_LEN: usize = 64 * 1024 * 1024;
+
+/// A header or data record yielded by [`StepReader`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Record {
+    Header(HeaderRecord),
+    Entity(Entity),
+}
+
+#[derive(Clone, Copy)]
+enum Section {
+    None,
+    Header,
+    Data,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Lexer {
+    Normal,
+    Slash,
+    String,
+    Comment,
+    CommentStar,
+}
+
+/// Incremental STEP reader that yields one record at a time.
+///
+/// Only the record currently being read is buffered, so memory use is bounded by the
+/// largest record (capped by [`StepReader::with_max_record_len`]) rather than the file size.
+pub struct StepReader<R> {
+    inner: R,
+    record: Vec<u8>,
+    lexer: Lexer,
+    section: Section,
+    max_record_len: usize,
+    offset: u64,
+    failed: bool,
+}
+
+impl<R: BufRead> StepReader<R> {
+    /// Creates a reader with the default record size limit.
+    ///
+    /// # Arguments
+    /// * `inner` - The source of the STEP text.
+    ///
+    /// # Returns
+    /// A new `StepReader` instance.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            record: Vec::new(),
+            lexer: Lexer::Normal,
+            section: Section::None,
+            max_record_len: DEFAULT_MAX_RECORD_LEN,
+            offset: 0,
+            failed: false,
+        }
+    }
+
+    /// Sets the maximum size of a single record; longer records fail with an error.
+    ///
+    /// # Arguments
+    /// * `max_record_len` - The limit in bytes.
+    ///
+    /// # Returns
+    /// The reader with the new limit.
+    pub fn with_max_record_len(mut self, max_record_len: usize) -> Self {
+        self.max_record_len = max_record_len;
+        self
+    }
+
+    /// Returns the number of bytes consumed from the underlying reader so far.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Reads the next `;`-terminated record, skipping comments.
+    fn next_raw(&mut self) -> Result<Option<String>, StepError> {
+        loop {
+            let buf = self
+                .inner
+                .fill_buf()
+                .map_err(|e| StepError::new(format!("read failed: {e}")))?;
+            if buf.is_empty() {
+                if self.record.iter().all(u8::is_ascii_whitespace) {
+                    return Ok(None);
+                }
+                return Err(StepError::new(format!(
+                    "unterminated record at end of input: {}",
+                    truncate(&String::from_utf8_lossy(&self.record))
+                )));
+            }
+            let mut consumed = 0;
+            let mut complete = false;
+            for &byte in buf {
+                consumed += 1;
+                if lex(&mut self.lexer, &mut self.record, byte) {
+                    complete = true;
+                    break;
+                }
+                if self.record.len() > self.max_record_len {
+                    break;
+                }
+            }
+            self.inner.consume(consumed);
+            self.offset += consumed as u64;
+            if complete {
+                let record = String::from_utf8_lossy(&self.record).into_owned();
+                self.record.clear();
+                return Ok(Some(record));
+            }
+            if self.record.len() > self.max_record_len {
+                return Err(StepError::new(format!(
+                    "record starting with '{}' exceeds {} bytes",
+                    truncate(&String::from_utf8_lossy(&self.record)),
+                    self.max_record_len
+                )));
+            }
+        }
+    }
+}
+
+/// Feeds one byte through the record lexer; returns `true` at the end of a record.
+fn lex(lexer: &mut Lexer, record: &mut Vec<u8>, byte: u8) -> bool {
+    match (*lexer, byte) {
+        (Lexer::String, b'\'') => {
+            *lexer = Lexer::Normal;
+            record.push(byte);
+        }
+        (Lexer::String, _) => record.push(byte),
+        (Lexer::Comment, b'*') => *lexer = Lexer::CommentStar,
+        (Lexer::CommentStar, b'/') => *lexer = Lexer::Normal,
+        (Lexer::CommentStar, b'*') => {}
+        (Lexer::Comment | Lexer::CommentStar, _) => *lexer = Lexer::Comment,
+        (Lexer::Slash, b'*') => *lexer = Lexer::Comment,
+        (Lexer::Slash, _) => {
+            record.push(b'/');
+            *lexer = Lexer::Normal;
+            return lex(lexer, record, byte);
+        }
+        (Lexer::Normal, b';') => return true,
+        (Lexer::Normal, b'\'') => {
+            *lexer = Lexer::String;
+            record.push(byte);
+        }
+        (Lexer::Normal, b'/') => *lexer = Lexer::Slash,
+        (Lexer::Normal, _) => record.push(byte),
+    }
+    false
+}
+
+impl<R: BufRead> Iterator for StepReader<R> {
+    type Item = Result<Record, StepError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        loop {
+            let raw = match self.next_raw() {
+                Ok(Some(raw)) => raw,
+                Ok(None) => return None,
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            };
+            let record = raw.trim();
+            let parsed = match record {
+                "ISO-10303-21" | "END-ISO-10303-21" | "" => continue,
+                "HEADER" => {
+                    self.section = Section::Header;
+                    continue;
+                }
+                "DATA" => {
+                    self.section = Section::Data;
+                    continue;
+                }
+                "ENDSEC" => {
+                    self.section = Section::None;
+                    continue;
+                }
+                _ => match self.section {
+                    Section::Header => parse_header(record).map(Record::Header),
+                    Section::Data => parse_entity(record).map(Record::Entity),
+                    Section::None => Err(StepError::new(format!(
+                        "record outside of a section: {}",
+                        truncate(record)
+                    ))),
+                },
+            };
+            self.failed = parsed.is_err();
+            return Some(parsed);
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => f.write_str("$"),
+            Value::Derived => f.write_str("*"),
+            Value::Integer(i) => write!(f, "{i}"),
+            Value::Real(r) => f.write_str(&format_real(*r)),
+            Value::String(s) => f.write_str(&encode_string(s)),
+            Value::Enum(e) => write!(f, ".{e}."),
+            Value::Binary(hex) => write!(f, "\"{hex}\""),
+            Value::Ref(id) => write!(f, "#{id}"),
+            Value::List(items) => {
+                f.write_str("(")?;
+                write_params(f, items)?;
+                f.write_str(")")
+            }
+            Value::Typed(name, inner) => write!(f, "{name}({inner})"),
+        }
+    }
+}
+
+impl fmt::Display for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}={}(", self.id, self.class)?;
+        write_params(f, &self.params)?;
+        f.write_str(");")
+    }
+}
+
+impl fmt::Display for HeaderRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}(", self.name)?;
+        write_params(f, &self.params)?;
+        f.write_str(");")
+    }
+}
+
+fn write_params(f: &mut fmt::Formatter<'_>, params: &[Value]) -> fmt::Result {
+    for (i, param) in params.iter().enumerate() {
+        if i > 0 {
+            f.write_str(",")?;
+        }
+        write!(f, "{param}")?;
+    }
+    Ok(())
+}
+
+/// Formats a real so that it always contains a decimal point, as STEP requires.
+fn format_real(value: f64) -> String {
+    let text = format!("{value:?}").to_ascii_uppercase();
+    match text.split_once('E') {
+        Some((mantissa, exponent)) if !mantissa.contains('.') => format!("{mantissa}.E{exponent}"),
+        Some(_) => text,
+        None if text.ends_with(".0") => text[..text.len() - 1].to_string(),
+        None => text,
+    }
+}
+
+/// Encodes a string literal, escaping quotes, backslashes and non-ASCII characters.
+fn encode_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('\'');
+    let mut wide = Vec::new();
+    let flush = |out: &mut String, wide: &mut Vec<u16>| {
+        if !wide.is_empty() {
+            out.push_str("\\X2\\");
+            for unit in wide.drain(..) {
+                out.push_str(&format!("{unit:04X}"));
+            }
+            out.push_str("\\X0\\");
+        }
+    };
+    for c in value.chars() {
+        if c.is_ascii() && !c.is_ascii_control() {
+            flush(&mut out, &mut wide);
+            match c {
+                '\'' => out.push_str("''"),
+                '\\' => out.push_str("\\\\"),
+                _ => out.push(c),
+            }
+        } else {
+            let mut units = [0u16; 2];
+            wide.extend_from_slice(c.encode_utf16(&mut units));
+        }
+    }
+    flush(&mut out, &mut wide);
+    out.push('\'');
+    out
+}
+
+/// Writes a STEP physical file record by record.
+pub struct StepWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> StepWriter<W> {
+    /// Writes the file preamble and header section.
+    ///
+    /// # Arguments
+    /// * `inner` - The destination.
+    /// * `header` - The header records to write.
+    ///
+    /// # Returns
+    /// A `Result` containing the writer, positioned in the DATA section.
+    pub fn new(mut inner: W, header: &[HeaderRecord]) -> std::io::Result<Self> {
+        writeln!(inner, "ISO-10303-21;")?;
+        writeln!(inner, "HEADER;")?;
+        for record in header {
+            writeln!(inner, "{record}")?;
+        }
+        writeln!(inner, "ENDSEC;")?;
+        writeln!(inner, "DATA;")?;
+        Ok(Self { inner })
+    }
+
+    /// Writes a single entity instance.
+    ///
+    /// # Arguments
+    /// * `entity` - The entity to write.
+    ///
+    /// # Returns
+    /// A `std::io::Result<()>` indicating success or failure.
+    pub fn write(&mut self, entity: &Entity) -> std::io::Result<()> {
+        writeln!(self.inner, "{entity}")
+    }
+
+    /// Closes the DATA section and returns the underlying writer.
+    ///
+    /// # Returns
+    /// A `Result` containing the destination.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        writeln!(self.inner, "ENDSEC;")?;
+        writeln!(self.inner, "END-ISO-10303-21;")?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+/// Parses a header record such as `FILE_NAME('a.ifc',...)`.
+pub(crate) fn parse_header(record: &str) -> Result<HeaderRecord, StepError> {
+    let mut parser = Parser::new(record);
+    let name = parser.keyword()?;
+    let params = parser.params()?;
+    Ok(HeaderRecord { name, params })
+}
+
+/// Parses a data record such as `#12=IFCWALL('2O2Fr$t4X7Zf8NOew3FLOH',...)`.
+pub(crate) fn parse_entity(record: &str) -> Result<Entity, StepError> {
+    let mut parser = Parser::new(record);
+    parser.skip_ws();
+    if !parser.eat('#') {
+        return Err(StepError::new(format!(
+            "expected entity id: {}",
+            truncate(record)
+        )));
+    }
+    let id = parser.integer()? as u64;
+    parser.skip_ws();
+    if !parser.eat('=') {
+        return Err(StepError::new(format!("expected '=' after #{id}")));
+    }
+    parser.skip_ws();
+    if parser.peek() == Some('(') {
+        // Complex instance: (IFCA(...)IFCB(...)); classes are joined and params concatenated.
+        parser.bump();
+        let mut classes = Vec::new();
+        let mut params = Vec::new();
+        loop {
+            parser.skip_ws();
+            if parser.eat(')') {
+                break;
+            }
+            classes.push(parser.keyword()?);
+            params.extend(parser.params()?);
+        }
+        return Ok(Entity {
+            id,
+            class: classes.join(" "),
+            params,
+        });
+    }
+    let class = parser.keyword()?;
+    let params = parser.params()?;
+    Ok(Entity { id, class, params })
+}
+
+fn truncate(record: &str) -> String {
+    record.chars().take(64).collect()
+}
+
+/// Deepest nesting of lists and typed values accepted in a record. Values are parsed
+/// recursively, so without a limit a record of many `(` would overflow the stack.
+const MAX_NESTING: usize = 64;
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(src: &'a str) -> Self {
+        Self {
+            src,
+            pos: 0,
+            depth: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_ws(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    fn error(&self, message: &str) -> StepError {
+        StepError::new(format!(
+            "{message} at offset {} in '{}'",
+            self.pos,
+            truncate(self.src)
+        ))
+    }
+
+    fn keyword(&mut self) -> Result<String, StepError> {
+        self.skip_ws();
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            self.bump();
+        }
+        if start == self.pos {
+            return Err(self.error("expected keyword"));
+        }
+        Ok(self.src[start..self.pos].to_ascii_uppercase())
+    }
+
+    fn integer(&mut self) -> Result<i64, StepError> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.bump();
+        }
+        self.src[start..self.pos]
+            .parse()
+            .map_err(|_| self.error("expected integer"))
+    }
+
+    fn params(&mut self) -> Result<Vec<Value>, StepError> {
+        self.skip_ws();
+        if !self.eat('(') {
+            return Err(self.error("expected '('"));
+        }
+        self.list_items()
+    }
+
+    /// Parses comma separated values up to and including the closing `)`.
+    fn list_items(&mut self) -> Result<Vec<Value>, StepError> {
+        let mut items = Vec::new();
+        self.skip_ws();
+        if self.eat(')') {
+            return Ok(items);
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_ws();
+            match self.bump() {
+                Some(',') => continue,
+                Some(')') => return Ok(items),
+                _ => return Err(self.error("expected ',' or ')'")),
+            }
+        }
+    }
+
+    /// Parses a value nested one level deeper, refusing to exceed `MAX_NESTING`.
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, StepError>,
+    ) -> Result<T, StepError> {
+        if self.depth >= MAX_NESTING {
+            return Err(self.error("values nested too deeply"));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn value(&mut self) -> Result<Value, StepError> {
+        self.skip_ws();
+        match self.peek() {
+            Some('$') => {
+                self.bump();
+                Ok(Value::Null)
+            }
+            Some('*') => {
+                self.bump();
+                Ok(Value::Derived)
+            }
+            Some('#') => {
+                self.bump();
+                Ok(Value::Ref(self.integer()? as u64))
+            }
+            Some('\'') => self.string().map(Value::String),
+            Some('"') => {
+                self.bump();
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c != '"') {
+                    self.bump();
+                }
+                let hex = self.src[start..self.pos].to_string();
+                if !self.eat('"') {
+                    return Err(self.error("unterminated binary"));
+                }
+                Ok(Value::Binary(hex))
+            }
+            Some('.') => {
+                self.bump();
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c != '.') {
+                    self.bump();
+                }
+                let literal = self.src[start..self.pos].to_ascii_uppercase();
+                if !self.eat('.') {
+                    return Err(self.error("unterminated enumeration"));
+                }
+                Ok(Value::Enum(literal))
+            }
+            Some('(') => {
+                self.bump();
+                Ok(Value::List(self.nested(Self::list_items)?))
+            }
+            Some(c) if c == '-' || c == '+' || c.is_ascii_digit() => self.number(),
+            Some(c) if c.is_ascii_alphabetic() => {
+                let name = self.keyword()?;
+                self.skip_ws();
+                if !self.eat('(') {
+                    return Err(self.error("expected '(' after type name"));
+                }
+                let value = self.nested(Self::value)?;
+                self.skip_ws();
+                if !self.eat(')') {
+                    return Err(self.error("expected ')' after typed value"));
+                }
+                Ok(Value::Typed(name, Box::new(value)))
+            }
+            _ => Err(self.error("unexpected character")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, StepError> {
+        let start = self.pos;
+        let mut real = false;
+        while let Some(c) = self.peek() {
+            match c {
+                '0'..='9' | '+' | '-' => {}
+                '.' | 'E' | 'e' => real = true,
+                _ => break,
+            }
+            self.bump();
+        }
+        let text = &self.src[start..self.pos];
+        if real {
+            text.parse()
+                .map(Value::Real)
+                .map_err(|_| self.error("invalid real"))
+        } else {
+            text.parse()
+                .map(Value::Integer)
+                .map_err(|_| self.error("invalid integer"))
+        }
+    }
+
+    fn string(&mut self) -> Result<String, StepError> {
+        self.bump();
+        let mut raw = String::new();
+        loop {
+            match self.bump() {
+                Some('\'') if self.peek() == Some('\'') => {
+                    self.bump();
+                    raw.push('\'');
+                }
+                Some('\'') => return Ok(decode_string(&raw)),
+                Some(c) => raw.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+}
+
+/// Decodes the ISO 10303-21 control directives (`\X2\`, `\X\`, `\S\`, ...) in a string.
+fn decode_string(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(idx) = rest.find('\\') {
+        out.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+        if let Some(tail) = rest.strip_prefix("\\\\") {
+            out.push('\\');
+            rest = tail;
+        } else if let Some(tail) = rest
+            .strip_prefix("\\X2\\")
+            .or_else(|| rest.strip_prefix("\\X4\\"))
+        {
+            let width = if rest.starts_with("\\X2\\") { 4 } else { 8 };
+            let end = tail.find("\\X0\\").unwrap_or(tail.len());
+            let hex = &tail[..end];
+            let units: Vec<u32> = hex
+                .as_bytes()
+                .chunks(width)
+                .filter_map(|c| u32::from_str_radix(std::str::from_utf8(c).ok()?, 16).ok())
+                .collect();
+            if width == 4 {
+                let units: Vec<u16> = units.iter().map(|&u| u as u16).collect();
+                out.push_str(&String::from_utf16_lossy(&units));
+            } else {
+                out.extend(units.into_iter().filter_map(char::from_u32));
+            }
+            rest = tail.get(end + 4..).unwrap_or("");
+        } else if let Some(tail) = rest.strip_prefix("\\X\\") {
+            if let Some(byte) = tail.get(..2).and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(char::from(byte));
+                rest = &tail[2..];
+            } else {
+                rest = tail;
+            }
+        } else if let Some(tail) = rest.strip_prefix("\\S\\") {
+            let mut chars = tail.chars();
+            if let Some(c) = chars.next() {
+                out.extend(char::from_u32(c as u32 + 128));
+            }
+            rest = chars.as_str();
+        } else if rest.starts_with("\\P") && rest.get(3..4) == Some("\\") {
+            rest = &rest[4..];
+        } else {
+            out.push('\\');
+            rest = &rest[1..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"ISO-10303-21;
+HEADER;
+FILE_DESCRIPTION(('ViewDefinition [CoordinationView]'),'2;1');
+FILE_NAME('sample.ifc','2024-01-01T00:00:00',(''),(''),'','','');
+FILE_SCHEMA(('IFC4'));
+ENDSEC;
+DATA;
+/* a comment; with a semicolon */
+#1=IFCCARTESIANPOINT((0.,1.5,-2.E-1));
+#2=IFCWALL('2O2Fr$t4X7Zf8NOew3FLOH',$,'Wall ''A''; north',$,$,#3,#4,$,.STANDARD.);
+#3=IFCPROPERTYSINGLEVALUE('Width',$,IFCLENGTHMEASURE(200),$);
+#4=IFCLABEL('Gr\X2\00FC00DF\X0\e');
+ENDSEC;
+END-ISO-10303-21;
+"#;
+
+    #[test]
+    fn test_parse_header_and_entities() {
+        let file = parse(SAMPLE).expect("valid STEP");
+
+        assert_eq!(file.schema(), Some("IFC4"));
+        assert_eq!(file.entities.len(), 4);
+
+        let point = file.get(1).unwrap();
+        assert_eq!(point.class, "IFCCARTESIANPOINT");
+        let coords: Vec<f64> = point
+            .param(0)
+            .as_list()
+            .iter()
+            .filter_map(Value::as_f64)
+            .collect();
+        assert_eq!(coords, vec![0.0, 1.5, -0.2]);
+    }
+
+    #[test]
+    fn test_parse_strings_enums_and_refs() {
+        let file = parse(SAMPLE).expect("valid STEP");
+        let wall = file.get(2).unwrap();
+
+        assert_eq!(wall.param(2).as_str(), Some("Wall 'A'; north"));
+        assert_eq!(wall.param(5).as_ref_id(), Some(3));
+        assert_eq!(wall.param(8).as_enum(), Some("STANDARD"));
+        assert_eq!(wall.param(42), &Value::Null);
+    }
+
+    #[test]
+    fn test_parse_typed_values_and_encoded_strings() {
+        let file = parse(SAMPLE).expect("valid STEP");
+
+        let property = file.get(3).unwrap();
+        assert_eq!(
+            property.param(2),
+            &Value::Typed("IFCLENGTHMEASURE".into(), Box::new(Value::Integer(200)))
+        );
+        assert_eq!(file.get(4).unwrap().param(0).as_str(), Some("Grüße"));
+    }
+
+    #[test]
+    fn test_reader_streams_records_across_buffer_boundaries() {
+        let reader = std::io::BufReader::with_capacity(1, SAMPLE.as_bytes());
+        let records: Vec<Record> = StepReader::new(reader)
+            .collect::<Result<_, _>>()
+            .expect("valid STEP");
+
+        assert_eq!(records.len(), 7);
+        assert!(matches!(&records[2], Record::Header(h) if h.name == "FILE_SCHEMA"));
+        assert!(matches!(&records[4], Record::Entity(e) if e.class == "IFCWALL"));
+    }
+
+    #[test]
+    fn test_reader_enforces_record_limit() {
+        let mut reader = StepReader::new(SAMPLE.as_bytes()).with_max_record_len(32);
+        let error = reader.find_map(Result::err).expect("limit exceeded");
+
+        assert!(error.message.contains("exceeds 32 bytes"));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_writer_round_trips_entities() {
+        let file = parse(SAMPLE).expect("valid STEP");
+        let mut ids: Vec<u64> = file.entities.keys().copied().collect();
+        ids.sort();
+
+        let mut writer = StepWriter::new(Vec::new(), &file.header).unwrap();
+        for id in &ids {
+            writer.write(file.get(*id).unwrap()).unwrap();
+        }
+        let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+        let reparsed = parse(&output).expect("written STEP is valid");
+
+        assert_eq!(reparsed.header, file.header);
+        for id in ids {
+            assert_eq!(reparsed.get(id), file.get(id));
+        }
+        assert!(output.contains("#1=IFCCARTESIANPOINT((0.,1.5,-0.2));"));
+        assert!(output.contains("'Gr\\X2\\00FC00DF\\X0\\e'"));
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_record() {
+        assert!(parse("DATA;\n#1=IFCWALL('a',;\nENDSEC;").is_err());
+        assert!(parse("#1=IFCWALL();").is_err());
+    }
+
+    #[test]
+    fn test_parse_limits_nesting() {
+        let nested =
+            |depth: usize| format!("#1=IFCWALL({}1{});", "(".repeat(depth), ")".repeat(depth));
+        assert!(parse_entity(&nested(MAX_NESTING)).is_ok());
+        assert!(parse_entity(&nested(MAX_NESTING + 1)).is_err());
+
+        // Far deeper than the stack could take if values were parsed without a limit
+        let hostile = format!("#1=IFCWALL({});", "(".repeat(1_000_000));
+        assert!(parse_entity(&hostile).is_err());
+        let typed = format!("#1=IFCWALL({}1);", "IFCLABEL(".repeat(1_000_000));
+        assert!(parse_entity(&typed).is_err());
+    }
+}
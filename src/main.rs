@@ -3,32 +3,118 @@
 
 pub mod guards {
     pub mod auth;
+    pub mod client;
+    pub mod precondition;
     pub mod ratelimit;
+    pub mod scope;
+}
+
+pub mod ifc {
+    pub mod format;
+    pub mod geometry;
+    pub mod index;
+    pub mod merge;
+    pub mod plan;
+    pub mod render;
+    pub mod step;
 }
 
 pub mod models {
+    pub mod blob;
+    pub mod branch;
     pub mod card;
+    pub mod link;
+    pub mod lock;
+    pub mod revision;
+    pub mod session;
+    pub mod share;
+    pub mod team;
+    pub mod thumbnail;
+    pub mod token;
+    pub mod upload;
     pub mod user;
 }
 
 pub mod routes {
+    pub mod account;
+    pub mod branch;
     pub mod data;
     pub mod github;
     pub mod health;
+    pub mod link;
+    pub mod lock;
+    pub mod oidc;
+    pub mod password;
+    pub mod plan;
+    pub mod resumable;
+    pub mod revision;
+    pub mod session;
+    pub mod share;
+    pub mod team;
+    pub mod thumbnail;
+    pub mod token;
+    pub mod trash;
+    pub mod upload;
+}
+
+pub mod storage {
+    pub mod blob;
+    pub mod fs;
+    pub mod s3;
 }
 
 pub mod config;
 pub mod database;
 pub mod errors;
+pub mod oidc;
+pub mod tasks;
 mod utils;
 
 use crate::config::Config;
-use crate::routes::data::{data_delete, data_get, data_update, data_upload};
-use crate::routes::github::{GitHubUser, github_callback, github_login};
+use crate::oidc::OidcClient;
+use crate::routes::account::{identity_list, identity_unlink, link_start};
+use crate::routes::branch::{
+    branch_commit, branch_create, branch_delete, branch_file, branch_get, branch_list, branch_merge,
+};
+use crate::routes::data::{
+    data_delete, data_file, data_get, data_list, data_patch, data_update, data_upload,
+};
+use crate::routes::github::{
+    GitHubUser, GitLabUser, GoogleUser, MicrosoftUser, github_callback, github_login,
+    gitlab_callback, gitlab_login, google_callback, google_login, microsoft_callback,
+    microsoft_login,
+};
 use crate::routes::health::health;
+use crate::routes::link::{
+    link_accesses, link_create, link_file, link_list, link_open, link_revoke, link_thumbnail,
+};
+use crate::routes::lock::{lock_acquire, lock_break, lock_get, lock_release};
+use crate::routes::oidc::{oidc_callback, oidc_login};
+use crate::routes::password::{
+    password_change, password_login, password_reset, register, user_create,
+};
+use crate::routes::plan::plan_get;
+use crate::routes::resumable::{
+    upload_cancel, upload_chunk, upload_create, upload_finalize, upload_offset,
+};
+use crate::routes::revision::{revision_file, revision_get, revision_list, revision_rollback};
+use crate::routes::session::{
+    logout, session_list, session_revoke, session_revoke_all, session_revoke_others,
+};
+use crate::routes::share::{share_grant, share_list, share_revoke};
+use crate::routes::team::{
+    org_create, org_get, org_invite, org_list, org_members, org_remove, project_create,
+    project_get, project_invite, project_list, project_members, project_remove,
+};
+use crate::routes::thumbnail::thumbnail_get;
+use crate::routes::token::{token_create, token_list, token_revoke};
+use crate::routes::trash::{trash_list, trash_purge, trash_restore};
+use crate::routes::upload::{upload_multipart, upload_raw};
+use crate::storage::blob::BlobStore;
 use database::Database;
 use errors::catchers;
 use rocket::config::SecretKey;
+use rocket::data::Limits;
 use rocket::routes;
 use rocket::{
     Build, Rocket, build, config::TlsConfig, launch, shield::ExpectCt, shield::Feature,
@@ -47,20 +133,101 @@ async fn rocket() -> Rocket<Build> {
             tls: (!config.tls_cert_path.is_empty() && !config.tls_key_path.is_empty())
                 .then(|| TlsConfig::from_paths(&config.tls_cert_path, &config.tls_key_path)),
             secret_key: SecretKey::derive_from(config.secret_key.as_bytes()),
+            limits: Limits::default()
+                .limit("file", config.max_upload_size())
+                .limit("data-form", config.max_upload_size()),
             ..rocket::Config::default()
         })
         .manage(config.clone())
         .manage(Database::new(&config).await)
+        .manage(BlobStore::from_config(&config))
+        .manage(OidcClient::new())
         .mount(
             "/",
             routes![
                 github_login,
                 github_callback,
+                gitlab_login,
+                gitlab_callback,
+                microsoft_login,
+                microsoft_callback,
+                google_login,
+                google_callback,
+                oidc_login,
+                oidc_callback,
+                link_start,
+                identity_list,
+                identity_unlink,
+                register,
+                password_login,
+                password_change,
+                user_create,
+                password_reset,
+                logout,
+                session_list,
+                session_revoke,
+                session_revoke_others,
+                session_revoke_all,
+                token_list,
+                token_create,
+                token_revoke,
                 health,
                 data_upload,
+                data_list,
                 data_get,
+                data_file,
                 data_update,
+                data_patch,
                 data_delete,
+                thumbnail_get,
+                plan_get,
+                upload_multipart,
+                upload_raw,
+                upload_create,
+                upload_offset,
+                upload_chunk,
+                upload_finalize,
+                upload_cancel,
+                revision_list,
+                revision_get,
+                revision_file,
+                revision_rollback,
+                branch_list,
+                branch_create,
+                branch_get,
+                branch_file,
+                branch_commit,
+                branch_delete,
+                branch_merge,
+                lock_get,
+                lock_acquire,
+                lock_release,
+                lock_break,
+                share_list,
+                share_grant,
+                share_revoke,
+                link_create,
+                link_list,
+                link_revoke,
+                link_accesses,
+                link_open,
+                link_file,
+                link_thumbnail,
+                org_create,
+                org_list,
+                org_get,
+                org_members,
+                org_invite,
+                org_remove,
+                project_create,
+                project_list,
+                project_get,
+                project_members,
+                project_invite,
+                project_remove,
+                trash_list,
+                trash_restore,
+                trash_purge,
             ],
         )
         .attach(
@@ -95,5 +262,47 @@ async fn rocket() -> Rocket<Build> {
                 Some(config.github_redirect_url.clone()),
             ),
         ))
+        .attach(OAuth2::<GitLabUser>::custom(
+            HyperRustlsAdapter::default(),
+            OAuthConfig::new(
+                StaticProvider {
+                    auth_uri: format!("{}/oauth/authorize", config.gitlab_url()).into(),
+                    token_uri: format!("{}/oauth/token", config.gitlab_url()).into(),
+                },
+                config.gitlab_client_id.clone(),
+                config.gitlab_client_secret.clone(),
+                Some(config.gitlab_redirect_url.clone()),
+            ),
+        ))
+        .attach(OAuth2::<MicrosoftUser>::custom(
+            HyperRustlsAdapter::default(),
+            OAuthConfig::new(
+                StaticProvider {
+                    auth_uri: format!(
+                        "https://login.microsoftonline.com/{}/oauth2/v2.0/authorize",
+                        config.microsoft_tenant()
+                    )
+                    .into(),
+                    token_uri: format!(
+                        "https://login.microsoftonline.com/{}/oauth2/v2.0/token",
+                        config.microsoft_tenant()
+                    )
+                    .into(),
+                },
+                config.microsoft_client_id.clone(),
+                config.microsoft_client_secret.clone(),
+                Some(config.microsoft_redirect_url.clone()),
+            ),
+        ))
+        .attach(OAuth2::<GoogleUser>::custom(
+            HyperRustlsAdapter::default(),
+            OAuthConfig::new(
+                StaticProvider::Google,
+                config.google_client_id.clone(),
+                config.google_client_secret.clone(),
+                Some(config.google_redirect_url.clone()),
+            ),
+        ))
+        .attach(tasks::cleanup())
         .register("/", catchers())
 }
@@ -0,0 +1,16 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use rocket::serde::{Deserialize, Serialize};
+
+/// A reference to an immutable file in the blob store.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct BlobRef {
+    /// The key of the blob within the store, derived from its hash.
+    pub key: String,
+    /// Lowercase hex SHA-256 digest of the contents.
+    pub sha256: String,
+    /// Size of the contents in bytes.
+    pub size: u64,
+}
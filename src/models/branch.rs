@@ -0,0 +1,42 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use chrono::{DateTime, Utc};
+use rocket::serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+/// Name of the implicit branch that the model record itself tracks.
+pub const MAIN_BRANCH: &str = "main";
+
+/// A named line of revisions developed independently from the main branch.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Branch {
+    pub id: Option<Thing>,
+    /// The model the branch belongs to.
+    pub model: Thing,
+    pub name: String,
+    /// Number of the revision last shared with the main branch, used as the merge base.
+    pub base: u64,
+    /// Number of the latest revision on the branch.
+    pub head: u64,
+    /// Login of the user who created the branch.
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Branch {
+    /// Returns `true` if `name` can be used for a new branch.
+    ///
+    /// # Arguments
+    /// * `name` - The proposed branch name.
+    pub fn is_valid_name(name: &str) -> bool {
+        name != MAIN_BRANCH
+            && !name.is_empty()
+            && name.len() <= 64
+            && name
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+    }
+}
@@ -9,7 +9,11 @@ use surrealdb::sql::Thing;
 #[serde(crate = "rocket::serde")]
 pub struct Card {
     pub id: Option<Thing>,
-    pub thumbnail: Option<String>,
+    /// The model the card presents, whose thumbnail is linked once it is rendered.
+    #[serde(default)]
+    pub model: Option<Thing>,
+    /// Reference to the server-rendered record in the `thumbnails` table.
+    pub thumbnail: Option<Thing>,
     pub title: String,
     pub author: User,
     pub description: String,
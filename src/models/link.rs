@@ -0,0 +1,110 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use chrono::{DateTime, Utc};
+use rocket::serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+/// A single export of a model that a share link may be limited to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase", tag = "kind")]
+pub enum Export {
+    /// The current model file.
+    File,
+    /// The rendered thumbnail.
+    Thumbnail,
+    /// The model file at one revision.
+    Revision { number: u64 },
+}
+
+/// A signed, expiring link granting read-only access to a model without signing in.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ShareLink {
+    pub id: Option<Thing>,
+    /// The shared model.
+    pub model: Thing,
+    /// The export the link is limited to, or `None` for the model and all its exports.
+    pub export: Option<Export>,
+    /// Argon2id hash of the password protecting the link.
+    pub password_hash: Option<String>,
+    /// Login of the user who created the link.
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ShareLink {
+    /// Returns `true` while the link is neither expired nor revoked.
+    pub fn is_active(&self) -> bool {
+        self.revoked_at.is_none() && Utc::now() < self.expires_at
+    }
+
+    /// Returns `true` if the link grants access to an export.
+    ///
+    /// # Arguments
+    /// * `export` - The requested export.
+    pub fn allows(&self, export: Export) -> bool {
+        self.export.is_none_or(|allowed| allowed == export)
+    }
+}
+
+/// One use of a share link, recorded whether or not access was granted.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct LinkAccess {
+    pub id: Option<Thing>,
+    /// The share link used.
+    pub link: Thing,
+    /// The requested resource, e.g. `model`, `file` or `thumbnail`.
+    pub resource: String,
+    /// The HTTP status the request was answered with.
+    pub status: u16,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub accessed_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn link(export: Option<Export>) -> ShareLink {
+        ShareLink {
+            id: None,
+            model: Thing::from(("ifc_models", "tower")),
+            export,
+            password_hash: None,
+            created_by: String::from("octocat"),
+            created_at: Utc::now(),
+            expires_at: Utc::now() + Duration::hours(1),
+            revoked_at: None,
+        }
+    }
+
+    #[test]
+    fn test_link_scope_and_lifetime() {
+        let full = link(None);
+        assert!(full.is_active());
+        assert!(full.allows(Export::Thumbnail));
+        assert!(full.allows(Export::Revision { number: 3 }));
+
+        let revision = link(Some(Export::Revision { number: 3 }));
+        assert!(revision.allows(Export::Revision { number: 3 }));
+        assert!(!revision.allows(Export::Revision { number: 4 }));
+        assert!(!revision.allows(Export::File));
+
+        let expired = ShareLink {
+            expires_at: Utc::now() - Duration::seconds(1),
+            ..link(None)
+        };
+        assert!(!expired.is_active());
+        let revoked = ShareLink {
+            revoked_at: Some(Utc::now()),
+            ..link(None)
+        };
+        assert!(!revoked.is_active());
+    }
+}
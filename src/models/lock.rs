@@ -0,0 +1,34 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use chrono::{DateTime, Utc};
+use rocket::serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+/// An exclusive, time-limited check-out of a model by one user.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ModelLock {
+    pub id: Option<Thing>,
+    /// The locked model.
+    pub model: Thing,
+    /// Login of the user holding the lock.
+    pub holder: String,
+    pub acquired_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl ModelLock {
+    /// Returns `true` once the lock has passed its expiry time.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+
+    /// Returns `true` if the lock is in force and held by someone other than `login`.
+    ///
+    /// # Arguments
+    /// * `login` - Login of the user attempting a change.
+    pub fn blocks(&self, login: &str) -> bool {
+        !self.is_expired() && self.holder != login
+    }
+}
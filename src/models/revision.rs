@@ -0,0 +1,41 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::models::{blob::BlobRef, branch::MAIN_BRANCH};
+use chrono::{DateTime, Utc};
+use rocket::serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use surrealdb::sql::Thing;
+
+/// An immutable snapshot of an IFC model, recorded on every change.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Revision {
+    pub id: Option<Thing>,
+    /// The model this revision belongs to.
+    pub model: Thing,
+    /// Sequence number of the revision within its model, starting at 1.
+    pub number: u64,
+    /// The branch the revision was committed to.
+    #[serde(default = "main_branch")]
+    pub branch: String,
+    /// Number of the revision this one was derived from.
+    pub parent: Option<u64>,
+    /// Number of the branch revision merged into this one, for merge revisions.
+    #[serde(default)]
+    pub merged: Option<u64>,
+    /// Login of the user who made the change.
+    pub author: String,
+    pub message: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub name: String,
+    pub version: String,
+    pub description: Option<String>,
+    pub metadata: HashMap<String, String>,
+    /// The model file at this revision; its `sha256` is the content hash.
+    pub file: Option<BlobRef>,
+}
+
+fn main_branch() -> String {
+    String::from(MAIN_BRANCH)
+}
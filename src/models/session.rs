@@ -0,0 +1,71 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use chrono::{DateTime, Duration, Utc};
+use rocket::serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+/// A signed-in browser or client, keyed by the random id stored in its session cookie.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Session {
+    pub id: Option<Thing>,
+    /// The signed-in user.
+    pub user: Thing,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// When the session was last used, updated at most once per `TOUCH_INTERVAL`.
+    pub last_seen_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Session {
+    /// Minimum time between two updates of `last_seen_at`, sparing a write per request.
+    pub const TOUCH_INTERVAL: Duration = Duration::minutes(1);
+
+    /// Returns `true` until the session expires.
+    pub fn is_active(&self) -> bool {
+        Utc::now() < self.expires_at
+    }
+
+    /// Returns `true` if `last_seen_at` is due to be updated.
+    ///
+    /// # Arguments
+    /// * `now` - The time of the current request.
+    pub fn is_stale(&self, now: DateTime<Utc>) -> bool {
+        now - self.last_seen_at >= Self::TOUCH_INTERVAL
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(age: Duration, lifetime: Duration) -> Session {
+        let created_at = Utc::now() - age;
+        Session {
+            id: None,
+            user: Thing::from(("users", "octocat")),
+            ip: None,
+            user_agent: None,
+            created_at,
+            last_seen_at: created_at,
+            expires_at: created_at + lifetime,
+        }
+    }
+
+    #[test]
+    fn test_session_lifetime_and_touch() {
+        let fresh = session(Duration::seconds(5), Duration::hours(24));
+        assert!(fresh.is_active());
+        assert!(!fresh.is_stale(Utc::now()));
+
+        let idle = session(Duration::minutes(10), Duration::hours(24));
+        assert!(idle.is_active());
+        assert!(idle.is_stale(Utc::now()));
+
+        let expired = session(Duration::hours(25), Duration::hours(24));
+        assert!(!expired.is_active());
+    }
+}
@@ -0,0 +1,49 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use chrono::{DateTime, Utc};
+use rocket::serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+/// Level of access a user has to a model; each role includes the ones below it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum Role {
+    /// May read the model, its file, revisions and branches.
+    Viewer,
+    /// May also change the model, commit to branches and take locks.
+    Editor,
+    /// May also delete the model and manage who it is shared with.
+    Owner,
+}
+
+/// Access to a model granted to a user other than its owner.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Share {
+    pub id: Option<Thing>,
+    /// The shared model.
+    pub model: Thing,
+    /// Login of the user the model is shared with.
+    pub login: String,
+    pub role: Role,
+    /// Login of the user who granted the access.
+    pub granted_by: String,
+    pub granted_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roles_are_ordered_by_privilege() {
+        assert!(Role::Viewer < Role::Editor);
+        assert!(Role::Editor < Role::Owner);
+        assert_eq!(
+            serde_json::from_str::<Role>("\"editor\"").unwrap(),
+            Role::Editor
+        );
+        assert_eq!(serde_json::to_string(&Role::Owner).unwrap(), "\"owner\"");
+    }
+}
@@ -0,0 +1,89 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::models::share::Role;
+use chrono::{DateTime, Utc};
+use rocket::serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+/// Role of a member within an organization or project; each role includes the ones below it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum TeamRole {
+    /// May read the models of the project.
+    Viewer,
+    /// May also upload and change models.
+    Author,
+    /// May also delete models, share them and create projects.
+    Coordinator,
+    /// May also invite and remove members.
+    Admin,
+}
+
+impl TeamRole {
+    /// Returns the access to a model that this role grants on the models of a project.
+    pub fn model_role(self) -> Role {
+        match self {
+            TeamRole::Viewer => Role::Viewer,
+            TeamRole::Author => Role::Editor,
+            TeamRole::Coordinator | TeamRole::Admin => Role::Owner,
+        }
+    }
+}
+
+/// A company or team owning projects.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Organization {
+    pub id: Option<Thing>,
+    pub name: String,
+    /// Login of the user who created the organization.
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A project of an organization, grouping the models of one building.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Project {
+    pub id: Option<Thing>,
+    /// The organization the project belongs to.
+    pub organization: Thing,
+    pub name: String,
+    pub description: Option<String>,
+    /// Login of the user who created the project.
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Membership of a user in an organization or project.
+///
+/// Members of an organization hold their role on each of its projects as well.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Membership {
+    pub id: Option<Thing>,
+    /// The organization or project.
+    pub scope: Thing,
+    /// Login of the member.
+    pub login: String,
+    pub role: TeamRole,
+    /// Login of the user who invited the member.
+    pub invited_by: String,
+    pub joined_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_team_roles_map_to_model_roles() {
+        assert!(TeamRole::Viewer < TeamRole::Author);
+        assert!(TeamRole::Coordinator < TeamRole::Admin);
+        assert_eq!(TeamRole::Viewer.model_role(), Role::Viewer);
+        assert_eq!(TeamRole::Author.model_role(), Role::Editor);
+        assert_eq!(TeamRole::Coordinator.model_role(), Role::Owner);
+        assert_eq!(TeamRole::Admin.model_role(), Role::Owner);
+    }
+}
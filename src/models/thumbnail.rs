@@ -0,0 +1,16 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use chrono::{DateTime, Utc};
+use rocket::serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+/// A server-rendered PNG preview of a stored IFC model.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Thumbnail {
+    pub id: Option<Thing>,
+    pub model: Thing,
+    pub data: String,
+    pub rendered_at: DateTime<Utc>,
+}
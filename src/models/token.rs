@@ -0,0 +1,128 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use chrono::{DateTime, Duration, Utc};
+use rocket::serde::{Deserialize, Serialize};
+use std::fmt;
+use surrealdb::sql::Thing;
+
+/// A permission a personal access token may be granted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub enum Scope {
+    /// Read models and their exports.
+    #[serde(rename = "ifc:read")]
+    IfcRead,
+    /// Upload, change and delete models.
+    #[serde(rename = "ifc:write")]
+    IfcWrite,
+    /// Use the admin rights of the token's user.
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Scope::IfcRead => "ifc:read",
+            Scope::IfcWrite => "ifc:write",
+            Scope::Admin => "admin",
+        })
+    }
+}
+
+/// A personal access token, used by scripts and CI in an `Authorization: Bearer` header.
+///
+/// The token is handed out once as `xbim_<key>_<secret>`; only the record key and a
+/// keyed hash of the secret are stored.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AccessToken {
+    pub id: Option<Thing>,
+    /// The user the token acts as.
+    pub user: Thing,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    /// HMAC-SHA256 of the secret under the configured `secret_key`.
+    pub secret_hash: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// When the token was last used, updated at most once per `TOUCH_INTERVAL`.
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl AccessToken {
+    /// Prefix of every personal access token, making leaked tokens easy to spot.
+    pub const PREFIX: &str = "xbim_";
+    /// Minimum time between two updates of `last_used_at`, sparing a write per request.
+    pub const TOUCH_INTERVAL: Duration = Duration::minutes(1);
+
+    /// Splits a bearer token into its record key and secret.
+    ///
+    /// # Arguments
+    /// * `token` - The token from the `Authorization` header.
+    ///
+    /// # Returns
+    /// The key and secret, or `None` if the token is not a personal access token.
+    pub fn parse(token: &str) -> Option<(&str, &str)> {
+        token
+            .strip_prefix(Self::PREFIX)?
+            .split_once('_')
+            .filter(|(key, secret)| !key.is_empty() && !secret.is_empty())
+    }
+
+    /// Returns `true` until the token expires.
+    pub fn is_active(&self) -> bool {
+        Utc::now() < self.expires_at
+    }
+
+    /// Returns `true` if the token grants a scope.
+    ///
+    /// # Arguments
+    /// * `scope` - The scope to check.
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    /// Returns `true` if `last_used_at` is due to be updated.
+    ///
+    /// # Arguments
+    /// * `now` - The time of the current request.
+    pub fn is_stale(&self, now: DateTime<Utc>) -> bool {
+        self.last_used_at
+            .is_none_or(|used| now - used >= Self::TOUCH_INTERVAL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_parsing_and_scopes() {
+        assert_eq!(
+            AccessToken::parse("xbim_0a1b_c2d3e4"),
+            Some(("0a1b", "c2d3e4"))
+        );
+        assert_eq!(AccessToken::parse("xbim_0a1b"), None);
+        assert_eq!(AccessToken::parse("xbim__c2d3"), None);
+        assert_eq!(AccessToken::parse("ghp_0a1b_c2d3"), None);
+
+        let scopes: Vec<Scope> = serde_json::from_str(r#"["ifc:read", "admin"]"#).unwrap();
+        let token = AccessToken {
+            id: None,
+            user: Thing::from(("users", "octocat")),
+            name: String::from("CI"),
+            scopes,
+            secret_hash: String::new(),
+            created_at: Utc::now(),
+            expires_at: Utc::now() + Duration::days(1),
+            last_used_at: None,
+        };
+        assert!(token.is_active());
+        assert!(token.allows(Scope::IfcRead));
+        assert!(!token.allows(Scope::IfcWrite));
+        assert!(token.is_stale(Utc::now()));
+        assert_eq!(Scope::IfcWrite.to_string(), "ifc:write");
+    }
+}
@@ -0,0 +1,39 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use chrono::{DateTime, Utc};
+use rocket::serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+/// A resumable upload that is still receiving chunks.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct UploadSession {
+    pub id: Option<Thing>,
+    /// Login of the user who created the upload; only they may continue it.
+    #[serde(default)]
+    pub owner: String,
+    pub length: u64,
+    pub offset: u64,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    pub filename: Option<String>,
+    /// The ID of the project to create the model in.
+    #[serde(default)]
+    pub project: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl UploadSession {
+    /// Returns `true` once the session has passed its expiry time.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+
+    /// Returns `true` once every byte of the upload has been received.
+    pub fn is_complete(&self) -> bool {
+        self.offset == self.length
+    }
+}
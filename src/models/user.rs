@@ -1,30 +1,147 @@
 #![warn(clippy::all)]
 #![forbid(unsafe_code)]
 
-use crate::routes::github::GitHubUser;
 use rocket::serde::{Deserialize, Serialize};
 use surrealdb::sql::Thing;
 
+/// An account at a login provider that a user signs in with.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Identity {
+    /// The provider: `github`, `gitlab`, `microsoft`, `google` or the name of an
+    /// OpenID Connect provider in the configuration.
+    pub provider: String,
+    /// The id of the account at the provider.
+    pub subject: String,
+}
+
+/// A user account as described by a login provider, before it is saved as a `User`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Account {
+    /// The id of the account at the provider.
+    pub subject: String,
+    pub login: String,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct User {
     pub id: Option<Thing>,
-    pub github_id: u64,
+    /// Accounts at login providers the user signs in with.
+    #[serde(default)]
+    pub identities: Vec<Identity>,
     pub login: String,
     pub name: Option<String>,
     pub email: Option<String>,
     pub avatar_url: Option<String>,
+    /// Argon2id hash of the password the user signs in with, if they have one.
+    #[serde(default)]
+    pub password_hash: Option<String>,
 }
 
-impl From<GitHubUser> for User {
-    fn from(github_user: GitHubUser) -> Self {
+impl User {
+    /// Longest login a user may choose, as on GitHub.
+    pub const MAX_LOGIN_LENGTH: usize = 39;
+
+    /// Returns `true` if a login may be chosen for a local account: letters, digits,
+    /// `-`, `_` and `.`, starting with a letter or digit.
+    ///
+    /// # Arguments
+    /// * `login` - The login to check.
+    pub fn is_valid_login(login: &str) -> bool {
+        login.len() <= Self::MAX_LOGIN_LENGTH
+            && login
+                .chars()
+                .next()
+                .is_some_and(|first| first.is_ascii_alphanumeric())
+            && login
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    }
+
+    /// Creates a local user who signs in with a password.
+    ///
+    /// # Arguments
+    /// * `login` - The login of the user.
+    /// * `name` - The display name.
+    /// * `email` - The email address.
+    /// * `password_hash` - The Argon2id hash of the password.
+    pub fn local(
+        login: String,
+        name: Option<String>,
+        email: Option<String>,
+        password_hash: String,
+    ) -> Self {
         Self {
             id: None,
-            github_id: github_user.id,
-            login: github_user.login,
-            name: github_user.name,
-            email: github_user.email,
-            avatar_url: github_user.avatar_url,
+            identities: Vec::new(),
+            login,
+            name,
+            email,
+            avatar_url: None,
+            password_hash: Some(password_hash),
         }
     }
+
+    /// Returns the number of ways the user can sign in: linked accounts and a password.
+    pub fn sign_in_methods(&self) -> usize {
+        self.identities.len() + usize::from(self.password_hash.is_some())
+    }
+
+    /// Creates a user signing in with a provider account for the first time.
+    ///
+    /// # Arguments
+    /// * `provider` - The login provider.
+    /// * `account` - The account at the provider.
+    pub fn new(provider: &str, account: Account) -> Self {
+        Self {
+            id: None,
+            identities: vec![Identity {
+                provider: provider.to_string(),
+                subject: account.subject,
+            }],
+            login: account.login,
+            name: account.name,
+            email: account.email,
+            avatar_url: account.avatar_url,
+            password_hash: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Utils;
+
+    #[test]
+    fn test_local_user_login_and_sign_in_methods() {
+        assert!(User::is_valid_login("octocat"));
+        assert!(User::is_valid_login("jane.doe-2_b"));
+        assert!(!User::is_valid_login(""));
+        assert!(!User::is_valid_login("-octocat"));
+        assert!(!User::is_valid_login("octo cat"));
+        assert!(!User::is_valid_login("octocat/../admin"));
+        assert!(!User::is_valid_login(
+            &"a".repeat(User::MAX_LOGIN_LENGTH + 1)
+        ));
+
+        let mut user = User::local(
+            String::from("octocat"),
+            None,
+            None,
+            Utils::hash_password("correct horse"),
+        );
+        assert_eq!(user.sign_in_methods(), 1);
+        user.identities.push(Identity {
+            provider: String::from("github"),
+            subject: String::from("583231"),
+        });
+        assert_eq!(user.sign_in_methods(), 2);
+        user.password_hash = None;
+        assert_eq!(user.sign_in_methods(), 1);
+    }
 }
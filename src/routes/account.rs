@@ -0,0 +1,341 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::config::Config;
+use crate::database::Database;
+use crate::guards::{
+    auth::{AuthGuard, SESSION_COOKIE, start_session},
+    client::ClientInfo,
+    ratelimit::RateLimitGuard,
+    scope::{IfcRead, IfcWrite, ScopeGuard},
+};
+use crate::models::user::{Account, Identity, User};
+use rocket::{
+    State, delete, get,
+    http::{Cookie, CookieJar, SameSite, Status},
+    response::{Flash, Redirect},
+    serde::json::Json,
+};
+use rocket_governor::RocketGovernor;
+use serde_json::json;
+
+/// Name of the private cookie marking a sign-in that links an account to the signed-in
+/// user; it holds the id of their session.
+const LINK_COOKIE: &str = "link_session";
+/// How long a user may take to link an account at the provider, in minutes.
+const LINK_MINUTES: i64 = 10;
+
+/// Returns `true` if a user with the login exists.
+///
+/// Logins identify users in ownership, shares and memberships, so a new account may not
+/// take over the login of another one. Logins are compared ignoring case, as in
+/// `Config::is_admin`.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `login` - The login to check.
+pub async fn login_taken(database: &Database, login: &str) -> bool {
+    database
+        .query::<User>(
+            "SELECT * FROM users WHERE string::lowercase(login) = string::lowercase($login) LIMIT 1",
+            ("login", login.to_string()),
+        )
+        .await
+        .map_or(true, |users| !users.is_empty())
+}
+
+/// Returns the user who signs in with a provider account, if any.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `identity` - The provider account.
+pub async fn find_user(
+    database: &Database,
+    identity: &Identity,
+) -> Result<Option<User>, surrealdb::Error> {
+    // Users saved before identities were introduced only hold their GitHub id.
+    let github_id = identity.subject.parse::<u64>().ok();
+    let legacy = match (identity.provider.as_str(), github_id) {
+        ("github", Some(_)) => " OR github_id = $github_id",
+        _ => "",
+    };
+    database
+        .query::<User>(
+            &format!("SELECT * FROM users WHERE identities CONTAINS $identity{legacy} LIMIT 1"),
+            json!({
+                "identity": identity,
+                "github_id": github_id,
+            }),
+        )
+        .await
+        .map(|users| users.into_iter().next())
+}
+
+/// Saves the user signing in with a provider account: updates the profile of a
+/// returning user, or creates a user for a new account.
+///
+/// Logins listed in `admins` grant admin rights, so only GitHub, whose logins the list
+/// names, may create an account with one; other providers let users choose their login.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `config` - The application configuration.
+/// * `provider` - The login provider.
+/// * `account` - The account described by the provider.
+///
+/// # Returns
+/// The saved user, or a message for the user.
+pub async fn sign_in(
+    database: &Database,
+    config: &Config,
+    provider: &str,
+    account: Account,
+) -> Result<User, &'static str> {
+    let identity = Identity {
+        provider: provider.to_string(),
+        subject: account.subject.clone(),
+    };
+    let saved = match find_user(database, &identity).await.map_err(|e| {
+        println!("Error loading user of {identity:?}: {e:?}");
+        "Failed to load user data"
+    })? {
+        Some(user) => {
+            let id = user
+                .id
+                .as_ref()
+                .ok_or("Failed to load user data")?
+                .id
+                .to_raw();
+            let mut identities = user.identities;
+            if !identities.contains(&identity) {
+                identities.push(identity);
+            }
+            let user = User {
+                id: None,
+                identities,
+                login: user.login,
+                name: account.name,
+                email: account.email,
+                avatar_url: account.avatar_url,
+                password_hash: user.password_hash,
+            };
+            database.update("users", &id, user).await
+        }
+        None => {
+            if provider != "github" && config.is_admin(&account.login) {
+                println!(
+                    "Refused {provider} account with reserved login {}",
+                    account.login
+                );
+                return Err("This login is reserved");
+            }
+            if login_taken(database, &account.login).await {
+                return Err(
+                    "An account with this login already exists; sign in and link this provider instead",
+                );
+            }
+            database.create("users", User::new(provider, account)).await
+        }
+    };
+    saved.map_err(|_| "Failed to save user data")
+}
+
+/// Links a provider account to a user.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `user` - The user to link the account to.
+/// * `provider` - The login provider.
+/// * `subject` - The id of the account at the provider.
+///
+/// # Returns
+/// The saved user, or a message for the user if the account belongs to someone else.
+async fn link(
+    database: &Database,
+    user: User,
+    provider: &str,
+    subject: String,
+) -> Result<User, &'static str> {
+    let identity = Identity {
+        provider: provider.to_string(),
+        subject,
+    };
+    let owner = find_user(database, &identity).await.map_err(|e| {
+        println!("Error loading user of {identity:?}: {e:?}");
+        "Failed to load user data"
+    })?;
+    match owner {
+        Some(owner) if owner.id == user.id => Ok(user),
+        Some(_) => Err("This account is already linked to another user"),
+        None => {
+            let id = user
+                .id
+                .as_ref()
+                .ok_or("Failed to load user data")?
+                .id
+                .to_raw();
+            let mut user = user;
+            println!("Linking {identity:?} to {}", user.login);
+            user.identities.push(identity);
+            database
+                .update("users", &id, user)
+                .await
+                .map_err(|_| "Failed to save user data")
+        }
+    }
+}
+
+/// Finishes a sign-in with a login provider. If the sign-in was started from
+/// `GET /auth/link/<provider>`, the account is linked to the signed-in user; otherwise the
+/// user is signed in and a session is started.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `config` - The application configuration.
+/// * `cookies` - The cookie jar of the response.
+/// * `client` - The client signing in.
+/// * `provider` - The login provider.
+/// * `account` - The account described by the provider.
+///
+/// # Returns
+/// A redirect to the application, with a flash message if the sign-in failed.
+pub async fn finish_sign_in(
+    database: &Database,
+    config: &Config,
+    cookies: &CookieJar<'_>,
+    client: ClientInfo,
+    provider: &str,
+    account: Account,
+) -> Result<Redirect, Flash<Redirect>> {
+    let fail = |message: &str| Flash::error(Redirect::to("/"), message);
+    if let Some(link_session) = cookies.get_private(LINK_COOKIE) {
+        cookies.remove_private(LINK_COOKIE);
+        let session = cookies
+            .get_private(SESSION_COOKIE)
+            .filter(|session| session.value() == link_session.value())
+            .ok_or_else(|| fail("Sign in again to link this account"))?;
+        let user = AuthGuard::authenticate(database, session.value())
+            .await
+            .ok_or_else(|| fail("Sign in again to link this account"))?;
+        link(database, user, provider, account.subject)
+            .await
+            .map_err(fail)?;
+        return Ok(Redirect::to("/"));
+    }
+
+    // Store the session server-side and only its id in the cookie
+    let user = sign_in(database, config, provider, account)
+        .await
+        .map_err(fail)?;
+    let user_id = user.id.ok_or_else(|| fail("Failed to save user data"))?;
+    start_session(database, config, cookies, user_id, client)
+        .await
+        .map_err(|_| fail("Failed to start session"))?;
+
+    Ok(Redirect::to("/"))
+}
+
+/// Link an account at another login provider to the signed-in user: sends the user to
+/// sign in with the provider, after which the account is linked instead of signed in.
+///
+/// # Arguments
+/// * `config` - The application configuration.
+/// * `cookies` - The cookie jar the link is kept in.
+/// * `authguard` - Authentication Guard.
+/// * `provider` - `github`, `gitlab`, `microsoft`, `google` or the name of an OpenID
+///   Connect provider.
+///
+/// # Returns
+/// A redirect to the login of the provider, 404 Not Found for unknown providers, or 403
+/// Forbidden when called with an access token.
+#[get("/auth/link/<provider>")]
+pub fn link_start(
+    config: &State<Config>,
+    cookies: &CookieJar<'_>,
+    authguard: AuthGuard,
+    provider: &str,
+) -> Result<Redirect, Status> {
+    let session = authguard.session().ok_or(Status::Forbidden)?;
+    let login = if Config::BUILT_IN_PROVIDERS.contains(&provider) {
+        format!("/auth/{provider}/login")
+    } else if config.oidc_provider(provider).is_some() {
+        format!("/auth/oidc/{provider}/login")
+    } else {
+        return Err(Status::NotFound);
+    };
+    println!("{} is linking a {provider} account", authguard.user.login);
+    cookies.add_private(
+        Cookie::build((LINK_COOKIE, session.to_string()))
+            .same_site(SameSite::Lax)
+            .http_only(true)
+            .max_age(rocket::time::Duration::minutes(LINK_MINUTES))
+            .build(),
+    );
+    Ok(Redirect::to(login))
+}
+
+/// List the accounts at login providers linked to the signed-in user.
+///
+/// # Arguments
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+///
+/// # Returns
+/// The linked identities, or 403 Forbidden when called with an access token.
+#[get("/auth/identities")]
+pub fn identity_list(
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+) -> Result<Json<Vec<Identity>>, Status> {
+    authguard.session().ok_or(Status::Forbidden)?;
+    Ok(Json(authguard.user.identities.clone()))
+}
+
+/// Unlink an account at a login provider from the signed-in user. The last way to sign
+/// in, an account or a password, cannot be unlinked.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `provider` - The login provider.
+/// * `subject` - The id of the account at the provider.
+///
+/// # Returns
+/// 204 No Content on success, 404 Not Found if the account is not linked, 409 Conflict
+/// if it is the last way to sign in, or 403 Forbidden when called with an access token.
+#[delete("/auth/identities/<provider>/<subject>")]
+pub async fn identity_unlink(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    provider: &str,
+    subject: &str,
+) -> Status {
+    if authguard.session().is_none() {
+        return Status::Forbidden;
+    }
+    let mut user = authguard.user.clone();
+    let Some(position) = user
+        .identities
+        .iter()
+        .position(|identity| identity.provider == provider && identity.subject == subject)
+    else {
+        return Status::NotFound;
+    };
+    if user.sign_in_methods() == 1 {
+        return Status::Conflict;
+    }
+    user.identities.remove(position);
+    let Some(id) = user.id.as_ref().map(|id| id.id.to_raw()) else {
+        return Status::InternalServerError;
+    };
+    println!("Unlinking {provider} account {subject} from {}", user.login);
+    match database.update::<User>("users", &id, user).await {
+        Ok(_) => Status::NoContent,
+        Err(e) => {
+            println!("Error unlinking {provider} account {subject}: {e:?}");
+            Status::InternalServerError
+        }
+    }
+}
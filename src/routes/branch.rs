@@ -0,0 +1,466 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::database::Database;
+use crate::guards::{
+    precondition::Preconditions,
+    ratelimit::RateLimitGuard,
+    scope::{IfcRead, IfcWrite, ScopeGuard},
+};
+use crate::ifc::merge;
+use crate::models::share::Role;
+use crate::models::{branch::Branch, revision::Revision};
+use crate::routes::data::{
+    IFCRequest, IFCResponse, ModelFile, StoredIFC, file_content_type, index_metadata, open_blob,
+    read_step, replace_model, store_content,
+};
+use crate::routes::lock::check_lock;
+use crate::routes::revision::{find_revision, next_revision_number, save_revision, snapshot};
+use crate::routes::share::authorize;
+use crate::routes::thumbnail::spawn_thumbnail;
+use crate::storage::blob::BlobReader;
+use crate::storage::blob::BlobStore;
+use chrono::Utc;
+use rocket::{
+    Responder, State, delete, get,
+    http::Status,
+    post, put,
+    serde::json::Json,
+    serde::{Deserialize, Serialize},
+    tokio::task::spawn_blocking,
+};
+use rocket_governor::RocketGovernor;
+
+/// A request to create a branch.
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct NewBranch {
+    pub name: String,
+    /// The revision to branch from; defaults to the head of the main branch.
+    pub from: Option<u64>,
+}
+
+/// The result of merging a branch into the main branch.
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct MergeResult {
+    pub model: IFCResponse,
+    #[serde(flatten)]
+    pub changes: merge::Merged,
+}
+
+/// A failed merge: either the conflicting elements or a plain error status.
+#[derive(Debug, Responder)]
+pub enum MergeError {
+    #[response(status = 409)]
+    Conflict(Json<Vec<merge::Conflict>>),
+    Failed(Status),
+}
+
+impl From<Status> for MergeError {
+    fn from(status: Status) -> Self {
+        MergeError::Failed(status)
+    }
+}
+
+/// Returns the record key of a branch.
+fn branch_key(model: &str, name: &str) -> String {
+    format!("{model}/{name}")
+}
+
+/// Loads a branch of a model.
+async fn load_branch(database: &Database, model: &str, name: &str) -> Result<Branch, Status> {
+    database
+        .read::<Branch>("ifc_branches", &branch_key(model, name))
+        .await
+        .map_err(|_| Status::NotFound)
+}
+
+/// Loads an IFC model.
+async fn load_model(database: &Database, id: &str) -> Result<StoredIFC, Status> {
+    database
+        .read::<StoredIFC>("ifc_models", id)
+        .await
+        .map_err(|e| {
+            println!("Error retrieving IFC model {id}: {e:?}");
+            Status::NotFound
+        })
+}
+
+/// Opens the file of a revision for reading.
+async fn revision_content(blobs: &BlobStore, revision: &Revision) -> Result<BlobReader, Status> {
+    open_blob(blobs, revision.file.as_ref().ok_or(Status::NotFound)?).await
+}
+
+/// Deletes every branch of a model.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `model` - The ID of the IFC model.
+pub async fn delete_branches(database: &Database, model: &str) {
+    if let Err(e) = database
+        .query::<Branch>(
+            "DELETE ifc_branches WHERE model = type::thing('ifc_models', $model)",
+            ("model", model.to_string()),
+        )
+        .await
+    {
+        println!("Error deleting branches of IFC model {model}: {e:?}");
+    }
+}
+
+/// List the branches of an IFC model.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+///
+/// # Returns
+/// The branches of the model, excluding the implicit main branch.
+#[get("/ifc/<id>/branches")]
+pub async fn branch_list(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+) -> Result<Json<Vec<Branch>>, Status> {
+    authorize(database, &id, &authguard, Role::Viewer).await?;
+    println!("Listing branches of IFC model {id}");
+    database
+        .query(
+            "SELECT * FROM ifc_branches \
+             WHERE model = type::thing('ifc_models', $model) ORDER BY name",
+            ("model", id.clone()),
+        )
+        .await
+        .map(Json)
+        .map_err(|e| {
+            println!("Error listing branches of IFC model {id}: {e:?}");
+            Status::InternalServerError
+        })
+}
+
+/// Create a branch of an IFC model.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+/// * `branch` - The branch name and the revision to start from.
+///
+/// # Returns
+/// The created branch, or 409 Conflict if the name is taken.
+#[post("/ifc/<id>/branches", data = "<branch>")]
+pub async fn branch_create(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+    branch: Json<NewBranch>,
+) -> Result<Json<Branch>, Status> {
+    authorize(database, &id, &authguard, Role::Editor).await?;
+    let branch = branch.into_inner();
+    println!("Creating branch {} of IFC model {id}", branch.name);
+    if !Branch::is_valid_name(&branch.name) {
+        return Err(Status::UnprocessableEntity);
+    }
+    if load_branch(database, &id, &branch.name).await.is_ok() {
+        return Err(Status::Conflict);
+    }
+    let model = load_model(database, &id).await?;
+    let from = branch.from.or(model.revision).ok_or(Status::NotFound)?;
+    let start = find_revision(database, &id, from).await?;
+
+    let now = Utc::now();
+    let new_branch = Branch {
+        id: None,
+        model: start.model,
+        name: branch.name.clone(),
+        base: from,
+        head: from,
+        created_by: authguard.user.login.clone(),
+        created_at: now,
+        updated_at: now,
+    };
+    database
+        .upsert("ifc_branches", &branch_key(&id, &branch.name), new_branch)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            println!("Error saving branch {}: {e:?}", branch.name);
+            Status::InternalServerError
+        })
+}
+
+/// Get a branch of an IFC model.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+/// * `name` - The branch name.
+///
+/// # Returns
+/// The branch.
+#[get("/ifc/<id>/branches/<name>")]
+pub async fn branch_get(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+    name: String,
+) -> Result<Json<Branch>, Status> {
+    authorize(database, &id, &authguard, Role::Viewer).await?;
+    load_branch(database, &id, &name).await.map(Json)
+}
+
+/// Download the model file at the head of a branch.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `blobs` - The blob store.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+/// * `name` - The branch name.
+///
+/// # Returns
+/// The STEP or ifcXML file content.
+#[get("/ifc/<id>/branches/<name>/file")]
+pub async fn branch_file(
+    database: &State<Database>,
+    blobs: &State<BlobStore>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+    name: String,
+) -> Result<ModelFile, Status> {
+    authorize(database, &id, &authguard, Role::Viewer).await?;
+    let branch = load_branch(database, &id, &name).await?;
+    let head = find_revision(database, &id, branch.head).await?;
+    Ok(ModelFile {
+        content_type: file_content_type(&head.metadata),
+        reader: revision_content(blobs, &head).await?,
+    })
+}
+
+/// Commit a new state of the model to a branch.
+///
+/// The branch head's file is kept when the commit carries no `file_content`.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `blobs` - The blob store.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+/// * `name` - The branch name.
+/// * `message` - Optional description of the change.
+/// * `model` - The model state to commit.
+///
+/// # Returns
+/// The new revision on the branch, or 409 Conflict if another commit moved the branch head first.
+#[put("/ifc/<id>/branches/<name>?<message>", data = "<model>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn branch_commit(
+    database: &State<Database>,
+    blobs: &State<BlobStore>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+    name: String,
+    message: Option<String>,
+    model: Json<IFCRequest>,
+) -> Result<Json<Revision>, Status> {
+    authorize(database, &id, &authguard, Role::Editor).await?;
+    println!("Committing to branch {name} of IFC model {id}");
+    let mut branch = load_branch(database, &id, &name).await?;
+    let head = find_revision(database, &id, branch.head).await?;
+    let mut model = StoredIFC::new(model.into_inner());
+    index_metadata(&mut model).await?;
+    if !store_content(blobs, &mut model).await? {
+        model.file = head.file;
+    }
+    model.id = Some(head.model);
+    model.revision = Some(next_revision_number(database, &id).await?);
+
+    let revision = save_revision(
+        database,
+        Revision {
+            branch: name.clone(),
+            parent: Some(branch.head),
+            ..snapshot(&model, &authguard.user.login, message)?
+        },
+    )
+    .await?;
+    let expected = branch.head;
+    branch.head = revision.number;
+    branch.updated_at = Utc::now();
+    let moved = database
+        .update_where(
+            "ifc_branches",
+            &branch_key(&id, &name),
+            branch,
+            "head = $head",
+            None,
+            ("head", expected),
+        )
+        .await
+        .map_err(|e| {
+            println!("Error updating branch {name}: {e:?}");
+            Status::InternalServerError
+        })?;
+    if moved.is_none() {
+        // Another commit moved the head first; drop the revision it would have orphaned.
+        println!("Branch {name} of IFC model {id} changed while committing");
+        if let Some(orphan) = &revision.id {
+            let _ = database
+                .delete::<Revision>("ifc_revisions", &orphan.id.to_raw())
+                .await;
+        }
+        return Err(Status::Conflict);
+    }
+    Ok(Json(revision))
+}
+
+/// Delete a branch. Its revisions are kept in the history.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+/// * `name` - The branch name.
+///
+/// # Returns
+/// 204 No Content on success.
+#[delete("/ifc/<id>/branches/<name>")]
+pub async fn branch_delete(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+    name: String,
+) -> Status {
+    if let Err(status) = authorize(database, &id, &authguard, Role::Editor).await {
+        return status;
+    }
+    match database
+        .delete::<Branch>("ifc_branches", &branch_key(&id, &name))
+        .await
+    {
+        Ok(true) => Status::NoContent,
+        Ok(false) => Status::NotFound,
+        Err(e) => {
+            println!("Error deleting branch {name}: {e:?}");
+            Status::InternalServerError
+        }
+    }
+}
+
+/// Merge a branch into the main branch, element by element using GlobalIds.
+///
+/// Elements changed only on the branch are taken over; elements changed on both sides
+/// are reported as conflicts and nothing is merged.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `blobs` - The blob store.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `preconditions` - The `If-Match` header.
+/// * `id` - The ID of the IFC model.
+/// * `name` - The branch name.
+/// * `message` - Optional description of the merge.
+///
+/// # Returns
+/// The merged model with the applied changes, 409 Conflict with the conflicting elements,
+/// or 412 Precondition Failed on a stale `If-Match` or if the model changed during the merge.
+#[post("/ifc/<id>/branches/<name>/merge?<message>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn branch_merge(
+    database: &State<Database>,
+    blobs: &State<BlobStore>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    preconditions: Preconditions,
+    id: String,
+    name: String,
+    message: Option<String>,
+) -> Result<Json<MergeResult>, MergeError> {
+    authorize(database, &id, &authguard, Role::Editor).await?;
+    println!("Merging branch {name} into IFC model {id}");
+    check_lock(database, &id, &authguard.user.login).await?;
+    let mut branch = load_branch(database, &id, &name).await?;
+    let previous = load_model(database, &id).await?;
+    preconditions.check_match(&previous.etag())?;
+    let ours_number = previous.revision.ok_or(Status::NotFound)?;
+    let base = find_revision(database, &id, branch.base).await?;
+    let ours = find_revision(database, &id, ours_number).await?;
+    let theirs = find_revision(database, &id, branch.head).await?;
+    if [&base, &ours, &theirs].iter().any(|revision| {
+        revision
+            .metadata
+            .get("ifc.format")
+            .is_some_and(|f| f != "step")
+    }) {
+        println!("Refusing to merge non-STEP revisions of IFC model {id}");
+        return Err(Status::UnprocessableEntity.into());
+    }
+    let files = (
+        read_step(revision_content(blobs, &base).await?).await?,
+        read_step(revision_content(blobs, &ours).await?).await?,
+        read_step(revision_content(blobs, &theirs).await?).await?,
+    );
+    let mut merged = spawn_blocking(move || {
+        merge::merge(&files.0, &files.1, &files.2)
+            .map_err(|conflicts| MergeError::Conflict(Json(conflicts)))
+    })
+    .await
+    .map_err(|_| Status::InternalServerError)??;
+    println!(
+        "Merged branch {name}: {} added, {} modified, {} removed",
+        merged.added.len(),
+        merged.modified.len(),
+        merged.removed.len()
+    );
+
+    let mut model = StoredIFC {
+        file_content: Some(std::mem::take(&mut merged.content)),
+        updated_at: Utc::now(),
+        ..previous.clone()
+    };
+    index_metadata(&mut model).await?;
+    let stored = store_content(blobs, &mut model).await?;
+    let model = replace_model(database, &id, &previous, model, true).await?;
+    let message = message.or_else(|| Some(format!("Merge branch '{name}'")));
+    save_revision(
+        database,
+        Revision {
+            parent: Some(ours_number),
+            merged: Some(branch.head),
+            ..snapshot(&model, &authguard.user.login, message)?
+        },
+    )
+    .await?;
+
+    branch.base = branch.head;
+    branch.updated_at = Utc::now();
+    if let Err(e) = database
+        .update("ifc_branches", &branch_key(&id, &name), branch)
+        .await
+    {
+        println!("Error updating branch {name}: {e:?}");
+    }
+    if stored && let (Some(model_id), Some(file)) = (&model.id, &model.file) {
+        spawn_thumbnail(database, blobs, model_id, file);
+    }
+    Ok(Json(MergeResult {
+        model: model.into(),
+        changes: merged,
+    }))
+}
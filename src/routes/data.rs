@@ -1,37 +1,427 @@
 #![warn(clippy::all)]
 #![forbid(unsafe_code)]
 
+use crate::database::{Database, Filter, Listing};
+use crate::guards::precondition::{Preconditions, Tagged};
 use crate::guards::ratelimit::RateLimitGuard;
-use crate::{database::Database, guards::auth::AuthGuard};
+use crate::guards::scope::{IfcRead, IfcWrite, ScopeGuard};
+use crate::ifc::{index::summarize, step::StepFile};
+use crate::models::{blob::BlobRef, share::Role, thumbnail::Thumbnail};
+use crate::routes::branch::delete_branches;
+use crate::routes::link::delete_links;
+use crate::routes::lock::{check_lock, remove_lock};
+use crate::routes::revision::{ALLOCATE_REVISION, delete_revisions, record_revision};
+use crate::routes::share::{access_filter, authorize, delete_shares};
+use crate::routes::team::upload_project;
+use crate::routes::thumbnail::spawn_thumbnail;
+use crate::storage::blob::{BlobReader, BlobStore};
+use crate::utils::Utils;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
 use chrono::{DateTime, Utc};
 use rocket::{
-    State, delete, get,
-    http::Status,
-    post, put,
+    FromForm, FromFormField, Request, Response, State, delete, get,
+    http::{ContentType, Status},
+    patch, post, put,
+    response::{self, Responder},
     serde::json::Json,
     serde::{Deserialize, Serialize},
+    tokio::task::spawn_blocking,
 };
 use rocket_governor::RocketGovernor;
+use serde_json::{Value, json};
 use std::collections::HashMap;
+use std::io::{BufReader, Cursor};
+use surrealdb::sql::Thing;
+use tokio_util::io::SyncIoBridge;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// An IFC model as stored in the `ifc_models` table.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct StoredIFC {
-    pub id: Option<String>,
+    pub id: Option<Thing>,
     pub name: String,
     pub version: String,
     pub description: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub metadata: HashMap<String, String>,
+    /// Login of the user owning the model; empty for models only admins may access.
+    #[serde(default)]
+    pub owner: String,
+    /// The project the model belongs to; its members share access to the model.
+    #[serde(default)]
+    pub project: Option<Thing>,
+    /// When the model was moved to the trash.
+    #[serde(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Login of the user who moved the model to the trash.
+    #[serde(default)]
+    pub deleted_by: Option<String>,
+    /// Number of the current revision in `ifc_revisions`.
+    pub revision: Option<u64>,
+    /// Reference to the model file in the blob store.
+    pub file: Option<BlobRef>,
+    /// The model file as text; accepted on upload and moved into the blob store.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub file_content: Option<String>,
 }
 
+/// The client-writable fields of an IFC model, accepted on create and update.
+///
+/// Identifiers, timestamps, revisions and file references are owned by the server;
+/// clients sending them have them ignored.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct IFCRequest {
+    pub name: String,
+    pub version: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// The ID of the project to create the model in; fixed once the model exists.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// The model file as text; moved into the blob store.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_content: Option<String>,
+}
+
+impl From<&StoredIFC> for IFCRequest {
+    fn from(model: &StoredIFC) -> Self {
+        IFCRequest {
+            name: model.name.clone(),
+            version: model.version.clone(),
+            description: model.description.clone(),
+            metadata: model.metadata.clone(),
+            project: None,
+            file_content: None,
+        }
+    }
+}
+
+/// An IFC model as returned to clients.
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct IFCResponse {
+    /// The record key of the model in `ifc_models`.
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub description: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub metadata: HashMap<String, String>,
+    pub owner: String,
+    /// The ID of the project the model belongs to.
+    pub project: Option<String>,
+    /// When the model was moved to the trash.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub revision: Option<u64>,
+    pub file: Option<BlobRef>,
+}
+
+impl From<StoredIFC> for IFCResponse {
+    fn from(model: StoredIFC) -> Self {
+        IFCResponse {
+            id: model.id.map(|id| id.id.to_raw()).unwrap_or_default(),
+            name: model.name,
+            version: model.version,
+            description: model.description,
+            created_at: model.created_at,
+            updated_at: model.updated_at,
+            metadata: model.metadata,
+            owner: model.owner,
+            project: model.project.map(|project| project.id.to_raw()),
+            deleted_at: model.deleted_at,
+            revision: model.revision,
+            file: model.file,
+        }
+    }
+}
+
+impl StoredIFC {
+    /// Creates a new, unsaved model from a client request, timestamped now.
+    ///
+    /// # Arguments
+    /// * `request` - The client-writable fields.
+    ///
+    /// # Returns
+    /// A model without ID, owner, project, revision or file reference.
+    pub fn new(request: IFCRequest) -> Self {
+        let now = Utc::now();
+        StoredIFC {
+            id: None,
+            name: request.name,
+            version: request.version,
+            description: request.description,
+            created_at: now,
+            updated_at: now,
+            metadata: request.metadata,
+            owner: String::new(),
+            project: None,
+            deleted_at: None,
+            deleted_by: None,
+            revision: None,
+            file: None,
+            file_content: request.file_content,
+        }
+    }
+
+    /// Replaces the client-writable fields of a stored model, bumping `updated_at`.
+    ///
+    /// # Arguments
+    /// * `request` - The new client-writable fields.
+    ///
+    /// # Returns
+    /// The model with its ID, owner, project and `created_at` kept.
+    pub fn updated(&self, request: IFCRequest) -> Self {
+        StoredIFC {
+            id: self.id.clone(),
+            created_at: self.created_at,
+            owner: self.owner.clone(),
+            project: self.project.clone(),
+            ..StoredIFC::new(request)
+        }
+    }
+
+    /// Returns the entity tag of the model, derived from its current revision.
+    ///
+    /// # Returns
+    /// A quoted strong entity tag, e.g. `"r7"`.
+    pub fn etag(&self) -> String {
+        match self.revision {
+            Some(revision) => format!("\"r{revision}\""),
+            // Models saved before revisions existed fall back to their modification time.
+            None => format!("\"t{}\"", self.updated_at.timestamp_millis()),
+        }
+    }
+}
+
+/// Default number of models per page of `GET /ifc` and `GET /trash`.
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+/// Largest number of models per page of `GET /ifc` and `GET /trash`.
+pub const MAX_PAGE_SIZE: usize = 200;
+
+/// Field by which models are listed.
+#[derive(Clone, Copy, FromFormField)]
+pub enum SortField {
+    #[field(value = "created_at")]
+    CreatedAt,
+    #[field(value = "updated_at")]
+    UpdatedAt,
+}
+
+/// Direction in which models are listed.
+#[derive(Clone, Copy, FromFormField)]
+pub enum SortOrder {
+    #[field(value = "asc")]
+    Asc,
+    #[field(value = "desc")]
+    Desc,
+}
+
+/// Query parameters of `GET /ifc`, e.g. `?sort=updated_at&order=desc&metadata[schema]=IFC4`.
+#[derive(FromForm)]
+pub struct ModelQuery {
+    /// Opaque cursor returned as `next_cursor` by the previous page.
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+    pub sort: Option<SortField>,
+    pub order: Option<SortOrder>,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    /// The ID of a project to list the models of.
+    pub project: Option<String>,
+    pub metadata: HashMap<String, String>,
+}
+
+/// A page of IFC models.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ModelPage {
+    pub items: Vec<IFCResponse>,
+    /// Cursor of the next page, absent on the last page.
+    pub next_cursor: Option<String>,
+}
+
+/// Encodes the position after a model as an opaque cursor.
+pub fn encode_cursor(at: DateTime<Utc>, id: &Thing) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{}|{}", at.to_rfc3339(), id.id.to_raw()))
+}
+
+/// Decodes a cursor produced by `encode_cursor`.
+pub fn decode_cursor(cursor: &str) -> Option<(DateTime<Utc>, String)> {
+    let decoded = String::from_utf8(URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()?;
+    let (at, id) = decoded.split_once('|')?;
+    let at = DateTime::parse_from_rfc3339(at).ok()?.with_timezone(&Utc);
+    Some((at, id.to_string()))
+}
+
+/// A model file streamed to the client as it is read from the blob store.
+pub struct ModelFile {
+    pub content_type: ContentType,
+    pub reader: BlobReader,
+}
+
+impl<'r> Responder<'r, 'static> for ModelFile {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .header(self.content_type)
+            .streamed_body(self.reader)
+            .ok()
+    }
+}
+
+/// Moves the inline file content of a model into the blob store.
+///
+/// # Arguments
+/// * `blobs` - The blob store.
+/// * `model` - The IFC model whose `file_content` is stored and replaced by a `file` reference.
+///
+/// # Returns
+/// `true` if a file was stored, or `false` if the model carried no inline content.
+pub async fn store_content(blobs: &BlobStore, model: &mut StoredIFC) -> Result<bool, Status> {
+    let Some(content) = model.file_content.take() else {
+        return Ok(false);
+    };
+    let blob = blobs.put(content.as_bytes()).await.map_err(|e| {
+        println!("Error storing IFC file: {e}");
+        Status::InternalServerError
+    })?;
+    println!("Stored IFC file {} ({} bytes)", blob.sha256, blob.size);
+    model.file = Some(blob);
+    Ok(true)
+}
+
+/// Opens the file of a model for reading from the blob store.
+///
+/// # Arguments
+/// * `blobs` - The blob store.
+/// * `model` - The IFC model.
+///
+/// # Returns
+/// The IFC file content as a stream, or 404 Not Found if the model has no file.
+pub async fn open_content(blobs: &BlobStore, model: &StoredIFC) -> Result<BlobReader, Status> {
+    // Models saved before the blob store existed still carry their content inline.
+    if let Some(content) = &model.file_content {
+        return Ok(Box::pin(Cursor::new(content.clone().into_bytes())));
+    }
+    open_blob(blobs, model.file.as_ref().ok_or(Status::NotFound)?).await
+}
+
+/// Opens a model file in the blob store for reading.
+///
+/// # Arguments
+/// * `blobs` - The blob store.
+/// * `blob` - The reference to the file.
+///
+/// # Returns
+/// The IFC file content as a stream; it fails at the end if the file is corrupt.
+pub async fn open_blob(blobs: &BlobStore, blob: &BlobRef) -> Result<BlobReader, Status> {
+    blobs.open(blob).await.map_err(|e| {
+        println!("Error opening IFC file {}: {e}", blob.sha256);
+        Status::InternalServerError
+    })
+}
+
+/// Parses a STEP model as it is read, without holding its text in memory.
+///
+/// # Arguments
+/// * `reader` - The IFC file content.
+///
+/// # Returns
+/// The parsed model, or 422 Unprocessable Entity if it is not valid STEP.
+pub async fn read_step(reader: BlobReader) -> Result<StepFile, Status> {
+    let reader = SyncIoBridge::new(reader);
+    spawn_blocking(move || StepFile::read(BufReader::new(reader)))
+        .await
+        .map_err(|e| {
+            println!("IFC parser panicked: {e:?}");
+            Status::InternalServerError
+        })?
+        .map_err(|e| {
+            println!("Error parsing IFC model: {e}");
+            Status::UnprocessableEntity
+        })
+}
+
+/// Returns the content type of a model file based on its `ifc.format` metadata.
+///
+/// # Arguments
+/// * `metadata` - The model metadata.
+///
+/// # Returns
+/// `application/xml` for ifcXML, otherwise `application/x-step`.
+pub fn file_content_type(metadata: &HashMap<String, String>) -> ContentType {
+    match metadata.get("ifc.format").map(String::as_str) {
+        Some("ifcxml") => ContentType::XML,
+        _ => ContentType::new("application", "x-step"),
+    }
+}
+
+/// Removes a file from the blob store once no model or revision references it anymore.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `blobs` - The blob store.
+/// * `blob` - The file that is no longer referenced by the caller.
+pub async fn release_file(database: &Database, blobs: &BlobStore, blob: &BlobRef) {
+    let references = database
+        .query::<Thing>(
+            "SELECT VALUE id FROM ifc_models, ifc_revisions WHERE file.sha256 = $sha256 LIMIT 1",
+            ("sha256", blob.sha256.clone()),
+        )
+        .await;
+    match references {
+        Ok(references) if references.is_empty() => {
+            if let Err(e) = blobs.delete(blob).await {
+                println!("Error deleting IFC file {}: {e}", blob.sha256);
+            }
+        }
+        Ok(_) => {}
+        Err(e) => println!(
+            "Error counting references to IFC file {}: {e:?}",
+            blob.sha256
+        ),
+    }
+}
+
+/// Indexes the STEP content of a model and merges the summary into its metadata.
+///
+/// # Arguments
+/// * `model` - The IFC model to index.
+///
+/// # Returns
+/// `Ok(())`, also when the content cannot be indexed, or 500 Internal Server Error if
+/// the indexer panicked.
+pub async fn index_metadata(model: &mut StoredIFC) -> Result<(), Status> {
+    let Some(content) = model.file_content.take() else {
+        return Ok(());
+    };
+    let (content, summary) = spawn_blocking(move || {
+        let summary = summarize(content.as_bytes());
+        (content, summary)
+    })
+    .await
+    .map_err(|e| {
+        println!("IFC indexer panicked: {e:?}");
+        Status::InternalServerError
+    })?;
+    model.file_content = Some(content);
+    match summary {
+        Ok(summary) => model.metadata.extend(summary.to_metadata()),
+        Err(e) => println!("Error indexing IFC model: {e}"),
+    }
+    Ok(())
+}
+
 /// Upload a new IFC model to the database.
 ///
 /// # Arguments
 /// * `database` - The database instance.
-/// * `_authguard` - Authentication Guard.
+/// * `blobs` - The blob store.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
 /// * `_ratelimitguard` - Rate Limit Guard.
 /// * `model` - The IFC model to upload.
 ///
@@ -40,15 +430,50 @@ pub struct StoredIFC {
 #[post("/ifc", data = "<model>")]
 pub async fn data_upload(
     database: &State<Database>,
-    _authguard: AuthGuard,
+    blobs: &State<BlobStore>,
+    authguard: ScopeGuard<IfcWrite>,
     _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
-    model: Json<StoredIFC>,
-) -> Result<Json<StoredIFC>, Status> {
+    model: Json<IFCRequest>,
+) -> Result<Json<IFCResponse>, Status> {
     println!("Processing IFC upload");
-    match database.create("ifc_models", model.into_inner()).await {
+    let request = model.into_inner();
+    let project = upload_project(database, request.project.as_deref(), &authguard).await?;
+    let mut model = StoredIFC::new(request);
+    model.project = project;
+    index_metadata(&mut model).await?;
+    create_model(database, blobs, model, &authguard.user.login)
+        .await
+        .map(|model| Json(model.into()))
+}
+
+/// Saves a new IFC model as its first revision, moving its file into the blob store,
+/// and renders its thumbnail.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `blobs` - The blob store.
+/// * `model` - The IFC model to save.
+/// * `author` - Login of the uploading user, who becomes the owner.
+///
+/// # Returns
+/// The saved IFC model with its ID.
+pub async fn create_model(
+    database: &Database,
+    blobs: &BlobStore,
+    mut model: StoredIFC,
+    author: &str,
+) -> Result<StoredIFC, Status> {
+    store_content(blobs, &mut model).await?;
+    model.owner = author.to_string();
+    model.revision = Some(1);
+    match database.create("ifc_models", model).await {
         Ok(saved_model) => {
             println!("Successfully saved IFC model");
-            Ok(Json(saved_model))
+            record_revision(database, &saved_model, None, author, None).await?;
+            if let (Some(id), Some(file)) = (&saved_model.id, &saved_model.file) {
+                spawn_thumbnail(database, blobs, id, file);
+            }
+            Ok(saved_model)
         }
         Err(e) => {
             println!("Error saving IFC model: {e:?}");
@@ -61,56 +486,260 @@ pub async fn data_upload(
 ///
 /// # Arguments
 /// * `database` - The database instance.
-/// * `_authguard` - Authentication Guard.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
 /// * `_ratelimitguard` - Rate Limit Guard.
+/// * `preconditions` - The `If-None-Match` header.
 /// * `id` - The ID of the IFC model to retrieve.
 ///
 /// # Returns
-/// The retrieved IFC model.
+/// The retrieved IFC model with its `ETag`, 304 Not Modified, or 403 Forbidden if the
+/// model is not shared with the caller.
 #[get("/ifc/<id>")]
 pub async fn data_get(
     database: &State<Database>,
-    _authguard: AuthGuard,
+    authguard: ScopeGuard<IfcRead>,
     _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    preconditions: Preconditions,
     id: String,
-) -> Result<Json<StoredIFC>, Status> {
+) -> Result<Tagged<Json<IFCResponse>>, Status> {
     println!("Retrieving IFC model {id}");
-    match database.read::<StoredIFC>("ifc_models", &id).await {
-        Ok(model) => {
-            println!("Successfully retrieved IFC model {id}");
-            Ok(Json(model))
-        }
-        Err(e) => {
-            println!("Error retrieving IFC model {id}: {e:?}");
-            Err(Status::NotFound)
-        }
+    let model = authorize(database, &id, &authguard, Role::Viewer).await?;
+    println!("Successfully retrieved IFC model {id}");
+    let etag = model.etag();
+    Ok(Tagged {
+        body: (!preconditions.is_not_modified(&etag)).then(|| Json(model.into())),
+        etag,
+    })
+}
+
+/// List IFC models one page at a time.
+///
+/// Only models the caller owns, that are shared with them or that belong to one of their
+/// projects are listed; admins see all.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `query` - Filters, ordering and the cursor of the page.
+///
+/// # Returns
+/// A page of IFC models, or 400 Bad Request for an invalid cursor.
+#[get("/ifc?<query..>")]
+pub async fn data_list(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    query: ModelQuery,
+) -> Result<Json<ModelPage>, Status> {
+    let sort = query.sort.unwrap_or(SortField::CreatedAt);
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let after = match &query.cursor {
+        Some(cursor) => Some(decode_cursor(cursor).ok_or(Status::BadRequest)?),
+        None => None,
+    };
+    let mut filters = vec![Filter::Unset("deleted_at")];
+    filters.extend(access_filter(&authguard));
+    if let Some(project) = query.project {
+        filters.push(Filter::Links("project", "projects", project));
+    }
+    if let Some(name) = query.name {
+        filters.push(Filter::Equals("name", name.into()));
+    }
+    if let Some(version) = query.version {
+        filters.push(Filter::Equals("version", version.into()));
+    }
+    if !query.metadata.is_empty() {
+        filters.push(Filter::Includes(
+            "metadata",
+            query
+                .metadata
+                .into_iter()
+                .map(|(key, value)| (key, value.into()))
+                .collect(),
+        ));
+    }
+    let listing = Listing {
+        filters,
+        sort: match sort {
+            SortField::CreatedAt => "created_at",
+            SortField::UpdatedAt => "updated_at",
+        },
+        descending: matches!(query.order, Some(SortOrder::Desc)),
+        after,
+        // One extra record tells whether another page follows.
+        limit: limit + 1,
+    };
+    let mut items = database
+        .list::<StoredIFC>("ifc_models", &listing)
+        .await
+        .map_err(|e| {
+            println!("Error listing IFC models: {e:?}");
+            Status::InternalServerError
+        })?;
+    let next_cursor = if items.len() > limit {
+        items.truncate(limit);
+        items.last().and_then(|model| {
+            let at = match sort {
+                SortField::CreatedAt => model.created_at,
+                SortField::UpdatedAt => model.updated_at,
+            };
+            model.id.as_ref().map(|id| encode_cursor(at, id))
+        })
+    } else {
+        None
+    };
+    Ok(Json(ModelPage {
+        items: items.into_iter().map(IFCResponse::from).collect(),
+        next_cursor,
+    }))
+}
+
+/// Download the file of an IFC model.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `blobs` - The blob store.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `preconditions` - The `If-None-Match` header.
+/// * `id` - The ID of the IFC model.
+///
+/// # Returns
+/// The STEP or ifcXML file streamed from the blob store and tagged with its SHA-256, or
+/// 304 Not Modified.
+#[get("/ifc/<id>/file")]
+pub async fn data_file(
+    database: &State<Database>,
+    blobs: &State<BlobStore>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    preconditions: Preconditions,
+    id: String,
+) -> Result<Tagged<ModelFile>, Status> {
+    println!("Downloading file of IFC model {id}");
+    let model = authorize(database, &id, &authguard, Role::Viewer).await?;
+    let etag = match &model.file {
+        Some(file) => format!("\"{}\"", file.sha256),
+        None => model.etag(),
+    };
+    if preconditions.is_not_modified(&etag) {
+        return Ok(Tagged { etag, body: None });
     }
+    Ok(Tagged {
+        etag,
+        body: Some(ModelFile {
+            content_type: file_content_type(&model.metadata),
+            reader: open_content(blobs, &model).await?,
+        }),
+    })
 }
 
 /// Update an existing IFC model.
 ///
+/// Every update is recorded as a new revision. The stored file is kept when the
+/// update carries no `file_content`.
+///
 /// # Arguments
 /// * `database` - The database instance.
-/// * `_authguard` - Authentication Guard.
+/// * `blobs` - The blob store.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
 /// * `_ratelimitguard` - Rate Limit Guard.
+/// * `preconditions` - The `If-Match` header.
 /// * `id` - The ID of the IFC model to update.
-/// * `model` - The updated IFC model data.
+/// * `message` - Optional description of the change.
+/// * `model` - The new client-writable fields of the IFC model.
 ///
 /// # Returns
-/// The updated IFC model.
-#[put("/ifc/<id>", data = "<model>")]
+/// The updated IFC model with its new `ETag`, 412 Precondition Failed, or 403 Forbidden
+/// unless the caller is an editor or owner.
+#[put("/ifc/<id>?<message>", data = "<model>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn data_update(
     database: &State<Database>,
-    _authguard: AuthGuard,
+    blobs: &State<BlobStore>,
+    authguard: ScopeGuard<IfcWrite>,
     _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    preconditions: Preconditions,
     id: String,
-    model: Json<StoredIFC>,
-) -> Result<Json<StoredIFC>, Status> {
+    message: Option<String>,
+    model: Json<IFCRequest>,
+) -> Result<Tagged<Json<IFCResponse>>, Status> {
     println!("Updating IFC model {id}");
-    match database.update("ifc_models", &id, model.into_inner()).await {
-        Ok(updated_model) => {
-            println!("Successfully updated IFC model {id}");
-            Ok(Json(updated_model))
+    let previous = authorize(database, &id, &authguard, Role::Editor).await?;
+    check_lock(database, &id, &authguard.user.login).await?;
+    preconditions.check_match(&previous.etag())?;
+    let model = previous.updated(model.into_inner());
+    let updated_model = save_update(
+        database,
+        blobs,
+        &id,
+        previous,
+        model,
+        &authguard.user.login,
+        message,
+    )
+    .await?;
+    Ok(Tagged {
+        etag: updated_model.etag(),
+        body: Some(Json(updated_model.into())),
+    })
+}
+
+/// Replaces a stored model unless another request changed or trashed it since it was read.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `id` - The ID of the IFC model.
+/// * `previous` - The model as read before the change.
+/// * `model` - The model to store.
+/// * `renumber` - Whether the model becomes a new revision. Its number is allocated in the
+///   same transaction, only once the model is known to be unchanged.
+///
+/// # Returns
+/// The stored model, or 412 Precondition Failed if the model changed in the meantime.
+pub async fn replace_model(
+    database: &Database,
+    id: &str,
+    previous: &StoredIFC,
+    mut model: StoredIFC,
+    renumber: bool,
+) -> Result<StoredIFC, Status> {
+    let unchanged = match previous.revision {
+        Some(_) => "revision = $revision",
+        // Models saved before revisions existed are told apart by their modification time.
+        None => "revision IS NONE AND <datetime> updated_at = <datetime> $updated_at",
+    };
+    // Revision numbers start at 1, so 0 marks the model until its number is allocated.
+    let numbering = format!(
+        "UPDATE type::thing('ifc_models', $model) \
+         SET revision = ({ALLOCATE_REVISION})[0] WHERE revision = 0"
+    );
+    if renumber {
+        model.revision = Some(0);
+    }
+    match database
+        .update_where(
+            "ifc_models",
+            id,
+            model,
+            &format!("{unchanged} AND deleted_at IS NONE"),
+            renumber.then_some(numbering.as_str()),
+            json!({
+                "model": id,
+                "revision": previous.revision,
+                "updated_at": previous.updated_at,
+            }),
+        )
+        .await
+    {
+        Ok(Some(model)) => Ok(model),
+        Ok(None) => {
+            println!("IFC model {id} changed while it was being updated");
+            Err(Status::PreconditionFailed)
         }
         Err(e) => {
             println!("Error updating IFC model {id}: {e:?}");
@@ -119,36 +748,201 @@ pub async fn data_update(
     }
 }
 
-/// Delete an IFC model by ID.
+/// Stores a changed IFC model as its next revision and renders its thumbnail.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `blobs` - The blob store.
+/// * `id` - The ID of the IFC model.
+/// * `previous` - The model as currently stored.
+/// * `model` - The changed model; its file is kept when it carries no `file_content`.
+/// * `author` - Login of the user making the change.
+/// * `message` - Optional description of the change.
+///
+/// # Returns
+/// The updated IFC model, or 412 Precondition Failed if it changed in the meantime.
+async fn save_update(
+    database: &Database,
+    blobs: &BlobStore,
+    id: &str,
+    previous: StoredIFC,
+    mut model: StoredIFC,
+    author: &str,
+    message: Option<String>,
+) -> Result<StoredIFC, Status> {
+    index_metadata(&mut model).await?;
+    let stored = store_content(blobs, &mut model).await?;
+    if !stored {
+        model.file = previous.file.clone();
+        model.file_content = previous.file_content.clone();
+    }
+    let updated_model = replace_model(database, id, &previous, model, true).await?;
+    println!("Successfully updated IFC model {id}");
+    record_revision(database, &updated_model, previous.revision, author, message).await?;
+    if stored && let (Some(id), Some(file)) = (&updated_model.id, &updated_model.file) {
+        spawn_thumbnail(database, blobs, id, file);
+    }
+    Ok(updated_model)
+}
+
+/// Partially update an IFC model with a JSON Merge Patch (RFC 7396).
+///
+/// The patch applies to the client-writable fields of [`IFCRequest`]. Members set to
+/// `null` are removed, e.g. `{"metadata": {"author": null}}` drops one metadata key.
+/// Server-managed fields are ignored; `updated_at` is set to the time of the change.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `blobs` - The blob store.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `preconditions` - The `If-Match` header.
+/// * `id` - The ID of the IFC model to update.
+/// * `message` - Optional description of the change.
+/// * `patch` - The merge patch document.
+///
+/// # Returns
+/// The updated IFC model with its new `ETag`, 412 Precondition Failed, or 422
+/// Unprocessable Entity if the patched model is invalid.
+#[patch(
+    "/ifc/<id>?<message>",
+    format = "application/merge-patch+json",
+    data = "<patch>"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn data_patch(
+    database: &State<Database>,
+    blobs: &State<BlobStore>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    preconditions: Preconditions,
+    id: String,
+    message: Option<String>,
+    patch: Json<Value>,
+) -> Result<Tagged<Json<IFCResponse>>, Status> {
+    println!("Patching IFC model {id}");
+    let previous = authorize(database, &id, &authguard, Role::Editor).await?;
+    check_lock(database, &id, &authguard.user.login).await?;
+    preconditions.check_match(&previous.etag())?;
+    let patch = patch.into_inner();
+    if !patch.is_object() {
+        return Err(Status::UnprocessableEntity);
+    }
+    let mut document = serde_json::to_value(IFCRequest::from(&previous)).map_err(|e| {
+        println!("Error serializing IFC model {id}: {e:?}");
+        Status::InternalServerError
+    })?;
+    Utils::merge_patch(&mut document, patch);
+    let request: IFCRequest = serde_json::from_value(document).map_err(|e| {
+        println!("Invalid patch for IFC model {id}: {e}");
+        Status::UnprocessableEntity
+    })?;
+    let model = previous.updated(request);
+    let updated_model = save_update(
+        database,
+        blobs,
+        &id,
+        previous,
+        model,
+        &authguard.user.login,
+        message,
+    )
+    .await?;
+    Ok(Tagged {
+        etag: updated_model.etag(),
+        body: Some(Json(updated_model.into())),
+    })
+}
+
+/// Move an IFC model to the trash.
+///
+/// Trashed models are hidden until they are restored, and purged for good once the
+/// configured `trash_retention_days` have passed.
 ///
 /// # Arguments
 /// * `database` - The database instance.
-/// * `_authguard` - Authentication Guard.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
 /// * `_ratelimitguard` - Rate Limit Guard.
+/// * `preconditions` - The `If-Match` header.
 /// * `id` - The ID of the IFC model to delete.
 ///
 /// # Returns
-/// 204 No Content on success, error status otherwise.
+/// 204 No Content on success, 403 Forbidden unless the caller owns the model,
+/// 412 Precondition Failed on a stale `If-Match`, error status otherwise.
 #[delete("/ifc/<id>")]
 pub async fn data_delete(
     database: &State<Database>,
-    _authguard: AuthGuard,
+    authguard: ScopeGuard<IfcWrite>,
     _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    preconditions: Preconditions,
     id: String,
 ) -> Status {
-    println!("Deleting IFC model {id}");
-    match database.delete::<StoredIFC>("ifc_models", &id).await {
-        Ok(true) => {
-            println!("Successfully deleted IFC model {id}");
+    println!("Moving IFC model {id} to the trash");
+    let previous = match authorize(database, &id, &authguard, Role::Owner).await {
+        Ok(model) => model,
+        Err(status) => return status,
+    };
+    if let Err(status) = check_lock(database, &id, &authguard.user.login).await {
+        return status;
+    }
+    if let Err(status) = preconditions.check_match(&previous.etag()) {
+        return status;
+    }
+    let model = StoredIFC {
+        deleted_at: Some(Utc::now()),
+        deleted_by: Some(authguard.user.login.clone()),
+        ..previous.clone()
+    };
+    match replace_model(database, &id, &previous, model, false).await {
+        Ok(_) => {
+            println!("Successfully moved IFC model {id} to the trash");
+            remove_lock(database, &id).await;
             Status::NoContent
         }
+        Err(status) => status,
+    }
+}
+
+/// Permanently deletes a model with its revisions, branches, shares, links and
+/// thumbnail, releasing files no longer referenced.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `blobs` - The blob store.
+/// * `id` - The ID of the IFC model.
+///
+/// # Returns
+/// 204 No Content on success, error status otherwise.
+pub async fn purge_model(database: &Database, blobs: &BlobStore, id: &str) -> Status {
+    match database.delete::<StoredIFC>("ifc_models", id).await {
+        Ok(true) => {
+            println!("Purging IFC model {id}");
+        }
         Ok(false) => {
             println!("IFC model {id} not found for deletion");
-            Status::NotFound
+            return Status::NotFound;
         }
         Err(e) => {
             println!("Error deleting IFC model {id}: {e:?}");
-            Status::InternalServerError
+            return Status::InternalServerError;
         }
     }
+    remove_lock(database, id).await;
+    delete_branches(database, id).await;
+    delete_shares(database, id).await;
+    delete_links(database, id).await;
+    let revisions = delete_revisions(database, id).await;
+    // Every file of the model is referenced by at least one of its revisions.
+    let files: HashMap<String, BlobRef> = revisions
+        .into_iter()
+        .filter_map(|revision| revision.file)
+        .map(|file| (file.sha256.clone(), file))
+        .collect();
+    for file in files.values() {
+        release_file(database, blobs, file).await;
+    }
+    if let Err(e) = database.delete::<Thumbnail>("thumbnails", id).await {
+        println!("Error deleting thumbnail of IFC model {id}: {e:?}");
+    }
+    Status::NoContent
 }
@@ -1,15 +1,32 @@
 #![warn(clippy::all)]
 #![forbid(unsafe_code)]
 
+use crate::config::Config;
 use crate::database::Database;
-use crate::models::user::User;
+use crate::guards::client::ClientInfo;
+use crate::models::user::Account;
+use crate::routes::account::finish_sign_in;
 use reqwest::Client as HttpClient;
-use rocket::http::{Cookie, CookieJar, SameSite};
+use rocket::http::CookieJar;
 use rocket::response::{Flash, Redirect};
-use rocket::serde::{Deserialize, Serialize};
+use rocket::serde::{Deserialize, DeserializeOwned, Serialize};
 use rocket::{State, get};
 use rocket_oauth2::{OAuth2, TokenResponse};
-use std::time::Duration;
+
+/// A user as returned by the user-info endpoint of an OAuth 2.0 login provider.
+pub trait ProviderUser: DeserializeOwned + Send + Sync + 'static {
+    /// The name of the provider in user identities.
+    const PROVIDER: &'static str;
+
+    /// Returns the URL of the user-info endpoint.
+    ///
+    /// # Arguments
+    /// * `config` - The application configuration.
+    fn user_info_url(config: &Config) -> String;
+
+    /// Maps the user info to a provider-neutral account.
+    fn into_account(self) -> Account;
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
@@ -21,6 +38,151 @@ pub struct GitHubUser {
     pub avatar_url: Option<String>,
 }
 
+impl ProviderUser for GitHubUser {
+    const PROVIDER: &'static str = "github";
+
+    fn user_info_url(_config: &Config) -> String {
+        String::from("https://api.github.com/user")
+    }
+
+    fn into_account(self) -> Account {
+        Account {
+            subject: self.id.to_string(),
+            login: self.login,
+            name: self.name,
+            email: self.email,
+            avatar_url: self.avatar_url,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct GitLabUser {
+    pub id: u64,
+    pub username: String,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+impl ProviderUser for GitLabUser {
+    const PROVIDER: &'static str = "gitlab";
+
+    fn user_info_url(config: &Config) -> String {
+        format!("{}/api/v4/user", config.gitlab_url())
+    }
+
+    fn into_account(self) -> Account {
+        Account {
+            subject: self.id.to_string(),
+            login: self.username,
+            name: self.name,
+            email: self.email,
+            avatar_url: self.avatar_url,
+        }
+    }
+}
+
+/// A user as returned by Microsoft Graph.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+pub struct MicrosoftUser {
+    pub id: String,
+    pub user_principal_name: String,
+    pub display_name: Option<String>,
+    pub mail: Option<String>,
+}
+
+impl ProviderUser for MicrosoftUser {
+    const PROVIDER: &'static str = "microsoft";
+
+    fn user_info_url(_config: &Config) -> String {
+        String::from("https://graph.microsoft.com/v1.0/me")
+    }
+
+    fn into_account(self) -> Account {
+        Account {
+            subject: self.id,
+            login: self.user_principal_name,
+            name: self.display_name,
+            email: self.mail,
+            avatar_url: None,
+        }
+    }
+}
+
+/// A user as returned by the Google userinfo endpoint.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct GoogleUser {
+    pub sub: String,
+    pub email: String,
+    pub name: Option<String>,
+    pub picture: Option<String>,
+}
+
+impl ProviderUser for GoogleUser {
+    const PROVIDER: &'static str = "google";
+
+    fn user_info_url(_config: &Config) -> String {
+        String::from("https://openidconnect.googleapis.com/v1/userinfo")
+    }
+
+    fn into_account(self) -> Account {
+        Account {
+            subject: self.sub,
+            // Google accounts have no username; the address is unique.
+            login: self.email.clone(),
+            name: self.name,
+            email: Some(self.email),
+            avatar_url: self.picture,
+        }
+    }
+}
+
+/// Completes a sign-in with an OAuth 2.0 provider: fetches the user info, then signs
+/// the user in or links the account.
+///
+/// # Arguments
+/// * `token` - The token obtained from the provider.
+/// * `cookies` - The cookie jar of the response.
+/// * `db` - The database instance.
+/// * `config` - The application configuration.
+/// * `client` - The client signing in.
+///
+/// # Returns
+/// A redirect to the application, with a flash message if the sign-in failed.
+async fn complete_sign_in<U: ProviderUser>(
+    token: TokenResponse<U>,
+    cookies: &CookieJar<'_>,
+    db: &Database,
+    config: &Config,
+    client: ClientInfo,
+) -> Result<Redirect, Flash<Redirect>> {
+    // Get the user data from the provider
+    let user: U = HttpClient::new()
+        .get(U::user_info_url(config))
+        .header("User-Agent", "xBIM-App")
+        .bearer_auth(token.access_token())
+        .send()
+        .await
+        .map_err(|_| Flash::error(Redirect::to("/"), "Failed to get user data"))?
+        .json()
+        .await
+        .map_err(|_| Flash::error(Redirect::to("/"), "Failed to parse user data"))?;
+
+    finish_sign_in(
+        db,
+        config,
+        cookies,
+        client,
+        U::PROVIDER,
+        user.into_account(),
+    )
+    .await
+}
+
 #[get("/auth/github/login")]
 pub fn github_login(oauth2: OAuth2<GitHubUser>, cookies: &CookieJar<'_>) -> Redirect {
     oauth2
@@ -33,41 +195,100 @@ pub async fn github_callback(
     token: TokenResponse<GitHubUser>,
     cookies: &CookieJar<'_>,
     db: &State<Database>,
+    config: &State<Config>,
+    client: ClientInfo,
 ) -> Result<Redirect, Flash<Redirect>> {
-    // Get GitHub user data
-    let github_user: GitHubUser = HttpClient::new()
-        .get("https://api.github.com/user")
-        .header("User-Agent", "xBIM-App")
-        .bearer_auth(token.access_token())
-        .send()
-        .await
-        .map_err(|_| Flash::error(Redirect::to("/"), "Failed to get GitHub user data"))?
-        .json()
-        .await
-        .map_err(|_| Flash::error(Redirect::to("/"), "Failed to parse GitHub user data"))?;
-
-    let github_id = github_user.id;
-
-    // Create or update user record
-    let user = User::from(github_user);
-    let user_clone = user.clone();
-    let saved_user = match db.create("users", user).await {
-        Ok(user) => user,
-        Err(_) => db
-            .update("users", &format!("github_id:{github_id}"), user_clone)
-            .await
-            .map_err(|_| Flash::error(Redirect::to("/"), "Failed to save user data"))?,
-    };
-
-    // Store just the session ID in cookie
-    let user_id = saved_user.id.unwrap().to_string();
-    cookies.add_private(
-        Cookie::build(("user_session", user_id))
-            .same_site(SameSite::Lax)
-            .http_only(true)
-            .max_age(Duration::from_secs(86400).try_into().unwrap())
-            .build(),
-    );
-
-    Ok(Redirect::to("/"))
+    complete_sign_in(token, cookies, db, config, client).await
+}
+
+#[get("/auth/gitlab/login")]
+pub fn gitlab_login(oauth2: OAuth2<GitLabUser>, cookies: &CookieJar<'_>) -> Redirect {
+    oauth2.get_redirect(cookies, &["read_user"]).unwrap()
+}
+
+#[get("/auth/gitlab/callback")]
+pub async fn gitlab_callback(
+    token: TokenResponse<GitLabUser>,
+    cookies: &CookieJar<'_>,
+    db: &State<Database>,
+    config: &State<Config>,
+    client: ClientInfo,
+) -> Result<Redirect, Flash<Redirect>> {
+    complete_sign_in(token, cookies, db, config, client).await
+}
+
+#[get("/auth/microsoft/login")]
+pub fn microsoft_login(oauth2: OAuth2<MicrosoftUser>, cookies: &CookieJar<'_>) -> Redirect {
+    oauth2.get_redirect(cookies, &["User.Read"]).unwrap()
+}
+
+#[get("/auth/microsoft/callback")]
+pub async fn microsoft_callback(
+    token: TokenResponse<MicrosoftUser>,
+    cookies: &CookieJar<'_>,
+    db: &State<Database>,
+    config: &State<Config>,
+    client: ClientInfo,
+) -> Result<Redirect, Flash<Redirect>> {
+    complete_sign_in(token, cookies, db, config, client).await
+}
+
+#[get("/auth/google/login")]
+pub fn google_login(oauth2: OAuth2<GoogleUser>, cookies: &CookieJar<'_>) -> Redirect {
+    oauth2
+        .get_redirect(cookies, &["openid", "email", "profile"])
+        .unwrap()
+}
+
+#[get("/auth/google/callback")]
+pub async fn google_callback(
+    token: TokenResponse<GoogleUser>,
+    cookies: &CookieJar<'_>,
+    db: &State<Database>,
+    config: &State<Config>,
+    client: ClientInfo,
+) -> Result<Redirect, Flash<Redirect>> {
+    complete_sign_in(token, cookies, db, config, client).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{from_value, json};
+
+    #[test]
+    fn test_provider_users_map_to_accounts() {
+        let github: GitHubUser =
+            from_value(json!({ "id": 1, "login": "octocat", "name": "The Octocat" })).unwrap();
+        let account = github.into_account();
+        assert_eq!(
+            (account.subject.as_str(), account.login.as_str()),
+            ("1", "octocat")
+        );
+
+        let gitlab: GitLabUser = from_value(json!({ "id": 7, "username": "jdoe" })).unwrap();
+        assert_eq!(gitlab.into_account().login, "jdoe");
+
+        let microsoft: MicrosoftUser = from_value(json!({
+            "id": "87d349ed",
+            "userPrincipalName": "jdoe@contoso.com",
+            "displayName": "Jane Doe",
+            "mail": null,
+        }))
+        .unwrap();
+        let account = microsoft.into_account();
+        assert_eq!(account.subject, "87d349ed");
+        assert_eq!(account.login, "jdoe@contoso.com");
+        assert_eq!(account.name.as_deref(), Some("Jane Doe"));
+
+        let google: GoogleUser = from_value(json!({
+            "sub": "1090",
+            "email": "jane@example.com",
+            "picture": "https://example.com/jane.png",
+        }))
+        .unwrap();
+        let account = google.into_account();
+        assert_eq!(account.login, "jane@example.com");
+        assert_eq!(account.email.as_deref(), Some("jane@example.com"));
+    }
 }
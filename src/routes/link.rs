@@ -0,0 +1,509 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::config::Config;
+use crate::database::Database;
+use crate::guards::{
+    ratelimit::RateLimitGuard,
+    scope::{IfcRead, IfcWrite, ScopeGuard},
+};
+use crate::models::link::{Export, LinkAccess, ShareLink};
+use crate::models::share::Role;
+use crate::routes::data::{
+    IFCResponse, ModelFile, StoredIFC, file_content_type, open_blob, open_content,
+};
+use crate::routes::password::{hash_password, verify_password};
+use crate::routes::revision::find_revision;
+use crate::routes::share::authorize;
+use crate::routes::thumbnail::load_thumbnail;
+use crate::storage::blob::BlobStore;
+use crate::utils::Utils;
+use chrono::{DateTime, Duration, Utc};
+use rocket::{
+    Request, State, async_trait, delete, get,
+    http::{ContentType, Status},
+    post,
+    request::{FromRequest, Outcome},
+    serde::{Deserialize, Serialize, json::Json},
+};
+use rocket_governor::RocketGovernor;
+
+/// Lifetime of a share link when none is requested, in hours.
+const DEFAULT_LINK_HOURS: u64 = 7 * 24;
+/// Longest lifetime of a share link, in hours.
+const MAX_LINK_HOURS: u64 = 90 * 24;
+
+/// A share link to create.
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct NewLink {
+    /// Lifetime of the link in hours, up to 90 days.
+    pub hours: Option<u64>,
+    /// Password reviewers must send in the `Link-Password` header.
+    pub password: Option<String>,
+    /// The export to limit the link to.
+    pub export: Option<Export>,
+}
+
+/// A share link as shown to its owners.
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct LinkResponse {
+    /// The record key of the link.
+    pub id: String,
+    /// The signed token to hand out; the link is opened at `/links/<token>`.
+    pub token: String,
+    pub export: Option<Export>,
+    pub protected: bool,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl LinkResponse {
+    fn new(config: &Config, link: ShareLink) -> Self {
+        let id = link
+            .id
+            .as_ref()
+            .map(|id| id.id.to_raw())
+            .unwrap_or_default();
+        LinkResponse {
+            token: format!("{id}.{}", Utils::sign(&config.secret_key, &id)),
+            id,
+            export: link.export,
+            protected: link.password_hash.is_some(),
+            created_by: link.created_by,
+            created_at: link.created_at,
+            expires_at: link.expires_at,
+            revoked_at: link.revoked_at,
+        }
+    }
+}
+
+/// What a reviewer sees when opening a share link.
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct LinkView {
+    pub export: Option<Export>,
+    pub expires_at: DateTime<Utc>,
+    /// The shared model, for links that are not limited to one export.
+    pub model: Option<IFCResponse>,
+}
+
+/// The reviewer opening a share link: the `Link-Password` header and client details
+/// recorded in the access log.
+pub struct LinkClient {
+    password: Option<String>,
+    ip: Option<String>,
+    user_agent: Option<String>,
+}
+
+#[async_trait]
+impl<'r> FromRequest<'r> for LinkClient {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let headers = request.headers();
+        Outcome::Success(LinkClient {
+            password: headers.get_one("Link-Password").map(str::to_string),
+            ip: request.client_ip().map(|ip| ip.to_string()),
+            user_agent: headers.get_one("User-Agent").map(str::to_string),
+        })
+    }
+}
+
+/// Verifies a share link token and checks it grants access to a resource, recording
+/// the attempt in the access log.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `config` - The application configuration.
+/// * `token` - The signed token.
+/// * `client` - The reviewer.
+/// * `resource` - Name of the requested resource for the access log.
+/// * `export` - The export requested from a link, or `None` for the link itself.
+///
+/// # Returns
+/// The link, 404 Not Found for unknown or forged tokens and trashed models, 410 Gone
+/// for expired or revoked links, 401 Unauthorized for a wrong password, or 403
+/// Forbidden if the link does not cover the resource.
+async fn open_link(
+    database: &Database,
+    config: &Config,
+    token: &str,
+    client: LinkClient,
+    resource: &str,
+    export: fn(&ShareLink) -> Option<Export>,
+) -> Result<ShareLink, Status> {
+    let (key, signature) = token.split_once('.').ok_or(Status::NotFound)?;
+    if !Utils::verify_signature(&config.secret_key, key, signature) {
+        return Err(Status::NotFound);
+    }
+    let link = database
+        .read::<ShareLink>("share_links", key)
+        .await
+        .map_err(|_| Status::NotFound)?;
+    let trashed = database
+        .read::<StoredIFC>("ifc_models", &link.model.id.to_raw())
+        .await
+        .map_or(true, |model| model.deleted_at.is_some());
+    let status = if trashed {
+        Status::NotFound
+    } else if !link.is_active() {
+        Status::Gone
+    } else if let Some(hash) = &link.password_hash
+        && !verify_password(
+            client.password.clone().unwrap_or_default(),
+            Some(hash.clone()),
+        )
+        .await
+    {
+        Status::Unauthorized
+    } else if export(&link).is_some_and(|export| !link.allows(export)) {
+        Status::Forbidden
+    } else {
+        Status::Ok
+    };
+    let access = LinkAccess {
+        id: None,
+        link: link.id.clone().ok_or(Status::NotFound)?,
+        resource: resource.to_string(),
+        status: status.code,
+        ip: client.ip,
+        user_agent: client.user_agent,
+        accessed_at: Utc::now(),
+    };
+    if let Err(e) = database.create("link_accesses", access).await {
+        println!("Error recording access to share link {key}: {e:?}");
+    }
+    if status == Status::Ok {
+        Ok(link)
+    } else {
+        println!("Refused share link {key} for {resource}: {status}");
+        Err(status)
+    }
+}
+
+/// Returns the file export served by a link: the revision it is limited to, if any,
+/// or the current model file.
+fn file_export(link: &ShareLink) -> Option<Export> {
+    match link.export {
+        Some(revision @ Export::Revision { .. }) => Some(revision),
+        _ => Some(Export::File),
+    }
+}
+
+/// Loads a share link of a model.
+async fn load_link(database: &Database, model: &str, link: &str) -> Result<ShareLink, Status> {
+    database
+        .read::<ShareLink>("share_links", link)
+        .await
+        .ok()
+        .filter(|found| found.model.id.to_raw() == model)
+        .ok_or(Status::NotFound)
+}
+
+/// Removes every share link of a model and its access log.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `model` - The ID of the IFC model.
+pub async fn delete_links(database: &Database, model: &str) {
+    if let Err(e) = database
+        .query::<ShareLink>(
+            "DELETE link_accesses WHERE link.model = type::thing('ifc_models', $model); \
+             DELETE share_links WHERE model = type::thing('ifc_models', $model)",
+            ("model", model.to_string()),
+        )
+        .await
+    {
+        println!("Error deleting share links of IFC model {model}: {e:?}");
+    }
+}
+
+/// Create a share link to an IFC model. Only owners may create links.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `config` - The application configuration.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+/// * `link` - Lifetime, password and export of the link.
+///
+/// # Returns
+/// The link with its token.
+#[post("/ifc/<id>/links", data = "<link>")]
+pub async fn link_create(
+    database: &State<Database>,
+    config: &State<Config>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+    link: Json<NewLink>,
+) -> Result<Json<LinkResponse>, Status> {
+    let model = authorize(database, &id, &authguard, Role::Owner).await?;
+    let link = link.into_inner();
+    if let Some(Export::Revision { number }) = link.export {
+        find_revision(database, &id, number).await?;
+    }
+    let key = Utils::random_token(16);
+    let hours = link
+        .hours
+        .unwrap_or(DEFAULT_LINK_HOURS)
+        .clamp(1, MAX_LINK_HOURS);
+    let password_hash = match link.password.filter(|password| !password.is_empty()) {
+        Some(password) => Some(hash_password(password).await?),
+        None => None,
+    };
+    println!("Creating share link {key} to IFC model {id} for {hours} hours");
+    let now = Utc::now();
+    database
+        .upsert(
+            "share_links",
+            &key,
+            ShareLink {
+                id: None,
+                model: model.id.ok_or(Status::NotFound)?,
+                export: link.export,
+                password_hash,
+                created_by: authguard.user.login.clone(),
+                created_at: now,
+                expires_at: now + Duration::hours(hours as i64),
+                revoked_at: None,
+            },
+        )
+        .await
+        .map(|link| Json(LinkResponse::new(config, link)))
+        .map_err(|e| {
+            println!("Error saving share link {key}: {e:?}");
+            Status::InternalServerError
+        })
+}
+
+/// List the share links of an IFC model, including expired and revoked ones.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `config` - The application configuration.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+///
+/// # Returns
+/// The links, newest first.
+#[get("/ifc/<id>/links")]
+pub async fn link_list(
+    database: &State<Database>,
+    config: &State<Config>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+) -> Result<Json<Vec<LinkResponse>>, Status> {
+    authorize(database, &id, &authguard, Role::Owner).await?;
+    database
+        .query::<ShareLink>(
+            "SELECT * FROM share_links WHERE model = type::thing('ifc_models', $model) \
+             ORDER BY created_at DESC",
+            ("model", id.clone()),
+        )
+        .await
+        .map(|links| {
+            Json(
+                links
+                    .into_iter()
+                    .map(|link| LinkResponse::new(config, link))
+                    .collect(),
+            )
+        })
+        .map_err(|e| {
+            println!("Error listing share links of IFC model {id}: {e:?}");
+            Status::InternalServerError
+        })
+}
+
+/// Revoke a share link. The link and its access log are kept.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+/// * `link` - The ID of the share link.
+///
+/// # Returns
+/// 204 No Content on success.
+#[delete("/ifc/<id>/links/<link>")]
+pub async fn link_revoke(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+    link: String,
+) -> Status {
+    if let Err(status) = authorize(database, &id, &authguard, Role::Owner).await {
+        return status;
+    }
+    let mut share_link = match load_link(database, &id, &link).await {
+        Ok(share_link) => share_link,
+        Err(status) => return status,
+    };
+    if share_link.revoked_at.is_none() {
+        share_link.revoked_at = Some(Utc::now());
+    }
+    match database.update("share_links", &link, share_link).await {
+        Ok(_) => {
+            println!("Revoked share link {link} to IFC model {id}");
+            Status::NoContent
+        }
+        Err(e) => {
+            println!("Error revoking share link {link}: {e:?}");
+            Status::InternalServerError
+        }
+    }
+}
+
+/// Get the access log of a share link.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+/// * `link` - The ID of the share link.
+///
+/// # Returns
+/// Every use of the link, newest first.
+#[get("/ifc/<id>/links/<link>/accesses")]
+pub async fn link_accesses(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+    link: String,
+) -> Result<Json<Vec<LinkAccess>>, Status> {
+    authorize(database, &id, &authguard, Role::Owner).await?;
+    load_link(database, &id, &link).await?;
+    database
+        .query(
+            "SELECT * FROM link_accesses WHERE link = type::thing('share_links', $link) \
+             ORDER BY accessed_at DESC",
+            ("link", link.clone()),
+        )
+        .await
+        .map(Json)
+        .map_err(|e| {
+            println!("Error listing accesses of share link {link}: {e:?}");
+            Status::InternalServerError
+        })
+}
+
+/// Open a share link without signing in.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `config` - The application configuration.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `client` - The `Link-Password` header and client details.
+/// * `token` - The signed token of the link.
+///
+/// # Returns
+/// The scope of the link and, unless it is limited to one export, the model.
+#[get("/links/<token>")]
+pub async fn link_open(
+    database: &State<Database>,
+    config: &State<Config>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    client: LinkClient,
+    token: String,
+) -> Result<Json<LinkView>, Status> {
+    let link = open_link(database, config, &token, client, "link", |_| None).await?;
+    let model = match link.export {
+        Some(_) => None,
+        None => {
+            let model = database
+                .read::<StoredIFC>("ifc_models", &link.model.id.to_raw())
+                .await
+                .map_err(|_| Status::NotFound)?;
+            Some(model.into())
+        }
+    };
+    Ok(Json(LinkView {
+        export: link.export,
+        expires_at: link.expires_at,
+        model,
+    }))
+}
+
+/// Download the model file behind a share link; for links to a revision, the file at
+/// that revision.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `blobs` - The blob store.
+/// * `config` - The application configuration.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `client` - The `Link-Password` header and client details.
+/// * `token` - The signed token of the link.
+///
+/// # Returns
+/// The STEP or ifcXML file content.
+#[get("/links/<token>/file")]
+pub async fn link_file(
+    database: &State<Database>,
+    blobs: &State<BlobStore>,
+    config: &State<Config>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    client: LinkClient,
+    token: String,
+) -> Result<ModelFile, Status> {
+    let link = open_link(database, config, &token, client, "file", file_export).await?;
+    let model = link.model.id.to_raw();
+    match file_export(&link) {
+        Some(Export::Revision { number }) => {
+            let revision = find_revision(database, &model, number).await?;
+            let file = revision.file.as_ref().ok_or(Status::NotFound)?;
+            Ok(ModelFile {
+                content_type: file_content_type(&revision.metadata),
+                reader: open_blob(blobs, file).await?,
+            })
+        }
+        _ => {
+            let model = database
+                .read::<StoredIFC>("ifc_models", &model)
+                .await
+                .map_err(|_| Status::NotFound)?;
+            Ok(ModelFile {
+                content_type: file_content_type(&model.metadata),
+                reader: open_content(blobs, &model).await?,
+            })
+        }
+    }
+}
+
+/// Get the thumbnail of the model behind a share link.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `config` - The application configuration.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `client` - The `Link-Password` header and client details.
+/// * `token` - The signed token of the link.
+///
+/// # Returns
+/// The PNG thumbnail.
+#[get("/links/<token>/thumbnail.png")]
+pub async fn link_thumbnail(
+    database: &State<Database>,
+    config: &State<Config>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    client: LinkClient,
+    token: String,
+) -> Result<(ContentType, Vec<u8>), Status> {
+    let link = open_link(database, config, &token, client, "thumbnail", |_| {
+        Some(Export::Thumbnail)
+    })
+    .await?;
+    load_thumbnail(database, &link.model.id.to_raw()).await
+}
@@ -0,0 +1,233 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::config::Config;
+use crate::database::Database;
+use crate::guards::{
+    ratelimit::RateLimitGuard,
+    scope::{Admin, IfcRead, IfcWrite, ScopeGuard},
+};
+use crate::models::lock::ModelLock;
+use crate::models::share::Role;
+use crate::routes::share::authorize;
+use chrono::{Duration, Utc};
+use rocket::{State, delete, get, http::Status, post, serde::json::Json};
+use rocket_governor::RocketGovernor;
+use serde_json::json;
+
+/// Longest lock a client may request, in minutes.
+const MAX_LOCK_MINUTES: u64 = 24 * 60;
+
+/// Returns the lock currently in force on a model, ignoring expired ones.
+async fn current_lock(database: &Database, model: &str) -> Option<ModelLock> {
+    database
+        .read::<ModelLock>("ifc_locks", model)
+        .await
+        .ok()
+        .filter(|lock| !lock.is_expired())
+}
+
+/// Refuses a change to a model that is locked by another user.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `model` - The ID of the IFC model.
+/// * `login` - Login of the user attempting the change.
+///
+/// # Returns
+/// `Ok(())` if the change may proceed, or 423 Locked.
+pub async fn check_lock(database: &Database, model: &str, login: &str) -> Result<(), Status> {
+    match current_lock(database, model).await {
+        Some(lock) if lock.blocks(login) => {
+            println!("IFC model {model} is locked by {}", lock.holder);
+            Err(Status::Locked)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Removes the lock of a model, whether or not it is in force.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `model` - The ID of the IFC model.
+pub async fn remove_lock(database: &Database, model: &str) {
+    if let Err(e) = database.delete::<ModelLock>("ifc_locks", model).await {
+        println!("Error removing lock of IFC model {model}: {e:?}");
+    }
+}
+
+/// Removes every lock whose expiry time has passed.
+///
+/// # Arguments
+/// * `database` - The database instance.
+///
+/// # Returns
+/// The number of removed locks.
+pub async fn purge_expired_locks(database: &Database) -> usize {
+    database
+        .query::<ModelLock>(
+            "DELETE ifc_locks WHERE <datetime> expires_at <= $now RETURN BEFORE",
+            ("now", surrealdb::sql::Datetime::from(Utc::now())),
+        )
+        .await
+        .map(|expired| expired.len())
+        .unwrap_or_else(|e| {
+            println!("Error removing expired locks: {e:?}");
+            0
+        })
+}
+
+/// Get the lock currently held on an IFC model.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+///
+/// # Returns
+/// The lock, or 404 Not Found if the model is not locked.
+#[get("/ifc/<id>/lock")]
+pub async fn lock_get(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+) -> Result<Json<ModelLock>, Status> {
+    authorize(database, &id, &authguard, Role::Viewer).await?;
+    current_lock(database, &id)
+        .await
+        .map(Json)
+        .ok_or(Status::NotFound)
+}
+
+/// Take or renew an exclusive lock on an IFC model.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `config` - The application configuration.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+/// * `minutes` - How long to hold the lock, up to 24 hours.
+///
+/// # Returns
+/// The lock, or 423 Locked if another user holds it.
+#[post("/ifc/<id>/lock?<minutes>")]
+pub async fn lock_acquire(
+    database: &State<Database>,
+    config: &State<Config>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+    minutes: Option<u64>,
+) -> Result<Json<ModelLock>, Status> {
+    authorize(database, &id, &authguard, Role::Editor).await?;
+    let login = authguard.user.login.clone();
+    println!("Locking IFC model {id} for {login}");
+    let now = Utc::now();
+    let duration = match minutes {
+        Some(minutes) => Duration::minutes(minutes.clamp(1, MAX_LOCK_MINUTES) as i64),
+        None => config.lock_timeout(),
+    };
+    // A single statement, so that two users cannot both find the model unlocked and take it.
+    // `acquired_at` is set first, while `holder` and `expires_at` still hold the old lock.
+    let locks = database
+        .query::<ModelLock>(
+            "UPSERT type::thing('ifc_locks', $id) SET \
+                acquired_at = IF holder = $holder AND <datetime> expires_at > <datetime> $now \
+                    THEN acquired_at ELSE $now END, \
+                model = type::thing('ifc_models', $id), \
+                holder = $holder, \
+                expires_at = $expires_at \
+             WHERE holder IS NONE OR holder = $holder OR <datetime> expires_at <= <datetime> $now \
+             RETURN AFTER",
+            json!({
+                "id": id,
+                "holder": login,
+                "now": now,
+                "expires_at": now + duration,
+            }),
+        )
+        .await
+        .map_err(|e| {
+            println!("Error saving lock of IFC model {id}: {e:?}");
+            Status::InternalServerError
+        })?;
+    match locks.into_iter().next() {
+        Some(lock) => Ok(Json(lock)),
+        None => {
+            println!("IFC model {id} is already locked by another user");
+            Err(Status::Locked)
+        }
+    }
+}
+
+/// Release the lock on an IFC model.
+///
+/// The holder may always release the lock, and expired locks may be cleared by anyone.
+/// Locks held by others are broken with `lock_break`.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+///
+/// # Returns
+/// 204 No Content on success, or 423 Locked if the lock belongs to someone else.
+#[delete("/ifc/<id>/lock")]
+pub async fn lock_release(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+) -> Status {
+    if let Err(status) = authorize(database, &id, &authguard, Role::Editor).await {
+        return status;
+    }
+    let login = authguard.user.login.clone();
+    let Ok(lock) = database.read::<ModelLock>("ifc_locks", &id).await else {
+        return Status::NotFound;
+    };
+    if lock.blocks(&login) {
+        println!("IFC model {id} is locked by {}", lock.holder);
+        return Status::Locked;
+    }
+    remove_lock(database, &id).await;
+    println!("Unlocked IFC model {id}");
+    Status::NoContent
+}
+
+/// Break the lock another user holds on an IFC model. Admins only.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `admin` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+///
+/// # Returns
+/// 204 No Content on success, 404 Not Found if the model is not locked, or 403
+/// Forbidden without the `admin` scope.
+#[delete("/ifc/<id>/lock?force")]
+pub async fn lock_break(
+    database: &State<Database>,
+    authguard: ScopeGuard<Admin>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+) -> Status {
+    if let Err(status) = authorize(database, &id, &authguard, Role::Editor).await {
+        return status;
+    }
+    let Ok(lock) = database.read::<ModelLock>("ifc_locks", &id).await else {
+        return Status::NotFound;
+    };
+    println!(
+        "Admin {} is breaking the lock of {} on IFC model {id}",
+        authguard.user.login, lock.holder
+    );
+    remove_lock(database, &id).await;
+    Status::NoContent
+}
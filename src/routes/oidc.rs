@@ -0,0 +1,144 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::config::Config;
+use crate::database::Database;
+use crate::guards::client::ClientInfo;
+use crate::oidc::{OidcClient, map_claims};
+use crate::routes::account::finish_sign_in;
+use crate::utils::Utils;
+use rocket::http::{Cookie, CookieJar, SameSite, Status};
+use rocket::response::{Flash, Redirect};
+use rocket::serde::{
+    Deserialize, Serialize,
+    json::{from_str, to_string},
+};
+use rocket::{State, get};
+
+/// Name of the private cookie holding a sign-in in progress.
+const FLOW_COOKIE: &str = "oidc_flow";
+/// How long a user may take to sign in at the provider, in minutes.
+const FLOW_MINUTES: i64 = 10;
+
+/// A sign-in in progress, kept in a private cookie between login and callback.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct Flow {
+    provider: String,
+    state: String,
+    nonce: String,
+    verifier: String,
+}
+
+/// Sign in with an OpenID Connect provider from the configuration.
+///
+/// # Arguments
+/// * `config` - The application configuration.
+/// * `oidc` - The OpenID Connect client.
+/// * `cookies` - The cookie jar the sign-in is kept in.
+/// * `provider` - The name of the provider.
+///
+/// # Returns
+/// A redirect to the provider, 404 Not Found for unknown providers, or 502 Bad Gateway
+/// if the provider metadata cannot be loaded.
+#[get("/auth/oidc/<provider>/login")]
+pub async fn oidc_login(
+    config: &State<Config>,
+    oidc: &State<OidcClient>,
+    cookies: &CookieJar<'_>,
+    provider: &str,
+) -> Result<Redirect, Status> {
+    let settings = config.oidc_provider(provider).ok_or(Status::NotFound)?;
+    let flow = Flow {
+        provider: provider.to_string(),
+        state: Utils::random_token(16),
+        nonce: Utils::random_token(16),
+        verifier: Utils::random_token(32),
+    };
+    let url = oidc
+        .authorization_url(settings, &flow.state, &flow.nonce, &flow.verifier)
+        .await
+        .map_err(|e| {
+            println!("Error signing in with {provider}: {e}");
+            Status::BadGateway
+        })?;
+    cookies.add_private(
+        Cookie::build((
+            FLOW_COOKIE,
+            to_string(&flow).map_err(|_| Status::InternalServerError)?,
+        ))
+        .same_site(SameSite::Lax)
+        .http_only(true)
+        .max_age(rocket::time::Duration::minutes(FLOW_MINUTES))
+        .build(),
+    );
+    Ok(Redirect::to(url.to_string()))
+}
+
+/// Complete a sign-in with an OpenID Connect provider: validate the ID token, then sign
+/// the user in or link the account.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `config` - The application configuration.
+/// * `oidc` - The OpenID Connect client.
+/// * `cookies` - The cookie jar the sign-in is kept in.
+/// * `client` - The client signing in.
+/// * `provider` - The name of the provider.
+/// * `code` - The authorization code.
+/// * `state` - The state sent with the authorization request.
+/// * `error` - The error reported by the provider, if the sign-in failed.
+///
+/// # Returns
+/// A redirect to the application, with a flash message if the sign-in failed.
+#[allow(clippy::too_many_arguments)]
+#[get("/auth/oidc/<provider>/callback?<code>&<state>&<error>")]
+pub async fn oidc_callback(
+    database: &State<Database>,
+    config: &State<Config>,
+    oidc: &State<OidcClient>,
+    cookies: &CookieJar<'_>,
+    client: ClientInfo,
+    provider: &str,
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+) -> Result<Redirect, Flash<Redirect>> {
+    let fail = |message: &str| Flash::error(Redirect::to("/"), message);
+    let flow = cookies
+        .get_private(FLOW_COOKIE)
+        .and_then(|cookie| from_str::<Flow>(cookie.value()).ok())
+        .ok_or_else(|| fail("Sign-in expired, please try again"))?;
+    cookies.remove_private(FLOW_COOKIE);
+    if let Some(error) = error {
+        println!("Sign-in with {provider} failed: {error}");
+        return Err(fail("Sign-in was cancelled or refused"));
+    }
+    let settings = config
+        .oidc_provider(provider)
+        .filter(|_| flow.provider == provider && state.as_deref() == Some(&flow.state))
+        .ok_or_else(|| fail("Invalid sign-in state"))?;
+    let code = code.ok_or_else(|| fail("Invalid sign-in state"))?;
+
+    // Validate the ID token and map its claims
+    let id_token = oidc
+        .exchange(settings, &code, &flow.verifier)
+        .await
+        .map_err(|e| {
+            println!("Error signing in with {provider}: {e}");
+            fail("Failed to get user data")
+        })?;
+    let claims = oidc
+        .validate(settings, &id_token, &flow.nonce)
+        .await
+        .map_err(|e| {
+            println!("Error signing in with {provider}: {e}");
+            fail("Failed to verify user data")
+        })?;
+    let account = map_claims(settings, &claims).map_err(|e| {
+        println!("Error signing in with {provider}: {e}");
+        fail("Failed to parse user data")
+    })?;
+
+    finish_sign_in(database, config, cookies, client, provider, account).await
+}
@@ -0,0 +1,450 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::config::Config;
+use crate::database::Database;
+use crate::guards::{
+    auth::{AuthGuard, start_session},
+    client::ClientInfo,
+    ratelimit::RateLimitGuard,
+    scope::{Admin, ScopeGuard},
+};
+use crate::models::session::Session;
+use crate::models::token::AccessToken;
+use crate::models::user::User;
+use crate::routes::account::login_taken;
+use crate::utils::Utils;
+use rocket::{
+    State,
+    http::{CookieJar, Status},
+    post, put,
+    serde::{Deserialize, json::Json},
+    tokio::task::spawn_blocking,
+};
+use rocket_governor::RocketGovernor;
+use serde_json::json;
+use std::sync::LazyLock;
+
+/// Longest password accepted, bounding the work of hashing it.
+const MAX_PASSWORD_LENGTH: usize = 1024;
+
+/// Hash checked against when signing in as an unknown user, so that the response takes
+/// as long as for a wrong password and does not reveal which logins exist.
+static DUMMY_HASH: LazyLock<String> =
+    LazyLock::new(|| Utils::hash_password(&Utils::random_token(16)));
+
+/// A local account to create.
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct NewUser {
+    pub login: String,
+    pub password: String,
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
+
+/// Login and password of a user signing in.
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Credentials {
+    pub login: String,
+    pub password: String,
+}
+
+/// A new password for the signed-in user.
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PasswordChange {
+    /// The current password; not needed when the user has none yet.
+    pub current: Option<String>,
+    pub new: String,
+}
+
+/// A new password set by an admin.
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PasswordReset {
+    pub password: String,
+}
+
+/// Returns `true` if a password is long enough, but not too long to hash.
+///
+/// # Arguments
+/// * `password` - The password to check.
+/// * `min_length` - The shortest password accepted, in characters.
+fn is_acceptable_password(password: &str, min_length: usize) -> bool {
+    let length = password.chars().count();
+    length >= min_length && length <= MAX_PASSWORD_LENGTH
+}
+
+/// Hashes a password on the blocking thread pool, as Argon2 is deliberately slow.
+///
+/// # Arguments
+/// * `password` - The password to hash.
+///
+/// # Returns
+/// The hash in PHC string format.
+pub async fn hash_password(password: String) -> Result<String, Status> {
+    spawn_blocking(move || Utils::hash_password(&password))
+        .await
+        .map_err(|e| {
+            println!("Error hashing password: {e:?}");
+            Status::InternalServerError
+        })
+}
+
+/// Checks a password against a hash on the blocking thread pool.
+///
+/// Without a hash, the password is checked against a dummy hash so that the check takes
+/// as long as for a wrong password, and the result is `false`.
+///
+/// # Arguments
+/// * `password` - The password to check.
+/// * `hash` - The hash in PHC string format.
+pub async fn verify_password(password: String, hash: Option<String>) -> bool {
+    spawn_blocking(move || match hash {
+        Some(hash) => Utils::verify_password(&password, &hash),
+        None => {
+            Utils::verify_password(&password, &DUMMY_HASH);
+            false
+        }
+    })
+    .await
+    .is_ok_and(|verified| verified)
+}
+
+/// Checks a new local account and saves it.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `config` - The application configuration.
+/// * `user` - Login, password and profile of the account.
+///
+/// # Returns
+/// The saved user, 400 Bad Request for an invalid login or a weak password, or 409
+/// Conflict if the login is taken.
+async fn create_local_user(
+    database: &Database,
+    config: &Config,
+    user: NewUser,
+) -> Result<User, Status> {
+    if !User::is_valid_login(&user.login)
+        || !is_acceptable_password(&user.password, config.min_password_length())
+    {
+        return Err(Status::BadRequest);
+    }
+    if login_taken(database, &user.login).await {
+        return Err(Status::Conflict);
+    }
+    println!("Creating local user {}", user.login);
+    let password_hash = hash_password(user.password).await?;
+    database
+        .create(
+            "users",
+            User::local(user.login, user.name, user.email, password_hash),
+        )
+        .await
+        .map_err(|e| {
+            println!("Error creating local user: {e:?}");
+            Status::InternalServerError
+        })
+}
+
+/// Loads the user with a login, compared ignoring case as in `login_taken`.
+pub async fn find_by_login(database: &Database, login: &str) -> Result<Option<User>, Status> {
+    database
+        .query::<User>(
+            "SELECT * FROM users WHERE string::lowercase(login) = string::lowercase($login) LIMIT 1",
+            ("login", login.to_string()),
+        )
+        .await
+        .map(|users| users.into_iter().next())
+        .map_err(|e| {
+            println!("Error loading user {login}: {e:?}");
+            Status::InternalServerError
+        })
+}
+
+/// Loads the user a login is given to, e.g. to share a model with them.
+///
+/// # Returns
+/// The user, whose `login` is spelled as stored, or 404 Not Found.
+pub async fn require_user(database: &Database, login: &str) -> Result<User, Status> {
+    find_by_login(database, login).await?.ok_or_else(|| {
+        println!("Unknown user {login}");
+        Status::NotFound
+    })
+}
+
+/// Returns a login spelled as stored, so that record keys built from it match whatever
+/// case a client used. Logins of users that no longer exist are returned unchanged.
+pub async fn canonical_login(database: &Database, login: String) -> Result<String, Status> {
+    Ok(find_by_login(database, &login)
+        .await?
+        .map_or(login, |user| user.login))
+}
+
+/// Saves a new password hash of a user and revokes their sessions.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `user` - The user.
+/// * `password` - The new password.
+/// * `keep` - A session to keep, such as the one the password was changed from. Without
+///   one, the password is being reset and the user's access tokens are revoked as well.
+async fn set_password(
+    database: &Database,
+    mut user: User,
+    password: String,
+    keep: Option<&str>,
+) -> Status {
+    let Some(id) = user.id.as_ref().map(|id| id.id.to_raw()) else {
+        return Status::InternalServerError;
+    };
+    user.password_hash = match hash_password(password).await {
+        Ok(hash) => Some(hash),
+        Err(status) => return status,
+    };
+    if let Err(e) = database.update::<User>("users", &id, user).await {
+        println!("Error saving password of user {id}: {e:?}");
+        return Status::InternalServerError;
+    }
+    let except = match keep {
+        Some(_) => " AND id != type::thing('sessions', $keep)",
+        None => "",
+    };
+    if let Err(e) = database
+        .query::<Session>(
+            &format!(
+                "DELETE sessions WHERE user = type::thing('users', $user){except} RETURN BEFORE"
+            ),
+            json!({ "user": id, "keep": keep }),
+        )
+        .await
+    {
+        println!("Error revoking sessions of user {id}: {e:?}");
+        return Status::InternalServerError;
+    }
+    if keep.is_some() {
+        return Status::NoContent;
+    }
+    match database
+        .query::<AccessToken>(
+            "DELETE access_tokens WHERE user = type::thing('users', $user) RETURN BEFORE",
+            ("user", id.clone()),
+        )
+        .await
+    {
+        Ok(_) => Status::NoContent,
+        Err(e) => {
+            println!("Error revoking access tokens of user {id}: {e:?}");
+            Status::InternalServerError
+        }
+    }
+}
+
+/// Register a local account and sign in with it. Only available when `allow_registration`
+/// is set; logins listed in `admins` are reserved.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `config` - The application configuration.
+/// * `cookies` - The cookie jar of the response.
+/// * `client` - The client signing in.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `user` - Login, password and profile of the account.
+///
+/// # Returns
+/// 201 Created on success, 403 Forbidden if registration is disabled, 400 Bad Request for
+/// an invalid login or a weak password, or 409 Conflict if the login is taken.
+#[post("/auth/register", data = "<user>")]
+pub async fn register(
+    database: &State<Database>,
+    config: &State<Config>,
+    cookies: &CookieJar<'_>,
+    client: ClientInfo,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    user: Json<NewUser>,
+) -> Status {
+    if !config.allow_registration {
+        return Status::Forbidden;
+    }
+    let user = user.into_inner();
+    if config.is_admin(&user.login) {
+        return Status::Conflict;
+    }
+    let user = match create_local_user(database, config, user).await {
+        Ok(user) => user,
+        Err(status) => return status,
+    };
+    let Some(id) = user.id else {
+        return Status::InternalServerError;
+    };
+    match start_session(database, config, cookies, id, client).await {
+        Ok(_) => Status::Created,
+        Err(e) => {
+            println!("Error starting session of {}: {e:?}", user.login);
+            Status::InternalServerError
+        }
+    }
+}
+
+/// Sign in with a login and password, starting a session.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `config` - The application configuration.
+/// * `cookies` - The cookie jar of the response.
+/// * `client` - The client signing in.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `credentials` - Login and password.
+///
+/// # Returns
+/// 204 No Content on success, or 401 Unauthorized for a wrong login or password.
+#[post("/auth/login", data = "<credentials>")]
+pub async fn password_login(
+    database: &State<Database>,
+    config: &State<Config>,
+    cookies: &CookieJar<'_>,
+    client: ClientInfo,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    credentials: Json<Credentials>,
+) -> Status {
+    let user = match find_by_login(database, &credentials.login).await {
+        Ok(user) => user,
+        Err(status) => return status,
+    };
+    let hash = user.as_ref().and_then(|user| user.password_hash.clone());
+    let verified = verify_password(credentials.password.clone(), hash).await;
+    let Some(id) = user.and_then(|user| user.id).filter(|_| verified) else {
+        println!("Failed password sign-in as {}", credentials.login);
+        return Status::Unauthorized;
+    };
+    println!("Signing in {} with password", credentials.login);
+    match start_session(database, config, cookies, id, client).await {
+        Ok(_) => Status::NoContent,
+        Err(e) => {
+            println!("Error starting session of {}: {e:?}", credentials.login);
+            Status::InternalServerError
+        }
+    }
+}
+
+/// Change the password of the signed-in user, or set one for a user who only signed in
+/// with login providers so far. Signs out every other session.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `config` - The application configuration.
+/// * `authguard` - Authentication Guard.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `change` - The current and the new password.
+///
+/// # Returns
+/// 204 No Content on success, 400 Bad Request for a weak password, or 403 Forbidden for a
+/// wrong current password or when called with an access token.
+#[put("/auth/password", data = "<change>")]
+pub async fn password_change(
+    database: &State<Database>,
+    config: &State<Config>,
+    authguard: AuthGuard,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    change: Json<PasswordChange>,
+) -> Status {
+    let Some(session) = authguard.session().map(str::to_string) else {
+        return Status::Forbidden;
+    };
+    let change = change.into_inner();
+    if let Some(hash) = &authguard.user.password_hash
+        && !verify_password(change.current.unwrap_or_default(), Some(hash.clone())).await
+    {
+        return Status::Forbidden;
+    }
+    if !is_acceptable_password(&change.new, config.min_password_length()) {
+        return Status::BadRequest;
+    }
+    println!("Changing password of {}", authguard.user.login);
+    set_password(database, authguard.user, change.new, Some(&session)).await
+}
+
+/// Create a local account. Admins only.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `config` - The application configuration.
+/// * `authguard` - Authentication Guard requiring the `admin` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `user` - Login, initial password and profile of the account.
+///
+/// # Returns
+/// 201 Created on success, 403 Forbidden without the `admin` scope, 400 Bad Request for an invalid
+/// login or a weak password, or 409 Conflict if the login is taken.
+#[post("/users", data = "<user>")]
+pub async fn user_create(
+    database: &State<Database>,
+    config: &State<Config>,
+    authguard: ScopeGuard<Admin>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    user: Json<NewUser>,
+) -> Status {
+    match create_local_user(database, config, user.into_inner()).await {
+        Ok(user) => {
+            println!("{} created local user {}", authguard.user.login, user.login);
+            Status::Created
+        }
+        Err(status) => status,
+    }
+}
+
+/// Reset the password of a user, signing them out everywhere and revoking their access
+/// tokens. Admins only.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `config` - The application configuration.
+/// * `authguard` - Authentication Guard requiring the `admin` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `login` - Login of the user.
+/// * `reset` - The new password.
+///
+/// # Returns
+/// 204 No Content on success, 403 Forbidden without the `admin` scope, 400 Bad Request for a weak
+/// password, or 404 Not Found for unknown users.
+#[put("/users/<login>/password", data = "<reset>")]
+pub async fn password_reset(
+    database: &State<Database>,
+    config: &State<Config>,
+    authguard: ScopeGuard<Admin>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    login: String,
+    reset: Json<PasswordReset>,
+) -> Status {
+    if !is_acceptable_password(&reset.password, config.min_password_length()) {
+        return Status::BadRequest;
+    }
+    let user = match find_by_login(database, &login).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Status::NotFound,
+        Err(status) => return status,
+    };
+    println!("{} reset the password of {login}", authguard.user.login);
+    set_password(database, user, reset.into_inner().password, None).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acceptable_password_length() {
+        assert!(is_acceptable_password("correct horse", 12));
+        assert!(!is_acceptable_password("short", 12));
+        // Length counts characters, not bytes
+        assert!(!is_acceptable_password("ééééé", 6));
+        assert!(is_acceptable_password("éééééé", 6));
+        assert!(!is_acceptable_password(
+            &"x".repeat(MAX_PASSWORD_LENGTH + 1),
+            12
+        ));
+    }
+}
@@ -0,0 +1,62 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::database::Database;
+use crate::guards::{
+    ratelimit::RateLimitGuard,
+    scope::{IfcRead, ScopeGuard},
+};
+use crate::ifc::{geometry, plan};
+use crate::models::share::Role;
+use crate::routes::data::{StoredIFC, open_content, read_step};
+use crate::routes::share::authorize;
+use crate::storage::blob::BlobStore;
+use rocket::{State, get, http::ContentType, http::Status, tokio::task::spawn_blocking};
+use rocket_governor::RocketGovernor;
+
+/// Get a 2D floor plan of a building storey as SVG.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `blobs` - The blob store.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+/// * `globalid` - The GlobalId of the `IfcBuildingStorey`.
+/// * `height` - Height of the section plane above the storey elevation, in metres.
+///
+/// # Returns
+/// The floor plan as an SVG document, or 400 Bad Request for a height that is not a
+/// finite number.
+#[get("/ifc/<id>/storeys/<globalid>/plan.svg?<height>")]
+pub async fn plan_get(
+    database: &State<Database>,
+    blobs: &State<BlobStore>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+    globalid: String,
+    height: Option<f64>,
+) -> Result<(ContentType, String), Status> {
+    let height = height.unwrap_or(plan::DEFAULT_CUT_HEIGHT);
+    if !height.is_finite() {
+        return Err(Status::BadRequest);
+    }
+    authorize(database, &id, &authguard, Role::Viewer).await?;
+    println!("Generating floor plan of storey {globalid} in IFC model {id}");
+    let model = database
+        .read::<StoredIFC>("ifc_models", &id)
+        .await
+        .map_err(|e| {
+            println!("Error retrieving IFC model {id}: {e:?}");
+            Status::NotFound
+        })?;
+    let file = read_step(open_content(blobs, &model).await?).await?;
+    let svg = spawn_blocking(move || {
+        let elements = geometry::extract(&file);
+        plan::floor_plan(&file, &elements, &globalid, height).ok_or(Status::NotFound)
+    })
+    .await
+    .map_err(|_| Status::InternalServerError)??;
+    Ok((ContentType::SVG, svg))
+}
@@ -0,0 +1,431 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::config::Config;
+use crate::database::Database;
+use crate::guards::{
+    auth::AuthGuard,
+    ratelimit::RateLimitGuard,
+    scope::{IfcWrite, ScopeGuard},
+};
+use crate::models::upload::UploadSession;
+use crate::routes::data::IFCResponse;
+use crate::routes::team::upload_project;
+use crate::routes::upload::{UploadFields, store_upload};
+use crate::storage::blob::BlobStore;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::Utc;
+use rocket::{
+    Data, Request, Response, State, async_trait, delete, head,
+    http::Status,
+    patch, post,
+    request::{FromRequest, Outcome},
+    response::{self, Responder},
+    serde::json::Json,
+    tokio::fs::{self, OpenOptions},
+};
+use rocket_governor::RocketGovernor;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use surrealdb::sql::Uuid;
+
+/// Version of the tus resumable upload protocol spoken by these endpoints.
+const TUS_VERSION: &str = "1.0.0";
+
+/// The tus request headers relevant to an upload.
+pub struct TusHeaders {
+    pub length: Option<u64>,
+    pub offset: Option<u64>,
+    pub metadata: HashMap<String, String>,
+}
+
+#[async_trait]
+impl<'r> FromRequest<'r> for TusHeaders {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let headers = request.headers();
+        let number = |name: &str| headers.get_one(name).and_then(|v| v.trim().parse().ok());
+        // `Upload-Metadata` is a comma separated list of `key base64(value)` pairs.
+        let metadata = headers
+            .get_one("Upload-Metadata")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|pair| {
+                let mut parts = pair.trim().splitn(2, ' ');
+                let key = parts.next().filter(|k| !k.is_empty())?;
+                let value = STANDARD.decode(parts.next().unwrap_or_default()).ok()?;
+                Some((key.to_string(), String::from_utf8(value).ok()?))
+            })
+            .collect();
+        Outcome::Success(TusHeaders {
+            length: number("Upload-Length"),
+            offset: number("Upload-Offset"),
+            metadata,
+        })
+    }
+}
+
+/// A tus protocol response carrying the upload state in headers.
+pub struct TusResponse {
+    status: Status,
+    session: Option<UploadSession>,
+    location: Option<String>,
+}
+
+impl TusResponse {
+    fn new(status: Status, session: Option<UploadSession>) -> Self {
+        Self {
+            status,
+            session,
+            location: None,
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for TusResponse {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build();
+        response
+            .status(self.status)
+            .raw_header("Tus-Resumable", TUS_VERSION)
+            .raw_header("Cache-Control", "no-store");
+        if let Some(session) = self.session {
+            response
+                .raw_header("Upload-Offset", session.offset.to_string())
+                .raw_header("Upload-Length", session.length.to_string())
+                .raw_header(
+                    "Upload-Expires",
+                    session
+                        .expires_at
+                        .format("%a, %d %b %Y %H:%M:%S GMT")
+                        .to_string(),
+                );
+        }
+        if let Some(location) = self.location {
+            response.raw_header("Location", location);
+        }
+        response.ok()
+    }
+}
+
+/// Returns the path of the partial file that backs an upload session.
+fn chunk_path(config: &Config, id: &str) -> PathBuf {
+    config.upload_dir().join(id)
+}
+
+/// Loads an upload session of a user, rejecting unknown and expired ones. Sessions of
+/// other users are reported as unknown.
+async fn load_session(
+    database: &Database,
+    id: &str,
+    authguard: &AuthGuard,
+) -> Result<UploadSession, Status> {
+    let session = database
+        .read::<UploadSession>("upload_sessions", id)
+        .await
+        .map_err(|_| Status::NotFound)?;
+    if session.owner != authguard.user.login {
+        println!("{} may not access upload {id}", authguard.user.login);
+        return Err(Status::NotFound);
+    }
+    if session.is_expired() {
+        return Err(Status::Gone);
+    }
+    Ok(session)
+}
+
+/// Removes an upload session together with its partial file.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `config` - The application configuration.
+/// * `id` - The ID of the upload session.
+pub async fn remove_session(database: &Database, config: &Config, id: &str) {
+    let path = chunk_path(config, id);
+    if let Err(e) = fs::remove_file(&path).await
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        println!("Error removing partial upload {}: {e}", path.display());
+    }
+    if let Err(e) = database
+        .delete::<UploadSession>("upload_sessions", id)
+        .await
+    {
+        println!("Error deleting upload session {id}: {e:?}");
+    }
+}
+
+/// Create a resumable upload session.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `config` - The application configuration.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `tus` - The `Upload-Length` and `Upload-Metadata` (name, version, description,
+///   filename, project).
+///
+/// # Returns
+/// 201 Created with the session URL in `Location`.
+#[post("/ifc/uploads")]
+pub async fn upload_create(
+    database: &State<Database>,
+    config: &State<Config>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    mut tus: TusHeaders,
+) -> Result<TusResponse, Status> {
+    let length = tus.length.ok_or(Status::BadRequest)?;
+    if length > config.max_upload_size().as_u64() {
+        return Err(Status::PayloadTooLarge);
+    }
+    let project = tus.metadata.remove("project");
+    upload_project(database, project.as_deref(), &authguard).await?;
+    let id = Uuid::new().to_raw();
+    println!("Creating resumable upload {id} of {length} bytes");
+
+    let dir = config.upload_dir();
+    fs::create_dir_all(&dir)
+        .await
+        .and(fs::File::create(chunk_path(config, &id)).await.map(drop))
+        .map_err(|e| {
+            println!("Error creating partial upload in {}: {e}", dir.display());
+            Status::InternalServerError
+        })?;
+
+    let now = Utc::now();
+    let session = UploadSession {
+        id: None,
+        owner: authguard.user.login.clone(),
+        length,
+        offset: 0,
+        name: tus.metadata.remove("name"),
+        version: tus.metadata.remove("version"),
+        description: tus.metadata.remove("description"),
+        filename: tus.metadata.remove("filename"),
+        project,
+        created_at: now,
+        expires_at: now + config.upload_expiry(),
+    };
+    let session = database
+        .upsert("upload_sessions", &id, session)
+        .await
+        .map_err(|e| {
+            println!("Error saving upload session {id}: {e:?}");
+            Status::InternalServerError
+        })?;
+    Ok(TusResponse {
+        location: Some(format!("/ifc/uploads/{id}")),
+        ..TusResponse::new(Status::Created, Some(session))
+    })
+}
+
+/// Get the current offset of a resumable upload.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the upload session.
+///
+/// # Returns
+/// 200 OK with `Upload-Offset` and `Upload-Length` headers.
+#[head("/ifc/uploads/<id>")]
+pub async fn upload_offset(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+) -> Result<TusResponse, Status> {
+    let session = load_session(database, &id, &authguard).await?;
+    Ok(TusResponse::new(Status::Ok, Some(session)))
+}
+
+/// Append a chunk to a resumable upload.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `config` - The application configuration.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the upload session.
+/// * `tus` - The `Upload-Offset` the chunk starts at.
+/// * `data` - The chunk contents.
+///
+/// # Returns
+/// 204 No Content with the new `Upload-Offset`, or 409 Conflict on an offset mismatch.
+#[patch(
+    "/ifc/uploads/<id>",
+    format = "application/offset+octet-stream",
+    data = "<data>"
+)]
+pub async fn upload_chunk(
+    database: &State<Database>,
+    config: &State<Config>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+    tus: TusHeaders,
+    data: Data<'_>,
+) -> Result<TusResponse, Status> {
+    let mut session = load_session(database, &id, &authguard).await?;
+    if tus.offset != Some(session.offset) {
+        println!(
+            "Rejecting chunk for upload {id}: offset {:?} != {}",
+            tus.offset, session.offset
+        );
+        return Err(Status::Conflict);
+    }
+
+    let path = chunk_path(config, &id);
+    let mut file = OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .await
+        .map_err(|_| Status::Gone)?;
+    let on_disk = file.metadata().await.map_err(|_| Status::Gone)?.len();
+    if on_disk != session.offset {
+        // A previous chunk was interrupted after writing; drop the unacknowledged tail.
+        file.set_len(session.offset)
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+    }
+
+    let remaining = session.length - session.offset;
+    let written = data
+        .open(remaining.into())
+        .stream_to(&mut file)
+        .await
+        .map_err(|e| {
+            println!("Error writing chunk for upload {id}: {e}");
+            Status::InternalServerError
+        })?;
+    if !written.complete {
+        let _ = file.set_len(session.offset).await;
+        return Err(Status::PayloadTooLarge);
+    }
+
+    session.offset += written.written;
+    session.expires_at = Utc::now() + config.upload_expiry();
+    let session = database
+        .update("upload_sessions", &id, session)
+        .await
+        .map_err(|e| {
+            println!("Error updating upload session {id}: {e:?}");
+            Status::InternalServerError
+        })?;
+    println!(
+        "Upload {id} at {} of {} bytes",
+        session.offset, session.length
+    );
+    Ok(TusResponse::new(Status::NoContent, Some(session)))
+}
+
+/// Finalize a completed resumable upload into a stored IFC model.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `blobs` - The blob store.
+/// * `config` - The application configuration.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the upload session.
+///
+/// # Returns
+/// The saved IFC model, or 409 Conflict if bytes are still missing.
+#[post("/ifc/uploads/<id>/finalize")]
+pub async fn upload_finalize(
+    database: &State<Database>,
+    blobs: &State<BlobStore>,
+    config: &State<Config>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+) -> Result<Json<IFCResponse>, Status> {
+    let session = load_session(database, &id, &authguard).await?;
+    if !session.is_complete() {
+        return Err(Status::Conflict);
+    }
+    println!("Finalizing resumable upload {id}");
+    let project = upload_project(database, session.project.as_deref(), &authguard).await?;
+    let result = store_upload(
+        database,
+        blobs,
+        chunk_path(config, &id),
+        config.max_upload_size().as_u64(),
+        session.filename,
+        UploadFields {
+            name: session.name,
+            version: session.version,
+            description: session.description,
+            project: session.project,
+        },
+        project,
+        &authguard,
+    )
+    .await;
+    remove_session(database, config, &id).await;
+    result
+}
+
+/// Cancel a resumable upload and discard the received data.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `config` - The application configuration.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the upload session.
+///
+/// # Returns
+/// 204 No Content on success, or 404 Not Found for unknown uploads and uploads of other
+/// users.
+#[delete("/ifc/uploads/<id>")]
+pub async fn upload_cancel(
+    database: &State<Database>,
+    config: &State<Config>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+) -> Result<TusResponse, Status> {
+    let session = database
+        .read::<UploadSession>("upload_sessions", &id)
+        .await
+        .map_err(|_| Status::NotFound)?;
+    if session.owner != authguard.user.login {
+        return Err(Status::NotFound);
+    }
+    remove_session(database, config, &id).await;
+    Ok(TusResponse::new(Status::NoContent, None))
+}
+
+/// Removes every upload session whose expiry time has passed.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `config` - The application configuration.
+///
+/// # Returns
+/// The number of removed sessions.
+pub async fn purge_expired_uploads(database: &Database, config: &Config) -> usize {
+    let expired: Vec<UploadSession> = match database
+        .query(
+            "SELECT * FROM upload_sessions WHERE <datetime> expires_at <= $now",
+            ("now", surrealdb::sql::Datetime::from(Utc::now())),
+        )
+        .await
+    {
+        Ok(expired) => expired,
+        Err(e) => {
+            println!("Error listing expired uploads: {e:?}");
+            return 0;
+        }
+    };
+    for session in &expired {
+        if let Some(id) = &session.id {
+            remove_session(database, config, &id.id.to_raw()).await;
+        }
+    }
+    expired.len()
+}
@@ -0,0 +1,336 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::database::Database;
+use crate::guards::{
+    precondition::Preconditions,
+    ratelimit::RateLimitGuard,
+    scope::{IfcRead, IfcWrite, ScopeGuard},
+};
+use crate::models::share::Role;
+use crate::models::{branch::MAIN_BRANCH, revision::Revision};
+use crate::routes::data::{
+    IFCResponse, ModelFile, StoredIFC, file_content_type, open_blob, replace_model,
+};
+use crate::routes::lock::check_lock;
+use crate::routes::share::authorize;
+use crate::routes::thumbnail::spawn_thumbnail;
+use crate::storage::blob::BlobStore;
+use chrono::Utc;
+use rocket::{State, get, http::Status, post, serde::json::Json};
+use rocket_governor::RocketGovernor;
+use serde_json::{Value, json};
+
+/// Looks up a revision of a model by its number.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `model` - The ID of the IFC model.
+/// * `number` - The revision number.
+///
+/// # Returns
+/// The revision, or 404 Not Found.
+pub async fn find_revision(
+    database: &Database,
+    model: &str,
+    number: u64,
+) -> Result<Revision, Status> {
+    database
+        .query::<Revision>(
+            "SELECT * FROM ifc_revisions \
+             WHERE model = type::thing('ifc_models', $model) AND number = $number",
+            json!({ "model": model, "number": number }),
+        )
+        .await
+        .map_err(|e| {
+            println!("Error retrieving revision {number} of IFC model {model}: {e:?}");
+            Status::InternalServerError
+        })?
+        .into_iter()
+        .next()
+        .ok_or(Status::NotFound)
+}
+
+/// Counts up the revision counter of the model `$model` and returns the new number.
+pub const ALLOCATE_REVISION: &str = "UPSERT type::thing('revision_counters', $model) \
+    SET last = (IF last IS NONE THEN \
+        (SELECT VALUE number FROM ifc_revisions \
+         WHERE model = type::thing('ifc_models', $model) \
+         ORDER BY number DESC LIMIT 1)[0] ?? 0 \
+    ELSE last END) + 1 RETURN VALUE last";
+
+/// Allocates the number of the next revision of a model, across all branches.
+///
+/// Numbers are counted up in a single statement on a per-model counter in
+/// `revision_counters`, so concurrent changes never get the same number. The counter of a
+/// model recorded before it existed starts from its highest revision number.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `model` - The ID of the IFC model.
+///
+/// # Returns
+/// One more than the highest revision number allocated so far.
+pub async fn next_revision_number(database: &Database, model: &str) -> Result<u64, Status> {
+    let numbers: Vec<u64> = database
+        .query(ALLOCATE_REVISION, ("model", model.to_string()))
+        .await
+        .map_err(|e| {
+            println!("Error numbering revision of IFC model {model}: {e:?}");
+            Status::InternalServerError
+        })?;
+    numbers.first().copied().ok_or_else(|| {
+        println!("Error numbering revision of IFC model {model}: no number allocated");
+        Status::InternalServerError
+    })
+}
+
+/// Captures the state of a model as an unsaved revision on the main branch.
+///
+/// # Arguments
+/// * `model` - The IFC model; its `revision` becomes the revision number.
+/// * `author` - Login of the user who made the change.
+/// * `message` - Optional description of the change.
+///
+/// # Returns
+/// The revision, or 500 Internal Server Error if the model has no ID.
+pub fn snapshot(
+    model: &StoredIFC,
+    author: &str,
+    message: Option<String>,
+) -> Result<Revision, Status> {
+    Ok(Revision {
+        id: None,
+        model: model.id.clone().ok_or(Status::InternalServerError)?,
+        number: model.revision.unwrap_or(1),
+        branch: String::from(MAIN_BRANCH),
+        parent: None,
+        merged: None,
+        author: author.to_string(),
+        message,
+        created_at: Utc::now(),
+        name: model.name.clone(),
+        version: model.version.clone(),
+        description: model.description.clone(),
+        metadata: model.metadata.clone(),
+        file: model.file.clone(),
+    })
+}
+
+/// Saves a revision.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `revision` - The revision to save.
+///
+/// # Returns
+/// The saved revision.
+pub async fn save_revision(database: &Database, revision: Revision) -> Result<Revision, Status> {
+    let number = revision.number;
+    database
+        .create("ifc_revisions", revision)
+        .await
+        .map_err(|e| {
+            println!("Error saving revision {number}: {e:?}");
+            Status::InternalServerError
+        })
+}
+
+/// Records the current state of a model as main branch revision `model.revision`.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `model` - The saved IFC model.
+/// * `parent` - Number of the revision the change was based on.
+/// * `author` - Login of the user who made the change.
+/// * `message` - Optional description of the change.
+///
+/// # Returns
+/// The recorded revision.
+pub async fn record_revision(
+    database: &Database,
+    model: &StoredIFC,
+    parent: Option<u64>,
+    author: &str,
+    message: Option<String>,
+) -> Result<Revision, Status> {
+    let revision = Revision {
+        parent,
+        ..snapshot(model, author, message)?
+    };
+    save_revision(database, revision).await
+}
+
+/// Deletes every revision of a model along with its revision counter.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `model` - The ID of the IFC model.
+///
+/// # Returns
+/// The deleted revisions.
+pub async fn delete_revisions(database: &Database, model: &str) -> Vec<Revision> {
+    if let Err(e) = database
+        .query::<Value>(
+            "DELETE type::thing('revision_counters', $model)",
+            ("model", model.to_string()),
+        )
+        .await
+    {
+        println!("Error deleting revision counter of IFC model {model}: {e:?}");
+    }
+    database
+        .query(
+            "DELETE ifc_revisions WHERE model = type::thing('ifc_models', $model) RETURN BEFORE",
+            ("model", model.to_string()),
+        )
+        .await
+        .unwrap_or_else(|e| {
+            println!("Error deleting revisions of IFC model {model}: {e:?}");
+            Vec::new()
+        })
+}
+
+/// List the revisions of an IFC model, newest first.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+/// * `branch` - Only list revisions committed to this branch.
+///
+/// # Returns
+/// The revisions of the model.
+#[get("/ifc/<id>/revisions?<branch>")]
+pub async fn revision_list(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+    branch: Option<String>,
+) -> Result<Json<Vec<Revision>>, Status> {
+    authorize(database, &id, &authguard, Role::Viewer).await?;
+    println!("Listing revisions of IFC model {id}");
+    database
+        .query(
+            "SELECT * FROM ifc_revisions \
+             WHERE model = type::thing('ifc_models', $model) \
+             AND (!$branch OR branch = $branch) ORDER BY number DESC",
+            json!({ "model": id, "branch": branch }),
+        )
+        .await
+        .map(Json)
+        .map_err(|e| {
+            println!("Error listing revisions of IFC model {id}: {e:?}");
+            Status::InternalServerError
+        })
+}
+
+/// Get a single revision of an IFC model.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+/// * `number` - The revision number.
+///
+/// # Returns
+/// The revision.
+#[get("/ifc/<id>/revisions/<number>")]
+pub async fn revision_get(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+    number: u64,
+) -> Result<Json<Revision>, Status> {
+    authorize(database, &id, &authguard, Role::Viewer).await?;
+    find_revision(database, &id, number).await.map(Json)
+}
+
+/// Download the model file as it was at a revision.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `blobs` - The blob store.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+/// * `number` - The revision number.
+///
+/// # Returns
+/// The STEP or ifcXML file content.
+#[get("/ifc/<id>/revisions/<number>/file")]
+pub async fn revision_file(
+    database: &State<Database>,
+    blobs: &State<BlobStore>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+    number: u64,
+) -> Result<ModelFile, Status> {
+    authorize(database, &id, &authguard, Role::Viewer).await?;
+    let revision = find_revision(database, &id, number).await?;
+    let file = revision.file.as_ref().ok_or(Status::NotFound)?;
+    Ok(ModelFile {
+        content_type: file_content_type(&revision.metadata),
+        reader: open_blob(blobs, file).await?,
+    })
+}
+
+/// Roll an IFC model back to an earlier revision.
+///
+/// The rollback is itself recorded as a new revision, so no history is lost.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `blobs` - The blob store.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `preconditions` - The `If-Match` header.
+/// * `id` - The ID of the IFC model.
+/// * `number` - The revision to restore.
+/// * `message` - Optional description of the rollback.
+///
+/// # Returns
+/// The restored IFC model, or 412 Precondition Failed on a stale `If-Match` or if the
+/// model changed in the meantime.
+#[post("/ifc/<id>/revisions/<number>/rollback?<message>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn revision_rollback(
+    database: &State<Database>,
+    blobs: &State<BlobStore>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    preconditions: Preconditions,
+    id: String,
+    number: u64,
+    message: Option<String>,
+) -> Result<Json<IFCResponse>, Status> {
+    let previous = authorize(database, &id, &authguard, Role::Editor).await?;
+    println!("Rolling IFC model {id} back to revision {number}");
+    check_lock(database, &id, &authguard.user.login).await?;
+    preconditions.check_match(&previous.etag())?;
+    let target = find_revision(database, &id, number).await?;
+    let model = StoredIFC {
+        name: target.name,
+        version: target.version,
+        description: target.description,
+        metadata: target.metadata,
+        file: target.file,
+        file_content: None,
+        updated_at: Utc::now(),
+        ..previous.clone()
+    };
+    let parent = previous.revision;
+    let model = replace_model(database, &id, &previous, model, true).await?;
+    let message = message.or_else(|| Some(format!("Roll back to revision {number}")));
+    record_revision(database, &model, parent, &authguard.user.login, message).await?;
+    if let (Some(model_id), Some(file)) = (&model.id, &model.file) {
+        spawn_thumbnail(database, blobs, model_id, file);
+    }
+    println!("Successfully rolled IFC model {id} back to revision {number}");
+    Ok(Json(model.into()))
+}
@@ -0,0 +1,301 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::database::Database;
+use crate::guards::{
+    auth::{AuthGuard, SESSION_COOKIE},
+    ratelimit::RateLimitGuard,
+    scope::{Admin, IfcRead, IfcWrite, ScopeGuard},
+};
+use crate::models::session::Session;
+use chrono::{DateTime, Utc};
+use rocket::{
+    State, delete, get,
+    http::{CookieJar, Status},
+    post,
+    serde::{Serialize, json::Json},
+};
+use rocket_governor::RocketGovernor;
+use serde_json::json;
+
+/// A session as shown to its user.
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SessionResponse {
+    /// The record key of the session.
+    pub id: String,
+    pub ip: Option<String>,
+    /// The browser or client, as reported in its `User-Agent` header.
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// Whether this is the session the request was made with.
+    pub current: bool,
+}
+
+impl SessionResponse {
+    fn new(session: Session, current: Option<&str>) -> Self {
+        let id = session.id.map(|id| id.id.to_raw()).unwrap_or_default();
+        SessionResponse {
+            current: current == Some(id.as_str()),
+            id,
+            ip: session.ip,
+            user_agent: session.user_agent,
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+            expires_at: session.expires_at,
+        }
+    }
+}
+
+/// Returns the record key of the signed-in user.
+fn user_key(authguard: &AuthGuard) -> Result<String, Status> {
+    authguard
+        .user
+        .id
+        .as_ref()
+        .map(|id| id.id.to_raw())
+        .ok_or(Status::InternalServerError)
+}
+
+/// Sign out, destroying the current session.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `cookies` - The cookie jar holding the session cookie.
+/// * `authguard` - Authentication Guard.
+///
+/// # Returns
+/// 204 No Content on success, or 400 Bad Request for requests made with an access token.
+#[post("/auth/logout")]
+pub async fn logout(
+    database: &State<Database>,
+    cookies: &CookieJar<'_>,
+    authguard: AuthGuard,
+) -> Status {
+    let Some(session) = authguard.session() else {
+        return Status::BadRequest;
+    };
+    println!("Signing out {}", authguard.user.login);
+    cookies.remove_private(SESSION_COOKIE);
+    match database.delete::<Session>("sessions", session).await {
+        Ok(_) => Status::NoContent,
+        Err(e) => {
+            println!("Error deleting session of {}: {e:?}", authguard.user.login);
+            Status::InternalServerError
+        }
+    }
+}
+
+/// List the active sessions of the signed-in user, most recently used first. Only
+/// available from a signed-in session.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+///
+/// # Returns
+/// The sessions of the user, or 403 Forbidden when called with an access token.
+#[get("/auth/sessions")]
+pub async fn session_list(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+) -> Result<Json<Vec<SessionResponse>>, Status> {
+    let current = authguard.session().ok_or(Status::Forbidden)?;
+    let sessions = database
+        .query::<Session>(
+            "SELECT * FROM sessions WHERE user = type::thing('users', $user) \
+             AND <datetime> expires_at > <datetime> $now ORDER BY last_seen_at DESC",
+            json!({ "user": user_key(&authguard)?, "now": Utc::now() }),
+        )
+        .await
+        .map_err(|e| {
+            println!("Error listing sessions of {}: {e:?}", authguard.user.login);
+            Status::InternalServerError
+        })?;
+    Ok(Json(active_sessions(sessions, current)))
+}
+
+/// Turns the sessions of a user into responses, leaving out expired ones that the
+/// periodic cleanup has not removed yet.
+///
+/// # Arguments
+/// * `sessions` - The sessions of a user.
+/// * `current` - The session the request was made with.
+fn active_sessions(sessions: Vec<Session>, current: &str) -> Vec<SessionResponse> {
+    sessions
+        .into_iter()
+        .filter(Session::is_active)
+        .map(|session| SessionResponse::new(session, Some(current)))
+        .collect()
+}
+
+/// Revoke one of the signed-in user's sessions, e.g. of a lost device. Only available
+/// from a signed-in session.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The record key of the session.
+///
+/// # Returns
+/// 204 No Content on success, 404 Not Found if the user holds no such session, or 403
+/// Forbidden when called with an access token.
+#[delete("/auth/sessions/<id>")]
+pub async fn session_revoke(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+) -> Status {
+    if authguard.session().is_none() {
+        return Status::Forbidden;
+    }
+    let user = match user_key(&authguard) {
+        Ok(user) => user,
+        Err(status) => return status,
+    };
+    match database
+        .query::<Session>(
+            "DELETE type::thing('sessions', $session) \
+             WHERE user = type::thing('users', $user) RETURN BEFORE",
+            json!({ "session": id, "user": user }),
+        )
+        .await
+    {
+        Ok(revoked) if revoked.is_empty() => Status::NotFound,
+        Ok(_) => {
+            println!("{} revoked session {id}", authguard.user.login);
+            Status::NoContent
+        }
+        Err(e) => {
+            println!("Error revoking session {id}: {e:?}");
+            Status::InternalServerError
+        }
+    }
+}
+
+/// Revoke every other session of the signed-in user, signing them out on all other
+/// devices. Only available from a signed-in session, which is kept.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+///
+/// # Returns
+/// 204 No Content on success, or 403 Forbidden when called with an access token.
+#[delete("/auth/sessions")]
+pub async fn session_revoke_others(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+) -> Status {
+    let Some(current) = authguard.session().map(str::to_string) else {
+        return Status::Forbidden;
+    };
+    let user = match user_key(&authguard) {
+        Ok(user) => user,
+        Err(status) => return status,
+    };
+    match database
+        .query::<Session>(
+            "DELETE sessions WHERE user = type::thing('users', $user) \
+             AND id != type::thing('sessions', $current) RETURN BEFORE",
+            json!({ "user": user, "current": current }),
+        )
+        .await
+    {
+        Ok(revoked) => {
+            println!(
+                "{} revoked {} other sessions",
+                authguard.user.login,
+                revoked.len()
+            );
+            Status::NoContent
+        }
+        Err(e) => {
+            println!("Error revoking sessions of {}: {e:?}", authguard.user.login);
+            Status::InternalServerError
+        }
+    }
+}
+
+/// Revoke every session of a user, signing them out everywhere. Admins only.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `admin` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `login` - Login of the user.
+///
+/// # Returns
+/// 204 No Content on success, or 403 Forbidden without the `admin` scope.
+#[delete("/users/<login>/sessions")]
+pub async fn session_revoke_all(
+    database: &State<Database>,
+    authguard: ScopeGuard<Admin>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    login: String,
+) -> Status {
+    match database
+        .query::<Session>(
+            "DELETE sessions WHERE string::lowercase(user.login) = string::lowercase($login) \
+             RETURN BEFORE",
+            ("login", login.clone()),
+        )
+        .await
+    {
+        Ok(revoked) => {
+            println!(
+                "{} revoked {} sessions of {login}",
+                authguard.user.login,
+                revoked.len()
+            );
+            Status::NoContent
+        }
+        Err(e) => {
+            println!("Error revoking sessions of {login}: {e:?}");
+            Status::InternalServerError
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use surrealdb::sql::Thing;
+
+    fn session(key: &str, expires_in: Duration) -> Session {
+        let now = Utc::now();
+        Session {
+            id: Some(Thing::from(("sessions", key))),
+            user: Thing::from(("users", "octocat")),
+            ip: None,
+            user_agent: None,
+            created_at: now - Duration::days(1),
+            last_seen_at: now,
+            expires_at: now + expires_in,
+        }
+    }
+
+    #[test]
+    fn test_expired_sessions_are_not_listed() {
+        let listed = active_sessions(
+            vec![
+                session("laptop", Duration::hours(1)),
+                session("phone", -Duration::seconds(1)),
+            ],
+            "laptop",
+        );
+
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, "laptop");
+        assert!(listed[0].current);
+    }
+}
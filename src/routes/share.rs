@@ -0,0 +1,304 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::database::{Database, Filter};
+use crate::guards::{
+    auth::AuthGuard,
+    ratelimit::RateLimitGuard,
+    scope::{IfcRead, IfcWrite, ScopeGuard},
+};
+use crate::models::share::{Role, Share};
+use crate::routes::data::StoredIFC;
+use crate::routes::password::{canonical_login, require_user};
+use crate::routes::team::project_model_role;
+use chrono::Utc;
+use rocket::{
+    State, delete, get,
+    http::Status,
+    put,
+    serde::{Deserialize, json::Json},
+};
+use rocket_governor::RocketGovernor;
+
+/// The role to grant when sharing a model.
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct NewShare {
+    pub role: Role,
+}
+
+/// Returns the record key of a share.
+fn share_key(model: &str, login: &str) -> String {
+    format!("{model}/{login}")
+}
+
+/// Returns the role a user holds on a model, if any: the highest of ownership, a
+/// share of the model and membership of its project.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `id` - The ID of the IFC model.
+/// * `model` - The IFC model.
+/// * `authguard` - The signed-in user; admins hold the owner role on every model.
+pub async fn role_of(
+    database: &Database,
+    id: &str,
+    model: &StoredIFC,
+    authguard: &AuthGuard,
+) -> Option<Role> {
+    if authguard.admin || model.owner.eq_ignore_ascii_case(&authguard.user.login) {
+        return Some(Role::Owner);
+    }
+    let shared = database
+        .read::<Share>("ifc_shares", &share_key(id, &authguard.user.login))
+        .await
+        .ok()
+        .map(|share| share.role);
+    let team = match &model.project {
+        Some(project) => project_model_role(database, project, authguard).await,
+        None => None,
+    };
+    shared.max(team)
+}
+
+/// Returns the listing filter selecting the models a user may view.
+///
+/// # Arguments
+/// * `authguard` - The signed-in user.
+///
+/// # Returns
+/// The filter, or `None` for admins, who may view every model.
+pub fn access_filter(authguard: &AuthGuard) -> Option<Filter> {
+    if authguard.admin {
+        return None;
+    }
+    let login = &authguard.user.login;
+    let member_of = |field| Filter::Listed {
+        field,
+        table: "memberships",
+        column: "scope",
+        login: login.clone(),
+    };
+    Some(Filter::Any(vec![
+        Filter::Equals("owner", login.as_str().into()),
+        Filter::Listed {
+            field: "id",
+            table: "ifc_shares",
+            column: "model",
+            login: login.clone(),
+        },
+        member_of("project"),
+        member_of("project.organization"),
+    ]))
+}
+
+/// Loads a model and checks that the signed-in user holds at least the required role.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `id` - The ID of the IFC model.
+/// * `authguard` - The signed-in user.
+/// * `required` - The least role needed for the operation.
+///
+/// # Returns
+/// The IFC model, 404 Not Found if it does not exist or is in the trash, or 403 Forbidden.
+pub async fn authorize(
+    database: &Database,
+    id: &str,
+    authguard: &AuthGuard,
+    required: Role,
+) -> Result<StoredIFC, Status> {
+    load_authorized(database, id, authguard, required, false).await
+}
+
+/// Loads a model from the trash and checks that the signed-in user holds at least the
+/// required role.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `id` - The ID of the IFC model.
+/// * `authguard` - The signed-in user.
+/// * `required` - The least role needed for the operation.
+///
+/// # Returns
+/// The IFC model, 404 Not Found if it does not exist or is not in the trash, or 403 Forbidden.
+pub async fn authorize_trashed(
+    database: &Database,
+    id: &str,
+    authguard: &AuthGuard,
+    required: Role,
+) -> Result<StoredIFC, Status> {
+    load_authorized(database, id, authguard, required, true).await
+}
+
+/// Loads a model that is in the trash or not, as requested, and checks the user's role.
+async fn load_authorized(
+    database: &Database,
+    id: &str,
+    authguard: &AuthGuard,
+    required: Role,
+    trashed: bool,
+) -> Result<StoredIFC, Status> {
+    let model = database
+        .read::<StoredIFC>("ifc_models", id)
+        .await
+        .map_err(|e| {
+            println!("Error retrieving IFC model {id}: {e:?}");
+            Status::NotFound
+        })?;
+    if model.deleted_at.is_some() != trashed {
+        return Err(Status::NotFound);
+    }
+    match role_of(database, id, &model, authguard).await {
+        Some(role) if role >= required => Ok(model),
+        role => {
+            println!(
+                "{} holds {role:?} on IFC model {id}, {required:?} required",
+                authguard.user.login
+            );
+            Err(Status::Forbidden)
+        }
+    }
+}
+
+/// Removes every share of a model.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `model` - The ID of the IFC model.
+pub async fn delete_shares(database: &Database, model: &str) {
+    if let Err(e) = database
+        .query::<Share>(
+            "DELETE ifc_shares WHERE model = type::thing('ifc_models', $model)",
+            ("model", model.to_string()),
+        )
+        .await
+    {
+        println!("Error deleting shares of IFC model {model}: {e:?}");
+    }
+}
+
+/// List the users an IFC model is shared with.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+///
+/// # Returns
+/// The shares of the model.
+#[get("/ifc/<id>/shares")]
+pub async fn share_list(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+) -> Result<Json<Vec<Share>>, Status> {
+    authorize(database, &id, &authguard, Role::Viewer).await?;
+    database
+        .query(
+            "SELECT * FROM ifc_shares WHERE model = type::thing('ifc_models', $model) ORDER BY login",
+            ("model", id.clone()),
+        )
+        .await
+        .map(Json)
+        .map_err(|e| {
+            println!("Error listing shares of IFC model {id}: {e:?}");
+            Status::InternalServerError
+        })
+}
+
+/// Share an IFC model with a user, or change their role. Only owners may share.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+/// * `login` - Login of the user to share with.
+/// * `share` - The role to grant.
+///
+/// # Returns
+/// The share, 403 Forbidden if the caller does not own the model, or 404 Not Found for
+/// unknown users.
+#[put("/ifc/<id>/shares/<login>", data = "<share>")]
+pub async fn share_grant(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+    login: String,
+    share: Json<NewShare>,
+) -> Result<Json<Share>, Status> {
+    let model = authorize(database, &id, &authguard, Role::Owner).await?;
+    let login = require_user(database, &login).await?.login;
+    if login.eq_ignore_ascii_case(&model.owner) {
+        return Err(Status::Conflict);
+    }
+    let role = share.into_inner().role;
+    println!("Sharing IFC model {id} with {login} as {role:?}");
+    database
+        .upsert(
+            "ifc_shares",
+            &share_key(&id, &login),
+            Share {
+                id: None,
+                model: model.id.ok_or(Status::InternalServerError)?,
+                login,
+                role,
+                granted_by: authguard.user.login.clone(),
+                granted_at: Utc::now(),
+            },
+        )
+        .await
+        .map(Json)
+        .map_err(|e| {
+            println!("Error sharing IFC model {id}: {e:?}");
+            Status::InternalServerError
+        })
+}
+
+/// Stop sharing an IFC model with a user. Owners may revoke anyone; users may leave.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+/// * `login` - Login of the user to revoke.
+///
+/// # Returns
+/// 204 No Content on success.
+#[delete("/ifc/<id>/shares/<login>")]
+pub async fn share_revoke(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+    login: String,
+) -> Status {
+    let login = match canonical_login(database, login).await {
+        Ok(login) => login,
+        Err(status) => return status,
+    };
+    let required = if login == authguard.user.login {
+        Role::Viewer
+    } else {
+        Role::Owner
+    };
+    if let Err(status) = authorize(database, &id, &authguard, required).await {
+        return status;
+    }
+    match database
+        .delete::<Share>("ifc_shares", &share_key(&id, &login))
+        .await
+    {
+        Ok(true) => Status::NoContent,
+        Ok(false) => Status::NotFound,
+        Err(e) => {
+            println!("Error revoking share of IFC model {id}: {e:?}");
+            Status::InternalServerError
+        }
+    }
+}
@@ -0,0 +1,636 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::database::Database;
+use crate::guards::{
+    auth::AuthGuard,
+    ratelimit::RateLimitGuard,
+    scope::{IfcRead, IfcWrite, ScopeGuard},
+};
+use crate::models::share::Role;
+use crate::models::team::{Membership, Organization, Project, TeamRole};
+use crate::routes::password::{canonical_login, require_user};
+use chrono::Utc;
+use rocket::{
+    State, delete, get,
+    http::Status,
+    post, put,
+    serde::{Deserialize, json::Json},
+};
+use rocket_governor::RocketGovernor;
+use serde_json::json;
+use surrealdb::sql::Thing;
+
+/// A new organization.
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct NewOrganization {
+    pub name: String,
+}
+
+/// A new project.
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct NewProject {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+/// The role to give an invited member.
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct NewMember {
+    pub role: TeamRole,
+}
+
+/// Returns the record key of a membership.
+fn membership_key(scope: &Thing, login: &str) -> String {
+    format!("{scope}/{login}")
+}
+
+/// Returns the role of a user within an organization or project, ignoring inheritance.
+async fn member_role(database: &Database, scope: &Thing, login: &str) -> Option<TeamRole> {
+    database
+        .read::<Membership>("memberships", &membership_key(scope, login))
+        .await
+        .ok()
+        .map(|membership| membership.role)
+}
+
+/// Returns the role of the signed-in user within a project, including the role they
+/// hold in its organization. Admins of the application are admins everywhere.
+async fn project_role(
+    database: &Database,
+    project: &Project,
+    authguard: &AuthGuard,
+) -> Option<TeamRole> {
+    if authguard.admin {
+        return Some(TeamRole::Admin);
+    }
+    let login = &authguard.user.login;
+    let direct = match &project.id {
+        Some(id) => member_role(database, id, login).await,
+        None => None,
+    };
+    direct.max(member_role(database, &project.organization, login).await)
+}
+
+/// Returns the access to a model that the signed-in user derives from its project.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `project` - The project the model belongs to.
+/// * `authguard` - The signed-in user.
+pub async fn project_model_role(
+    database: &Database,
+    project: &Thing,
+    authguard: &AuthGuard,
+) -> Option<Role> {
+    let project = database
+        .read::<Project>("projects", &project.id.to_raw())
+        .await
+        .ok()?;
+    project_role(database, &project, authguard)
+        .await
+        .map(TeamRole::model_role)
+}
+
+/// Resolves the project a new model is created in, checking that the signed-in user
+/// may author models there.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `project` - The ID of the project, if any.
+/// * `authguard` - The signed-in user.
+///
+/// # Returns
+/// The project record, 404 Not Found if it does not exist, or 403 Forbidden.
+pub async fn upload_project(
+    database: &Database,
+    project: Option<&str>,
+    authguard: &AuthGuard,
+) -> Result<Option<Thing>, Status> {
+    match project {
+        Some(id) => Ok(require_project(database, id, authguard, TeamRole::Author)
+            .await?
+            .id),
+        None => Ok(None),
+    }
+}
+
+/// Loads an organization and checks that the signed-in user holds at least a role in it.
+async fn require_organization(
+    database: &Database,
+    id: &str,
+    authguard: &AuthGuard,
+    required: TeamRole,
+) -> Result<Organization, Status> {
+    let organization = database
+        .read::<Organization>("organizations", id)
+        .await
+        .map_err(|_| Status::NotFound)?;
+    let scope = organization.id.clone().ok_or(Status::NotFound)?;
+    let role = if authguard.admin {
+        Some(TeamRole::Admin)
+    } else {
+        member_role(database, &scope, &authguard.user.login).await
+    };
+    match role {
+        Some(role) if role >= required => Ok(organization),
+        _ => Err(Status::Forbidden),
+    }
+}
+
+/// Loads a project and checks that the signed-in user holds at least a role in it.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `id` - The ID of the project.
+/// * `authguard` - The signed-in user.
+/// * `required` - The least role needed for the operation.
+///
+/// # Returns
+/// The project, 404 Not Found if it does not exist, or 403 Forbidden.
+pub async fn require_project(
+    database: &Database,
+    id: &str,
+    authguard: &AuthGuard,
+    required: TeamRole,
+) -> Result<Project, Status> {
+    let project = database
+        .read::<Project>("projects", id)
+        .await
+        .map_err(|_| Status::NotFound)?;
+    match project_role(database, &project, authguard).await {
+        Some(role) if role >= required => Ok(project),
+        role => {
+            println!(
+                "{} holds {role:?} in project {id}, {required:?} required",
+                authguard.user.login
+            );
+            Err(Status::Forbidden)
+        }
+    }
+}
+
+/// Lists the members of an organization or project.
+async fn members(database: &Database, scope: &Thing) -> Result<Vec<Membership>, Status> {
+    database
+        .query(
+            "SELECT * FROM memberships WHERE scope = type::thing($table, $id) ORDER BY login",
+            json!({ "table": scope.tb, "id": scope.id.to_raw() }),
+        )
+        .await
+        .map_err(|e| {
+            println!("Error listing members of {scope}: {e:?}");
+            Status::InternalServerError
+        })
+}
+
+/// Returns `true` if `login` is the only admin of an organization or project.
+async fn is_last_admin(database: &Database, scope: &Thing, login: &str) -> Result<bool, Status> {
+    let admins: Vec<Membership> = members(database, scope)
+        .await?
+        .into_iter()
+        .filter(|member| member.role == TeamRole::Admin)
+        .collect();
+    Ok(admins.len() == 1 && admins[0].login.eq_ignore_ascii_case(login))
+}
+
+/// Adds a member to an organization or project, or changes their role.
+async fn save_member(
+    database: &Database,
+    scope: Thing,
+    login: String,
+    role: TeamRole,
+    invited_by: &str,
+) -> Result<Membership, Status> {
+    let key = membership_key(&scope, &login);
+    let joined_at = database
+        .read::<Membership>("memberships", &key)
+        .await
+        .map_or_else(|_| Utc::now(), |existing| existing.joined_at);
+    println!("Adding {login} to {scope} as {role:?}");
+    database
+        .upsert(
+            "memberships",
+            &key,
+            Membership {
+                id: None,
+                scope,
+                login,
+                role,
+                invited_by: invited_by.to_string(),
+                joined_at,
+            },
+        )
+        .await
+        .map_err(|e| {
+            println!("Error saving membership {key}: {e:?}");
+            Status::InternalServerError
+        })
+}
+
+/// Removes a member from an organization or project.
+async fn remove_member(database: &Database, scope: &Thing, login: &str) -> Status {
+    match database
+        .delete::<Membership>("memberships", &membership_key(scope, login))
+        .await
+    {
+        Ok(true) => Status::NoContent,
+        Ok(false) => Status::NotFound,
+        Err(e) => {
+            println!("Error removing {login} from {scope}: {e:?}");
+            Status::InternalServerError
+        }
+    }
+}
+
+/// Create an organization. The creator becomes its first admin.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `organization` - The name of the organization.
+///
+/// # Returns
+/// The created organization.
+#[post("/orgs", data = "<organization>")]
+pub async fn org_create(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    organization: Json<NewOrganization>,
+) -> Result<Json<Organization>, Status> {
+    let name = organization.into_inner().name;
+    if name.trim().is_empty() {
+        return Err(Status::UnprocessableEntity);
+    }
+    let login = authguard.user.login.clone();
+    println!("Creating organization {name} for {login}");
+    let organization: Organization = database
+        .create(
+            "organizations",
+            Organization {
+                id: None,
+                name,
+                created_by: login.clone(),
+                created_at: Utc::now(),
+            },
+        )
+        .await
+        .map_err(|e| {
+            println!("Error creating organization: {e:?}");
+            Status::InternalServerError
+        })?;
+    let scope = organization.id.clone().ok_or(Status::InternalServerError)?;
+    save_member(database, scope, login.clone(), TeamRole::Admin, &login).await?;
+    Ok(Json(organization))
+}
+
+/// List the organizations the signed-in user is a member of.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+///
+/// # Returns
+/// The organizations, ordered by name.
+#[get("/orgs")]
+pub async fn org_list(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+) -> Result<Json<Vec<Organization>>, Status> {
+    database
+        .query(
+            "SELECT * FROM organizations WHERE $admin OR id IN \
+             (SELECT VALUE scope FROM memberships WHERE login = $login) ORDER BY name",
+            json!({ "admin": authguard.admin, "login": authguard.user.login }),
+        )
+        .await
+        .map(Json)
+        .map_err(|e| {
+            println!("Error listing organizations: {e:?}");
+            Status::InternalServerError
+        })
+}
+
+/// Get an organization.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the organization.
+///
+/// # Returns
+/// The organization, or 403 Forbidden for non-members.
+#[get("/orgs/<id>")]
+pub async fn org_get(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+) -> Result<Json<Organization>, Status> {
+    require_organization(database, &id, &authguard, TeamRole::Viewer)
+        .await
+        .map(Json)
+}
+
+/// List the members of an organization.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the organization.
+///
+/// # Returns
+/// The memberships, ordered by login.
+#[get("/orgs/<id>/members")]
+pub async fn org_members(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+) -> Result<Json<Vec<Membership>>, Status> {
+    let organization = require_organization(database, &id, &authguard, TeamRole::Viewer).await?;
+    let scope = organization.id.ok_or(Status::NotFound)?;
+    members(database, &scope).await.map(Json)
+}
+
+/// Invite a user to an organization, or change their role. Only admins may invite.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the organization.
+/// * `login` - Login of the user to invite.
+/// * `member` - The role to give the member.
+///
+/// # Returns
+/// The membership, 404 Not Found for unknown users, or 409 Conflict when demoting the
+/// last admin.
+#[put("/orgs/<id>/members/<login>", data = "<member>")]
+pub async fn org_invite(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+    login: String,
+    member: Json<NewMember>,
+) -> Result<Json<Membership>, Status> {
+    let organization = require_organization(database, &id, &authguard, TeamRole::Admin).await?;
+    let scope = organization.id.ok_or(Status::NotFound)?;
+    let login = require_user(database, &login).await?.login;
+    let role = member.into_inner().role;
+    if role != TeamRole::Admin && is_last_admin(database, &scope, &login).await? {
+        return Err(Status::Conflict);
+    }
+    save_member(database, scope, login, role, &authguard.user.login)
+        .await
+        .map(Json)
+}
+
+/// Remove a member from an organization. Admins may remove anyone; members may leave.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the organization.
+/// * `login` - Login of the member to remove.
+///
+/// # Returns
+/// 204 No Content on success, or 409 Conflict when removing the last admin.
+#[delete("/orgs/<id>/members/<login>")]
+pub async fn org_remove(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+    login: String,
+) -> Status {
+    let login = match canonical_login(database, login).await {
+        Ok(login) => login,
+        Err(status) => return status,
+    };
+    let required = if login == authguard.user.login {
+        TeamRole::Viewer
+    } else {
+        TeamRole::Admin
+    };
+    let scope = match require_organization(database, &id, &authguard, required).await {
+        Ok(organization) => match organization.id {
+            Some(scope) => scope,
+            None => return Status::NotFound,
+        },
+        Err(status) => return status,
+    };
+    match is_last_admin(database, &scope, &login).await {
+        Ok(false) => remove_member(database, &scope, &login).await,
+        Ok(true) => Status::Conflict,
+        Err(status) => status,
+    }
+}
+
+/// Create a project in an organization. Coordinators and admins may create projects.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the organization.
+/// * `project` - The name and description of the project.
+///
+/// # Returns
+/// The created project.
+#[post("/orgs/<id>/projects", data = "<project>")]
+pub async fn project_create(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+    project: Json<NewProject>,
+) -> Result<Json<Project>, Status> {
+    let organization =
+        require_organization(database, &id, &authguard, TeamRole::Coordinator).await?;
+    let project = project.into_inner();
+    if project.name.trim().is_empty() {
+        return Err(Status::UnprocessableEntity);
+    }
+    println!("Creating project {} in organization {id}", project.name);
+    database
+        .create(
+            "projects",
+            Project {
+                id: None,
+                organization: organization.id.ok_or(Status::NotFound)?,
+                name: project.name,
+                description: project.description,
+                created_by: authguard.user.login.clone(),
+                created_at: Utc::now(),
+            },
+        )
+        .await
+        .map(Json)
+        .map_err(|e| {
+            println!("Error creating project: {e:?}");
+            Status::InternalServerError
+        })
+}
+
+/// List the projects of an organization.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the organization.
+///
+/// # Returns
+/// The projects, ordered by name.
+#[get("/orgs/<id>/projects")]
+pub async fn project_list(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+) -> Result<Json<Vec<Project>>, Status> {
+    require_organization(database, &id, &authguard, TeamRole::Viewer).await?;
+    database
+        .query(
+            "SELECT * FROM projects WHERE organization = type::thing('organizations', $id) ORDER BY name",
+            ("id", id.clone()),
+        )
+        .await
+        .map(Json)
+        .map_err(|e| {
+            println!("Error listing projects of organization {id}: {e:?}");
+            Status::InternalServerError
+        })
+}
+
+/// Get a project.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the project.
+///
+/// # Returns
+/// The project, or 403 Forbidden for non-members.
+#[get("/projects/<id>")]
+pub async fn project_get(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+) -> Result<Json<Project>, Status> {
+    require_project(database, &id, &authguard, TeamRole::Viewer)
+        .await
+        .map(Json)
+}
+
+/// List the direct members of a project. Members of its organization are not repeated.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the project.
+///
+/// # Returns
+/// The memberships, ordered by login.
+#[get("/projects/<id>/members")]
+pub async fn project_members(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+) -> Result<Json<Vec<Membership>>, Status> {
+    let project = require_project(database, &id, &authguard, TeamRole::Viewer).await?;
+    let scope = project.id.ok_or(Status::NotFound)?;
+    members(database, &scope).await.map(Json)
+}
+
+/// Invite a user to a project, or change their role. Only admins may invite.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the project.
+/// * `login` - Login of the user to invite.
+/// * `member` - The role to give the member.
+///
+/// # Returns
+/// The membership, 404 Not Found for unknown users, or 409 Conflict when demoting the
+/// last admin.
+#[put("/projects/<id>/members/<login>", data = "<member>")]
+pub async fn project_invite(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+    login: String,
+    member: Json<NewMember>,
+) -> Result<Json<Membership>, Status> {
+    let project = require_project(database, &id, &authguard, TeamRole::Admin).await?;
+    let scope = project.id.ok_or(Status::NotFound)?;
+    let login = require_user(database, &login).await?.login;
+    let role = member.into_inner().role;
+    if role != TeamRole::Admin && is_last_admin(database, &scope, &login).await? {
+        return Err(Status::Conflict);
+    }
+    save_member(database, scope, login, role, &authguard.user.login)
+        .await
+        .map(Json)
+}
+
+/// Remove a member from a project. Admins may remove anyone; members may leave.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the project.
+/// * `login` - Login of the member to remove.
+///
+/// # Returns
+/// 204 No Content on success, or 409 Conflict when removing the last admin.
+#[delete("/projects/<id>/members/<login>")]
+pub async fn project_remove(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+    login: String,
+) -> Status {
+    let login = match canonical_login(database, login).await {
+        Ok(login) => login,
+        Err(status) => return status,
+    };
+    let required = if login == authguard.user.login {
+        TeamRole::Viewer
+    } else {
+        TeamRole::Admin
+    };
+    let scope = match require_project(database, &id, &authguard, required).await {
+        Ok(Project {
+            id: Some(scope), ..
+        }) => scope,
+        Ok(_) => return Status::NotFound,
+        Err(status) => return status,
+    };
+    match is_last_admin(database, &scope, &login).await {
+        Ok(false) => remove_member(database, &scope, &login).await,
+        Ok(true) => Status::Conflict,
+        Err(status) => status,
+    }
+}
@@ -0,0 +1,174 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::database::Database;
+use crate::guards::{
+    ratelimit::RateLimitGuard,
+    scope::{IfcRead, ScopeGuard},
+};
+use crate::ifc::{geometry, render, step::StepFile};
+use crate::models::blob::BlobRef;
+use crate::models::card::Card;
+use crate::models::share::Role;
+use crate::models::thumbnail::Thumbnail;
+use crate::routes::share::authorize;
+use crate::storage::blob::BlobStore;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::Utc;
+use rocket::{
+    State, get,
+    http::{ContentType, Status},
+    tokio::{self, task::spawn_blocking},
+};
+use rocket_governor::RocketGovernor;
+use serde_json::json;
+use std::io::{BufRead, BufReader};
+use surrealdb::sql::Thing;
+use tokio_util::io::SyncIoBridge;
+
+/// Renders a PNG thumbnail from the STEP text of an IFC model.
+///
+/// # Arguments
+/// * `reader` - The source of the IFC file content.
+///
+/// # Returns
+/// A `Result` containing the PNG bytes or a description of the failure.
+pub fn render_png<R: BufRead>(reader: R) -> Result<Vec<u8>, String> {
+    let file = StepFile::read(reader).map_err(|e| e.to_string())?;
+    let elements = geometry::extract(&file);
+    render::isometric(&elements, render::THUMBNAIL_SIZE)
+        .to_png()
+        .map_err(|e| e.to_string())
+}
+
+/// Renders and stores the thumbnail of a model, replacing any previous one.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `blobs` - The blob store.
+/// * `model` - The ID of the model the thumbnail belongs to.
+/// * `file` - The model file, read from the blob store while rendering.
+///
+/// # Returns
+/// The ID of the stored thumbnail record, or `None` if rendering or storing failed.
+pub async fn store_thumbnail(
+    database: &Database,
+    blobs: &BlobStore,
+    model: &Thing,
+    file: &BlobRef,
+) -> Option<Thing> {
+    let reader = match blobs.open(file).await {
+        Ok(reader) => SyncIoBridge::new(reader),
+        Err(e) => {
+            println!("Error opening IFC file {} for {model}: {e}", file.sha256);
+            return None;
+        }
+    };
+    let png = match spawn_blocking(move || render_png(BufReader::new(reader))).await {
+        Ok(Ok(png)) => png,
+        Ok(Err(e)) => {
+            println!("Error rendering thumbnail for {model}: {e}");
+            return None;
+        }
+        Err(e) => {
+            println!("Thumbnail renderer for {model} panicked: {e:?}");
+            return None;
+        }
+    };
+    let thumbnail = Thumbnail {
+        id: None,
+        model: model.clone(),
+        data: STANDARD.encode(png),
+        rendered_at: Utc::now(),
+    };
+    match database
+        .upsert("thumbnails", &model.id.to_raw(), thumbnail)
+        .await
+    {
+        Ok(saved) => {
+            println!("Successfully rendered thumbnail for {model}");
+            saved.id
+        }
+        Err(e) => {
+            println!("Error saving thumbnail for {model}: {e:?}");
+            None
+        }
+    }
+}
+
+/// Renders the thumbnail of a model in the background and links it from the cards that
+/// present the model, so that saving the model does not wait for the renderer.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `blobs` - The blob store.
+/// * `model` - The ID of the model the thumbnail belongs to.
+/// * `file` - The model file, read from the blob store while rendering.
+pub fn spawn_thumbnail(database: &Database, blobs: &BlobStore, model: &Thing, file: &BlobRef) {
+    let (database, blobs, model, file) =
+        (database.clone(), blobs.clone(), model.clone(), file.clone());
+    tokio::spawn(async move {
+        let Some(thumbnail) = store_thumbnail(&database, &blobs, &model, &file).await else {
+            return;
+        };
+        if let Err(e) = database
+            .query::<Card>(
+                "UPDATE cards SET thumbnail = type::thing('thumbnails', $thumbnail) \
+                 WHERE model = type::thing('ifc_models', $model)",
+                json!({
+                    "thumbnail": thumbnail.id.to_raw(),
+                    "model": model.id.to_raw(),
+                }),
+            )
+            .await
+        {
+            println!("Error linking thumbnail of {model} to its cards: {e:?}");
+        }
+    });
+}
+
+/// Get the rendered thumbnail of an IFC model.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+///
+/// # Returns
+/// The thumbnail as a PNG image.
+#[get("/ifc/<id>/thumbnail.png")]
+pub async fn thumbnail_get(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+) -> Result<(ContentType, Vec<u8>), Status> {
+    authorize(database, &id, &authguard, Role::Viewer).await?;
+    load_thumbnail(database, &id).await
+}
+
+/// Loads the rendered thumbnail of a model.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `id` - The ID of the IFC model.
+///
+/// # Returns
+/// The PNG image, or 404 Not Found if none was rendered.
+pub async fn load_thumbnail(
+    database: &Database,
+    id: &str,
+) -> Result<(ContentType, Vec<u8>), Status> {
+    let thumbnail = database
+        .read::<Thumbnail>("thumbnails", id)
+        .await
+        .map_err(|e| {
+            println!("Error retrieving thumbnail {id}: {e:?}");
+            Status::NotFound
+        })?;
+    STANDARD
+        .decode(thumbnail.data)
+        .map(|png| (ContentType::PNG, png))
+        .map_err(|_| Status::InternalServerError)
+}
@@ -0,0 +1,215 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::config::Config;
+use crate::database::Database;
+use crate::guards::{
+    auth::AuthGuard,
+    ratelimit::RateLimitGuard,
+    scope::{IfcRead, IfcWrite, ScopeGuard},
+};
+use crate::models::token::{AccessToken, Scope};
+use crate::utils::Utils;
+use chrono::{DateTime, Duration, Utc};
+use rocket::{
+    State, delete, get,
+    http::Status,
+    post,
+    serde::{Deserialize, Serialize, json::Json},
+};
+use rocket_governor::RocketGovernor;
+use serde_json::json;
+use surrealdb::sql::Thing;
+
+/// Lifetime of an access token when none is requested, in days.
+const DEFAULT_TOKEN_DAYS: u64 = 90;
+/// Longest lifetime of an access token, in days.
+const MAX_TOKEN_DAYS: u64 = 365;
+
+/// A personal access token to create.
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct NewToken {
+    /// What the token is for, e.g. the name of a CI pipeline.
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    /// Lifetime of the token in days, up to a year.
+    pub days: Option<u64>,
+}
+
+/// A personal access token as shown to its user.
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct TokenResponse {
+    /// The record key of the token.
+    pub id: String,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// The token itself; only returned when it is created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl From<AccessToken> for TokenResponse {
+    fn from(token: AccessToken) -> Self {
+        TokenResponse {
+            id: token.id.map(|id| id.id.to_raw()).unwrap_or_default(),
+            name: token.name,
+            scopes: token.scopes,
+            created_at: token.created_at,
+            expires_at: token.expires_at,
+            last_used_at: token.last_used_at,
+            token: None,
+        }
+    }
+}
+
+/// Returns the record of the signed-in user.
+fn user_record(authguard: &AuthGuard) -> Result<&Thing, Status> {
+    authguard
+        .user
+        .id
+        .as_ref()
+        .ok_or(Status::InternalServerError)
+}
+
+/// List the personal access tokens of the signed-in user.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+///
+/// # Returns
+/// The tokens, without their secrets, or 403 Forbidden when called with an access token.
+#[get("/auth/tokens")]
+pub async fn token_list(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+) -> Result<Json<Vec<TokenResponse>>, Status> {
+    authguard.session().ok_or(Status::Forbidden)?;
+    database
+        .query::<AccessToken>(
+            "SELECT * FROM access_tokens WHERE user = type::thing('users', $user) \
+             ORDER BY created_at DESC",
+            ("user", user_record(&authguard)?.id.to_raw()),
+        )
+        .await
+        .map(|tokens| Json(tokens.into_iter().map(TokenResponse::from).collect()))
+        .map_err(|e| {
+            println!("Error listing tokens of {}: {e:?}", authguard.user.login);
+            Status::InternalServerError
+        })
+}
+
+/// Create a personal access token. Tokens can only be created from a signed-in session.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `config` - The application configuration.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `token` - Name, scopes and lifetime of the token.
+///
+/// # Returns
+/// The token including its secret, which is not shown again, 400 Bad Request for a
+/// token without name or scopes, or 403 Forbidden when called with an access token.
+#[post("/auth/tokens", data = "<token>")]
+pub async fn token_create(
+    database: &State<Database>,
+    config: &State<Config>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    token: Json<NewToken>,
+) -> Result<Json<TokenResponse>, Status> {
+    if authguard.session().is_none() {
+        return Err(Status::Forbidden);
+    }
+    let token = token.into_inner();
+    if token.name.trim().is_empty() || token.scopes.is_empty() {
+        return Err(Status::BadRequest);
+    }
+    let days = token
+        .days
+        .unwrap_or(DEFAULT_TOKEN_DAYS)
+        .clamp(1, MAX_TOKEN_DAYS);
+    let key = Utils::random_token(8);
+    let secret = Utils::random_token(32);
+    let now = Utc::now();
+    println!(
+        "Creating access token {key} for {} with scopes {:?}",
+        authguard.user.login, token.scopes
+    );
+    let stored = database
+        .create(
+            "access_tokens",
+            AccessToken {
+                id: Some(Thing::from(("access_tokens", key.as_str()))),
+                user: user_record(&authguard)?.clone(),
+                name: token.name,
+                scopes: token.scopes,
+                secret_hash: Utils::sign(&config.secret_key, &secret),
+                created_at: now,
+                expires_at: now + Duration::days(days as i64),
+                last_used_at: None,
+            },
+        )
+        .await
+        .map_err(|e| {
+            println!("Error creating access token: {e:?}");
+            Status::InternalServerError
+        })?;
+    Ok(Json(TokenResponse {
+        token: Some(format!("{}{key}_{secret}", AccessToken::PREFIX)),
+        ..stored.into()
+    }))
+}
+
+/// Revoke one of the signed-in user's personal access tokens.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The record key of the token.
+///
+/// # Returns
+/// 204 No Content on success, 404 Not Found if the user holds no such token, or 403
+/// Forbidden when called with an access token.
+#[delete("/auth/tokens/<id>")]
+pub async fn token_revoke(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+) -> Status {
+    if authguard.session().is_none() {
+        return Status::Forbidden;
+    }
+    let user = match user_record(&authguard) {
+        Ok(user) => user.id.to_raw(),
+        Err(status) => return status,
+    };
+    match database
+        .query::<AccessToken>(
+            "DELETE type::thing('access_tokens', $token) \
+             WHERE user = type::thing('users', $user) RETURN BEFORE",
+            json!({ "token": id, "user": user }),
+        )
+        .await
+    {
+        Ok(revoked) if revoked.is_empty() => Status::NotFound,
+        Ok(_) => {
+            println!("{} revoked access token {id}", authguard.user.login);
+            Status::NoContent
+        }
+        Err(e) => {
+            println!("Error revoking access token {id}: {e:?}");
+            Status::InternalServerError
+        }
+    }
+}
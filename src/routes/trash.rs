@@ -0,0 +1,162 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::config::Config;
+use crate::database::{Database, Filter, Listing};
+use crate::guards::{
+    ratelimit::RateLimitGuard,
+    scope::{IfcRead, IfcWrite, ScopeGuard},
+};
+use crate::models::share::Role;
+use crate::routes::data::{
+    DEFAULT_PAGE_SIZE, IFCResponse, MAX_PAGE_SIZE, ModelPage, StoredIFC, decode_cursor,
+    encode_cursor, purge_model,
+};
+use crate::routes::share::{access_filter, authorize_trashed};
+use crate::storage::blob::BlobStore;
+use chrono::Utc;
+use rocket::{State, delete, get, http::Status, post, serde::json::Json};
+use rocket_governor::RocketGovernor;
+
+/// List the models in the trash, most recently deleted first.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:read` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `cursor` - Cursor of the page, from `next_cursor` of the previous page.
+/// * `limit` - Number of models per page.
+///
+/// # Returns
+/// A page of trashed models the caller may view, or 400 Bad Request for an invalid cursor.
+#[get("/trash?<cursor>&<limit>")]
+pub async fn trash_list(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcRead>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    cursor: Option<String>,
+    limit: Option<usize>,
+) -> Result<Json<ModelPage>, Status> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let after = match &cursor {
+        Some(cursor) => Some(decode_cursor(cursor).ok_or(Status::BadRequest)?),
+        None => None,
+    };
+    let mut filters = vec![Filter::Set("deleted_at")];
+    filters.extend(access_filter(&authguard));
+    let listing = Listing {
+        filters,
+        sort: "deleted_at",
+        descending: true,
+        after,
+        // One extra record tells whether another page follows.
+        limit: limit + 1,
+    };
+    let mut items = database
+        .list::<StoredIFC>("ifc_models", &listing)
+        .await
+        .map_err(|e| {
+            println!("Error listing trashed IFC models: {e:?}");
+            Status::InternalServerError
+        })?;
+    let next_cursor = if items.len() > limit {
+        items.truncate(limit);
+        items
+            .last()
+            .and_then(|model| Some(encode_cursor(model.deleted_at?, model.id.as_ref()?)))
+    } else {
+        None
+    };
+    Ok(Json(ModelPage {
+        items: items.into_iter().map(IFCResponse::from).collect(),
+        next_cursor,
+    }))
+}
+
+/// Restore a model from the trash. Only owners may restore.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+///
+/// # Returns
+/// The restored model, or 404 Not Found if it is not in the trash.
+#[post("/trash/<id>/restore")]
+pub async fn trash_restore(
+    database: &State<Database>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+) -> Result<Json<IFCResponse>, Status> {
+    let mut model = authorize_trashed(database, &id, &authguard, Role::Owner).await?;
+    println!("Restoring IFC model {id} from the trash");
+    model.deleted_at = None;
+    model.deleted_by = None;
+    database
+        .update::<StoredIFC>("ifc_models", &id, model)
+        .await
+        .map(|model| Json(model.into()))
+        .map_err(|e| {
+            println!("Error restoring IFC model {id}: {e:?}");
+            Status::InternalServerError
+        })
+}
+
+/// Permanently delete a model from the trash. Only owners may purge.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `blobs` - The blob store.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `id` - The ID of the IFC model.
+///
+/// # Returns
+/// 204 No Content on success, or 404 Not Found if the model is not in the trash.
+#[delete("/trash/<id>")]
+pub async fn trash_purge(
+    database: &State<Database>,
+    blobs: &State<BlobStore>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    id: String,
+) -> Status {
+    if let Err(status) = authorize_trashed(database, &id, &authguard, Role::Owner).await {
+        return status;
+    }
+    purge_model(database, blobs, &id).await
+}
+
+/// Permanently deletes every model that has been in the trash longer than the
+/// configured retention.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `blobs` - The blob store.
+/// * `config` - The application configuration.
+///
+/// # Returns
+/// The number of purged models.
+pub async fn purge_expired_trash(database: &Database, blobs: &BlobStore, config: &Config) -> usize {
+    let cutoff = Utc::now() - config.trash_retention();
+    let expired = database
+        .query::<StoredIFC>(
+            "SELECT * FROM ifc_models \
+             WHERE deleted_at IS NOT NONE AND <datetime> deleted_at <= $cutoff",
+            ("cutoff", surrealdb::sql::Datetime::from(cutoff)),
+        )
+        .await
+        .unwrap_or_else(|e| {
+            println!("Error listing expired trash: {e:?}");
+            Vec::new()
+        });
+    let mut purged = 0;
+    for id in expired.into_iter().filter_map(|model| model.id) {
+        if purge_model(database, blobs, &id.id.to_raw()).await == Status::NoContent {
+            purged += 1;
+        }
+    }
+    purged
+}
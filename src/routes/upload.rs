@@ -0,0 +1,248 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::config::Config;
+use crate::database::Database;
+use crate::guards::{
+    auth::AuthGuard,
+    ratelimit::RateLimitGuard,
+    scope::{IfcWrite, ScopeGuard},
+};
+use crate::ifc::format::{IngestError, extracted_path, ingest};
+use crate::routes::data::{IFCRequest, IFCResponse, StoredIFC, create_model, release_file};
+use crate::routes::team::upload_project;
+use crate::storage::blob::BlobStore;
+use rocket::{
+    Data, FromForm, State,
+    form::Form,
+    fs::TempFile,
+    http::Status,
+    post,
+    serde::json::Json,
+    tokio::{fs, task::spawn_blocking},
+};
+use rocket_governor::RocketGovernor;
+use std::io;
+use std::path::{Path, PathBuf};
+use surrealdb::sql::{Thing, Uuid};
+
+/// A model file uploaded as `multipart/form-data`.
+#[derive(FromForm)]
+pub struct UploadForm<'r> {
+    pub file: TempFile<'r>,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    /// The ID of the project to create the model in.
+    pub project: Option<String>,
+}
+
+/// Optional model fields supplied alongside an upload.
+#[derive(FromForm)]
+pub struct UploadFields {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    /// The ID of the project to create the model in.
+    pub project: Option<String>,
+}
+
+/// Returns a unique path in the system temporary directory for a spooled upload.
+fn spool_path() -> PathBuf {
+    std::env::temp_dir().join(format!("xbim-upload-{}", Uuid::new()))
+}
+
+/// Removes a spooled upload along with the model extracted from it, if any.
+async fn discard_upload(path: &Path) {
+    for path in [path.to_path_buf(), extracted_path(path)] {
+        match fs::remove_file(&path).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => println!("Error removing spooled upload {}: {e}", path.display()),
+        }
+    }
+}
+
+/// Detects, decompresses and indexes a spooled upload, then stores it as a new model.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `blobs` - The blob store.
+/// * `path` - The path of the spooled upload; it is removed afterwards.
+/// * `limit` - The maximum size of the decompressed model in bytes.
+/// * `filename` - The client-supplied file name, if any.
+/// * `fields` - The model name, version and description.
+/// * `project` - The project to create the model in, checked before the upload was spooled.
+/// * `authguard` - The uploading user, who becomes the owner.
+///
+/// # Returns
+/// The saved IFC model with its ID.
+#[allow(clippy::too_many_arguments)]
+pub async fn store_upload(
+    database: &Database,
+    blobs: &BlobStore,
+    path: PathBuf,
+    limit: u64,
+    filename: Option<String>,
+    fields: UploadFields,
+    project: Option<Thing>,
+    authguard: &AuthGuard,
+) -> Result<Json<IFCResponse>, Status> {
+    let spooled = path.clone();
+    let mut ingested = match spawn_blocking(move || ingest(&spooled, limit)).await {
+        Ok(Ok(ingested)) => ingested,
+        Ok(Err(e)) => {
+            discard_upload(&path).await;
+            println!("Error ingesting IFC upload: {e}");
+            return Err(match e {
+                IngestError::Unsupported => Status::UnsupportedMediaType,
+                IngestError::TooLarge => Status::PayloadTooLarge,
+                IngestError::Io(_) => Status::UnprocessableEntity,
+            });
+        }
+        Err(e) => {
+            discard_upload(&path).await;
+            println!("IFC upload ingest panicked: {e:?}");
+            return Err(Status::InternalServerError);
+        }
+    };
+    println!("Detected {} upload", ingested.format.as_str());
+    let stored = blobs.put_file(&ingested.path).await;
+    discard_upload(&path).await;
+    let blob = stored.map_err(|e| {
+        println!("Error storing IFC file: {e}");
+        Status::InternalServerError
+    })?;
+    println!("Stored IFC file {} ({} bytes)", blob.sha256, blob.size);
+
+    if let Some(filename) = &filename {
+        ingested
+            .metadata
+            .insert(String::from("ifc.filename"), filename.clone());
+    }
+    let mut model = StoredIFC::new(IFCRequest {
+        name: fields
+            .name
+            .or(filename)
+            .or_else(|| ingested.metadata.get("ifc.project").cloned())
+            .unwrap_or_else(|| String::from("Untitled")),
+        version: fields.version.unwrap_or_else(|| String::from("1")),
+        description: fields.description,
+        metadata: ingested.metadata,
+        project: None,
+        file_content: None,
+    });
+    model.project = project;
+    model.file = Some(blob.clone());
+    match create_model(database, blobs, model, &authguard.user.login).await {
+        Ok(model) => Ok(Json(model.into())),
+        Err(status) => {
+            release_file(database, blobs, &blob).await;
+            Err(status)
+        }
+    }
+}
+
+/// Upload a raw `.ifc`, `.ifczip` or `.ifcxml` file as `multipart/form-data`.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `blobs` - The blob store.
+/// * `config` - The application configuration.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `form` - The form containing the `file` and optional model fields.
+///
+/// # Returns
+/// The saved IFC model with its ID.
+#[post("/ifc/upload", format = "multipart/form-data", data = "<form>")]
+pub async fn upload_multipart(
+    database: &State<Database>,
+    blobs: &State<BlobStore>,
+    config: &State<Config>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    mut form: Form<UploadForm<'_>>,
+) -> Result<Json<IFCResponse>, Status> {
+    println!("Processing multipart IFC upload");
+    let project = upload_project(database, form.project.as_deref(), &authguard).await?;
+    let path = spool_path();
+    if let Err(e) = form.file.copy_to(&path).await {
+        discard_upload(&path).await;
+        println!("Error spooling IFC upload: {e}");
+        return Err(Status::InternalServerError);
+    }
+    let filename = form.file.name().map(str::to_string);
+    let form = form.into_inner();
+    store_upload(
+        database,
+        blobs,
+        path,
+        config.max_upload_size().as_u64(),
+        filename,
+        UploadFields {
+            name: form.name,
+            version: form.version,
+            description: form.description,
+            project: form.project,
+        },
+        project,
+        &authguard,
+    )
+    .await
+}
+
+/// Upload a raw `.ifc`, `.ifczip` or `.ifcxml` file as `application/octet-stream`.
+///
+/// # Arguments
+/// * `database` - The database instance.
+/// * `blobs` - The blob store.
+/// * `config` - The application configuration.
+/// * `authguard` - Authentication Guard requiring the `ifc:write` scope.
+/// * `_ratelimitguard` - Rate Limit Guard.
+/// * `fields` - The optional model name, version, description and project.
+/// * `data` - The file contents.
+///
+/// # Returns
+/// The saved IFC model with its ID.
+#[post(
+    "/ifc/upload?<fields..>",
+    format = "application/octet-stream",
+    data = "<data>"
+)]
+pub async fn upload_raw(
+    database: &State<Database>,
+    blobs: &State<BlobStore>,
+    config: &State<Config>,
+    authguard: ScopeGuard<IfcWrite>,
+    _ratelimitguard: RocketGovernor<'_, RateLimitGuard>,
+    fields: UploadFields,
+    data: Data<'_>,
+) -> Result<Json<IFCResponse>, Status> {
+    println!("Processing raw IFC upload");
+    let project = upload_project(database, fields.project.as_deref(), &authguard).await?;
+    let path = spool_path();
+    let written = match data.open(config.max_upload_size()).into_file(&path).await {
+        Ok(written) => written,
+        Err(e) => {
+            discard_upload(&path).await;
+            println!("Error spooling IFC upload: {e}");
+            return Err(Status::InternalServerError);
+        }
+    };
+    if !written.is_complete() {
+        discard_upload(&path).await;
+        return Err(Status::PayloadTooLarge);
+    }
+    store_upload(
+        database,
+        blobs,
+        path,
+        config.max_upload_size().as_u64(),
+        None,
+        fields,
+        project,
+        &authguard,
+    )
+    .await
+}
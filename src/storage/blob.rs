@@ -0,0 +1,296 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::config::Config;
+use crate::models::blob::BlobRef;
+use crate::storage::{fs::FsBackend, s3::S3Backend};
+use rocket::async_trait;
+use rocket::tokio::fs;
+use rocket::tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+use sha2::{Digest, Sha256};
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, ready};
+
+/// The contents of a blob, read as a stream.
+pub type BlobReader = Pin<Box<dyn AsyncRead + Send>>;
+
+/// A key-value store for immutable file contents.
+#[async_trait]
+pub trait Backend: Send + Sync {
+    /// Stores `data` under `key`, replacing any existing contents.
+    async fn put(&self, key: &str, data: &[u8]) -> io::Result<()>;
+
+    /// Stores the file at `path` under `key` without reading it into memory.
+    /// `sha256` is the hex digest of the file, for backends that sign the contents.
+    async fn put_file(&self, key: &str, path: &Path, sha256: &str) -> io::Result<()>;
+
+    /// Returns the contents stored under `key`, or `NotFound`.
+    async fn get(&self, key: &str) -> io::Result<Vec<u8>>;
+
+    /// Opens the contents stored under `key` for reading as a stream, or `NotFound`.
+    async fn open(&self, key: &str) -> io::Result<BlobReader>;
+
+    /// Returns `true` if contents are stored under `key`.
+    async fn exists(&self, key: &str) -> io::Result<bool>;
+
+    /// Removes the contents stored under `key`; missing keys are not an error.
+    async fn delete(&self, key: &str) -> io::Result<()>;
+}
+
+/// Content-addressable storage for model files, keyed and deduplicated by SHA-256.
+#[derive(Clone)]
+pub struct BlobStore {
+    backend: Arc<dyn Backend>,
+}
+
+impl BlobStore {
+    /// Creates a blob store on top of the given backend.
+    ///
+    /// # Arguments
+    /// * `backend` - The backend that holds the contents.
+    ///
+    /// # Returns
+    /// A new `BlobStore` instance.
+    pub fn new(backend: impl Backend + 'static) -> Self {
+        Self {
+            backend: Arc::new(backend),
+        }
+    }
+
+    /// Creates the blob store selected by `blob_backend` in the configuration.
+    ///
+    /// # Arguments
+    /// * `config` - The application configuration.
+    ///
+    /// # Returns
+    /// An S3 backed store for `"s3"`, otherwise a local filesystem store.
+    pub fn from_config(config: &Config) -> Self {
+        match config.blob_backend.as_str() {
+            "s3" => Self::new(S3Backend::from_config(config)),
+            _ => Self::new(FsBackend::new(config.blob_dir())),
+        }
+    }
+
+    /// Returns the storage key for a SHA-256 digest, fanned out by its first byte.
+    ///
+    /// # Arguments
+    /// * `sha256` - Lowercase hex SHA-256 digest.
+    ///
+    /// # Returns
+    /// The key, e.g. `sha256/ab/abcdef…`.
+    pub fn key(sha256: &str) -> String {
+        format!("sha256/{}/{sha256}", &sha256[..2])
+    }
+
+    /// Stores file contents unless identical contents are already present.
+    ///
+    /// # Arguments
+    /// * `data` - The file contents.
+    ///
+    /// # Returns
+    /// A `Result` containing the reference to the stored blob.
+    pub async fn put(&self, data: &[u8]) -> io::Result<BlobRef> {
+        let sha256 = hex::encode(Sha256::digest(data));
+        let blob = BlobRef {
+            key: Self::key(&sha256),
+            sha256,
+            size: data.len() as u64,
+        };
+        if !self.backend.exists(&blob.key).await? {
+            self.backend.put(&blob.key, data).await?;
+        }
+        Ok(blob)
+    }
+
+    /// Stores the contents of a file unless identical contents are already present,
+    /// reading it in chunks rather than into memory.
+    ///
+    /// # Arguments
+    /// * `path` - The path of the file.
+    ///
+    /// # Returns
+    /// A `Result` containing the reference to the stored blob.
+    pub async fn put_file(&self, path: &Path) -> io::Result<BlobRef> {
+        let mut file = fs::File::open(path).await?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0; 64 * 1024];
+        let mut size = 0;
+        loop {
+            let read = file.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            size += read as u64;
+        }
+        let sha256 = hex::encode(hasher.finalize());
+        let blob = BlobRef {
+            key: Self::key(&sha256),
+            sha256,
+            size,
+        };
+        if !self.backend.exists(&blob.key).await? {
+            self.backend.put_file(&blob.key, path, &blob.sha256).await?;
+        }
+        Ok(blob)
+    }
+
+    /// Reads and verifies the contents of a blob.
+    ///
+    /// # Arguments
+    /// * `blob` - The reference to the blob.
+    ///
+    /// # Returns
+    /// A `Result` containing the contents, or `InvalidData` if they fail the hash check.
+    pub async fn get(&self, blob: &BlobRef) -> io::Result<Vec<u8>> {
+        let data = self.backend.get(&blob.key).await?;
+        if hex::encode(Sha256::digest(&data)) != blob.sha256 {
+            return Err(corrupt(blob));
+        }
+        Ok(data)
+    }
+
+    /// Opens a blob for reading without loading it into memory. The contents are
+    /// verified while they are read: the stream ends with `InvalidData` instead of
+    /// end-of-file if they fail the hash check.
+    ///
+    /// # Arguments
+    /// * `blob` - The reference to the blob.
+    ///
+    /// # Returns
+    /// A `Result` containing the stream of contents.
+    pub async fn open(&self, blob: &BlobRef) -> io::Result<BlobReader> {
+        Ok(Box::pin(Verified {
+            inner: self.backend.open(&blob.key).await?,
+            hasher: Some(Sha256::new()),
+            blob: blob.clone(),
+        }))
+    }
+
+    /// Removes a blob from the store.
+    ///
+    /// # Arguments
+    /// * `blob` - The reference to the blob.
+    pub async fn delete(&self, blob: &BlobRef) -> io::Result<()> {
+        self.backend.delete(&blob.key).await
+    }
+}
+
+/// Returns the error reported for a blob whose contents do not match its hash.
+fn corrupt(blob: &BlobRef) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("blob {} is corrupt", blob.key),
+    )
+}
+
+/// A blob stream that hashes the contents as they pass and checks them at the end.
+struct Verified {
+    inner: BlobReader,
+    /// The running hash, taken once the end of the stream has been checked.
+    hasher: Option<Sha256>,
+    blob: BlobRef,
+}
+
+impl AsyncRead for Verified {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        let wants_data = buf.remaining() > 0;
+        ready!(this.inner.as_mut().poll_read(cx, buf))?;
+        let read = &buf.filled()[filled..];
+        match this.hasher.as_mut() {
+            Some(hasher) if !read.is_empty() => hasher.update(read),
+            // Reading nothing into a buffer with room left marks the end of the stream.
+            Some(_) if wants_data => {
+                let digest = this
+                    .hasher
+                    .take()
+                    .map(|hasher| hex::encode(hasher.finalize()));
+                if digest.as_ref() != Some(&this.blob.sha256) {
+                    return Poll::Ready(Err(corrupt(&this.blob)));
+                }
+            }
+            _ => {}
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store(name: &str) -> (BlobStore, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("xbim-blob-{}-{name}", std::process::id()));
+        (BlobStore::new(FsBackend::new(dir.clone())), dir)
+    }
+
+    #[rocket::async_test]
+    async fn test_put_deduplicates_by_hash() {
+        let (store, dir) = temp_store("dedup");
+        let first = store.put(b"ISO-10303-21;").await.unwrap();
+        let second = store.put(b"ISO-10303-21;").await.unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(first.size, 13);
+        assert_eq!(
+            first.key,
+            format!("sha256/{}/{}", &first.sha256[..2], first.sha256)
+        );
+        assert_eq!(store.get(&first).await.unwrap(), b"ISO-10303-21;");
+        let mut streamed = Vec::new();
+        store
+            .open(&first)
+            .await
+            .unwrap()
+            .read_to_end(&mut streamed)
+            .await
+            .unwrap();
+        assert_eq!(streamed, b"ISO-10303-21;");
+
+        store.delete(&first).await.unwrap();
+        assert!(store.get(&first).await.is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[rocket::async_test]
+    async fn test_put_file_matches_put() {
+        let (store, dir) = temp_store("file");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("upload.ifc");
+        std::fs::write(&path, b"ISO-10303-21;").unwrap();
+
+        let blob = store.put_file(&path).await.unwrap();
+        assert_eq!(blob, store.put(b"ISO-10303-21;").await.unwrap());
+        assert_eq!(store.get(&blob).await.unwrap(), b"ISO-10303-21;");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[rocket::async_test]
+    async fn test_get_rejects_corrupt_blob() {
+        let (store, dir) = temp_store("corrupt");
+        let blob = store.put(b"original").await.unwrap();
+        std::fs::write(dir.join(&blob.key), b"tampered").unwrap();
+
+        let error = store.get(&blob).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let mut streamed = Vec::new();
+        let error = store
+            .open(&blob)
+            .await
+            .unwrap()
+            .read_to_end(&mut streamed)
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
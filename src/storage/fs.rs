@@ -0,0 +1,81 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::storage::blob::{Backend, BlobReader};
+use rocket::async_trait;
+use rocket::tokio::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use surrealdb::sql::Uuid;
+
+/// A blob backend that keeps each blob as a file below a root directory.
+pub struct FsBackend {
+    root: PathBuf,
+}
+
+impl FsBackend {
+    /// Creates a filesystem backend.
+    ///
+    /// # Arguments
+    /// * `root` - The directory holding the blobs; it is created on first write.
+    ///
+    /// # Returns
+    /// A new `FsBackend` instance.
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// Returns the path of a key, rejecting keys that would escape the root.
+    fn path(&self, key: &str) -> io::Result<PathBuf> {
+        if key.is_empty() || key.split('/').any(|part| part.is_empty() || part == "..") {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid blob key {key:?}"),
+            ));
+        }
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl Backend for FsBackend {
+    async fn put(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        // Write to a temporary file first so readers never observe a partial blob.
+        let partial = path.with_extension(format!("partial-{}", Uuid::new()));
+        fs::write(&partial, data).await?;
+        fs::rename(&partial, &path).await
+    }
+
+    async fn put_file(&self, key: &str, source: &Path, _sha256: &str) -> io::Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let partial = path.with_extension(format!("partial-{}", Uuid::new()));
+        fs::copy(source, &partial).await?;
+        fs::rename(&partial, &path).await
+    }
+
+    async fn get(&self, key: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.path(key)?).await
+    }
+
+    async fn open(&self, key: &str) -> io::Result<BlobReader> {
+        Ok(Box::pin(fs::File::open(self.path(key)?).await?))
+    }
+
+    async fn exists(&self, key: &str) -> io::Result<bool> {
+        fs::try_exists(self.path(key)?).await
+    }
+
+    async fn delete(&self, key: &str) -> io::Result<()> {
+        match fs::remove_file(self.path(key)?).await {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
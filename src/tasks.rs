@@ -0,0 +1,58 @@
+#![warn(clippy::all)]
+#![forbid(unsafe_code)]
+
+use crate::config::Config;
+use crate::database::Database;
+use crate::guards::auth::purge_expired_sessions;
+use crate::routes::lock::purge_expired_locks;
+use crate::routes::resumable::purge_expired_uploads;
+use crate::routes::trash::purge_expired_trash;
+use crate::storage::blob::BlobStore;
+use rocket::fairing::AdHoc;
+use rocket::tokio::{self, time};
+use std::time::Duration;
+
+/// Interval between runs of the scheduled cleanup.
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Returns a fairing that periodically removes expired data once Rocket has launched.
+///
+/// # Returns
+/// The cleanup fairing.
+pub fn cleanup() -> AdHoc {
+    AdHoc::on_liftoff("Scheduled cleanup", |rocket| {
+        Box::pin(async move {
+            let (Some(database), Some(blobs), Some(config)) = (
+                rocket.state::<Database>(),
+                rocket.state::<BlobStore>(),
+                rocket.state::<Config>(),
+            ) else {
+                println!("Scheduled cleanup disabled: database, blobs or config not managed");
+                return;
+            };
+            let (database, blobs, config) = (database.clone(), blobs.clone(), config.clone());
+            tokio::spawn(async move {
+                let mut interval = time::interval(CLEANUP_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    let uploads = purge_expired_uploads(&database, &config).await;
+                    if uploads > 0 {
+                        println!("Removed {uploads} expired uploads");
+                    }
+                    let locks = purge_expired_locks(&database).await;
+                    if locks > 0 {
+                        println!("Removed {locks} expired locks");
+                    }
+                    let sessions = purge_expired_sessions(&database).await;
+                    if sessions > 0 {
+                        println!("Removed {sessions} expired sessions");
+                    }
+                    let trashed = purge_expired_trash(&database, &blobs, &config).await;
+                    if trashed > 0 {
+                        println!("Purged {trashed} models from the trash");
+                    }
+                }
+            });
+        })
+    })
+}
@@ -2,7 +2,17 @@
 #![forbid(unsafe_code)]
 
 use crate::config::Config;
+use argon2::{
+    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+    password_hash::{
+        SaltString,
+        rand_core::{OsRng, RngCore},
+    },
+};
 use colored::*;
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::Sha256;
 use std::env;
 use std::path::PathBuf;
 use surrealdb::Error;
@@ -26,6 +36,99 @@ impl Utils {
             .join(filename)
     }
 
+    /// Returns a random, URL-safe token.
+    ///
+    /// # Arguments
+    /// * `bytes` - The number of random bytes; the token is twice as long.
+    pub fn random_token(bytes: usize) -> String {
+        let mut token = vec![0u8; bytes];
+        OsRng.fill_bytes(&mut token);
+        hex::encode(token)
+    }
+
+    /// Signs a message with HMAC-SHA256.
+    ///
+    /// # Arguments
+    /// * `secret` - The signing key.
+    /// * `message` - The message to sign.
+    ///
+    /// # Returns
+    /// The signature as lowercase hex.
+    pub fn sign(secret: &str, message: &str) -> String {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key size");
+        mac.update(message.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    /// Checks an HMAC-SHA256 signature in constant time.
+    ///
+    /// # Arguments
+    /// * `secret` - The signing key.
+    /// * `message` - The signed message.
+    /// * `signature` - The signature as hex.
+    pub fn verify_signature(secret: &str, message: &str, signature: &str) -> bool {
+        let Ok(signature) = hex::decode(signature) else {
+            return false;
+        };
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key size");
+        mac.update(message.as_bytes());
+        mac.verify_slice(&signature).is_ok()
+    }
+
+    /// Hashes a password with Argon2id and a random salt.
+    ///
+    /// # Arguments
+    /// * `password` - The password to hash.
+    ///
+    /// # Returns
+    /// The hash in PHC string format.
+    pub fn hash_password(password: &str) -> String {
+        Argon2::default()
+            .hash_password(password.as_bytes(), &SaltString::generate(&mut OsRng))
+            .expect("Argon2 hashing with default parameters cannot fail")
+            .to_string()
+    }
+
+    /// Checks a password against a hash produced by `hash_password`.
+    ///
+    /// # Arguments
+    /// * `password` - The password to check.
+    /// * `hash` - The hash in PHC string format.
+    pub fn verify_password(password: &str, hash: &str) -> bool {
+        PasswordHash::new(hash).is_ok_and(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+    }
+
+    /// Applies a JSON Merge Patch (RFC 7396) to a document.
+    ///
+    /// # Arguments
+    /// * `target` - The document to modify in place.
+    /// * `patch` - The patch; `null` members remove the corresponding member of `target`.
+    pub fn merge_patch(target: &mut Value, patch: Value) {
+        let Value::Object(patch) = patch else {
+            *target = patch;
+            return;
+        };
+        if !target.is_object() {
+            *target = Value::Object(Default::default());
+        }
+        let Value::Object(members) = target else {
+            unreachable!();
+        };
+        for (key, value) in patch {
+            if value.is_null() {
+                members.remove(&key);
+            } else {
+                Self::merge_patch(members.entry(key).or_insert(Value::Null), value);
+            }
+        }
+    }
+
     /// Displays a formatted error message for database connection issues.
     ///
     /// # Arguments
@@ -91,3 +194,81 @@ impl Utils {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge_patch_rfc7396_examples() {
+        let cases = [
+            (json!({"a": "b"}), json!({"a": "c"}), json!({"a": "c"})),
+            (
+                json!({"a": "b"}),
+                json!({"b": "c"}),
+                json!({"a": "b", "b": "c"}),
+            ),
+            (json!({"a": "b"}), json!({"a": null}), json!({})),
+            (
+                json!({"a": "b", "b": "c"}),
+                json!({"a": null}),
+                json!({"b": "c"}),
+            ),
+            (json!({"a": ["b"]}), json!({"a": "c"}), json!({"a": "c"})),
+            (json!({"a": "c"}), json!({"a": ["b"]}), json!({"a": ["b"]})),
+            (
+                json!({"a": {"b": "c"}}),
+                json!({"a": {"b": "d", "c": null}}),
+                json!({"a": {"b": "d"}}),
+            ),
+            (
+                json!({"a": [{"b": "c"}]}),
+                json!({"a": [1]}),
+                json!({"a": [1]}),
+            ),
+            (json!(["a", "b"]), json!(["c", "d"]), json!(["c", "d"])),
+            (json!({"a": "b"}), json!(["c"]), json!(["c"])),
+            (json!({"a": "foo"}), json!(null), json!(null)),
+            (json!({"a": "foo"}), json!("bar"), json!("bar")),
+            (
+                json!({"e": null}),
+                json!({"a": 1}),
+                json!({"e": null, "a": 1}),
+            ),
+            (
+                json!([1, 2]),
+                json!({"a": "b", "c": null}),
+                json!({"a": "b"}),
+            ),
+            (
+                json!({}),
+                json!({"a": {"bb": {"ccc": null}}}),
+                json!({"a": {"bb": {}}}),
+            ),
+        ];
+        for (mut target, patch, expected) in cases {
+            Utils::merge_patch(&mut target, patch);
+            assert_eq!(target, expected);
+        }
+    }
+
+    #[test]
+    fn test_password_hash_round_trip() {
+        let hash = Utils::hash_password("correct horse");
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(Utils::verify_password("correct horse", &hash));
+        assert!(!Utils::verify_password("battery staple", &hash));
+        assert!(!Utils::verify_password("correct horse", "not a hash"));
+    }
+
+    #[test]
+    fn test_signature_round_trip() {
+        let signature = Utils::sign("secret", "message");
+        assert!(Utils::verify_signature("secret", "message", &signature));
+        assert!(!Utils::verify_signature("other", "message", &signature));
+        assert!(!Utils::verify_signature("secret", "tampered", &signature));
+        assert!(!Utils::verify_signature("secret", "message", "zz"));
+        assert_eq!(Utils::random_token(16).len(), 32);
+    }
+}